@@ -1,6 +1,11 @@
+use pdf_viewer::devices::DeviceRegistry;
 use pdf_viewer::routes::main_page::MainTemplate;
 use pdf_viewer::state::Pdf;
 
+use std::time::Duration;
+
+use tokio::{sync::mpsc, time::sleep};
+
 use crate::app::stateful_table::TableItem;
 
 #[derive(Debug)]
@@ -9,9 +14,9 @@ pub enum ApiError {
     InvalidResponse,
 }
 
-impl Into<String> for ApiError {
-    fn into(self) -> String {
-        match self {
+impl From<ApiError> for String {
+    fn from(error: ApiError) -> Self {
+        match error {
             ApiError::InvalidResponse => String::from("Failed to parse JSON response"),
             ApiError::NoConnection(s) => format!("Failed to connect to server at: \"{s}\""),
         }
@@ -22,6 +27,7 @@ pub struct ApiClient {
     connection_ip: String,
     pdf_list: Vec<Pdf>,
     read_history: (usize, usize, usize),
+    devices: DeviceRegistry,
 }
 
 impl ApiClient {
@@ -30,6 +36,7 @@ impl ApiClient {
             connection_ip: ip,
             pdf_list: vec![],
             read_history: (0, 0, 0),
+            devices: DeviceRegistry::default(),
         }
     }
 
@@ -45,12 +52,16 @@ impl ApiClient {
 
         self.pdf_list = template.pdfs().to_vec();
         self.read_history = (template.month(), template.week(), template.today());
+        self.devices = template.devices().clone();
 
         Ok(())
     }
 
     pub fn pdfs_as_table_item(&self) -> Vec<TableItem> {
-        self.pdf_list.iter().map(|p| (*p).clone().into()).collect()
+        self.pdf_list
+            .iter()
+            .map(|p| TableItem::new(p, &self.devices))
+            .collect()
     }
 
     pub fn connection_ip(&self) -> &str {
@@ -61,3 +72,22 @@ impl ApiClient {
         self.read_history
     }
 }
+
+/// Listens to the servers event stream and notifies `updates` whenever
+/// something changed, reconnecting if the connection drops.
+pub async fn listen_for_updates(ip: String, updates: mpsc::Sender<()>) {
+    let url = format!("{ip}/api/events");
+
+    loop {
+        if let Ok(mut response) = reqwest::get(&url).await {
+            while let Ok(Some(chunk)) = response.chunk().await {
+                // Keep-alive comments carry no data, anything else is a change
+                if chunk.windows(5).any(|w| w == b"data:") && updates.send(()).await.is_err() {
+                    return;
+                }
+            }
+        }
+
+        sleep(Duration::from_secs(5)).await;
+    }
+}
@@ -6,7 +6,7 @@ use ratatui::{
     Terminal,
 };
 use std::{io, process::Command, time::Duration};
-use tokio::time::sleep;
+use tokio::{sync::mpsc, time::sleep};
 
 pub mod stateful_table;
 use crate::api_client::ApiClient;
@@ -19,15 +19,18 @@ pub struct App {
     table: StatefulTable,
     client: ApiClient,
     popup: Option<(String, String)>,
+    /// Notified whenever the server reports a change.
+    updates: mpsc::Receiver<()>,
 }
 
 impl App {
-    pub fn new(api_client: ApiClient) -> Self {
+    pub fn new(api_client: ApiClient, updates: mpsc::Receiver<()>) -> Self {
         let items = api_client.pdfs_as_table_item();
         Self {
             table: StatefulTable::with_items(items),
             client: api_client,
             popup: None,
+            updates,
         }
     }
 
@@ -40,6 +43,8 @@ impl App {
         );
 
         // TODO: Dynamically get the browser instead.
+        // The browser outlives the TUI, there is nothing to wait for.
+        #[allow(clippy::zombie_processes)]
         Command::new("firefox")
             .args(url.split(" "))
             .spawn()
@@ -89,6 +94,21 @@ pub async fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io
             })?;
         }
 
+        // Drain the pending notifications, one refresh covers all of them
+        let mut changed = false;
+        while app.updates.try_recv().is_ok() {
+            changed = true;
+        }
+        if changed {
+            if let Err(api_error) = app.client.refresh().await {
+                app.popup = Some(("Error".into(), api_error.into()));
+            }
+        }
+
+        if !event::poll(Duration::from_millis(100))? {
+            continue;
+        }
+
         let key = if let Event::Key(key) = event::read()? {
             key
         } else {
@@ -12,16 +12,13 @@ use ratatui::{
 use super::App;
 
 pub async fn render_pdf_page<B: Backend>(
-    mut app: &mut App,
+    app: &mut App,
     terminal: &mut Terminal<B>,
 ) -> io::Result<()> {
     terminal.draw(|f| {
-        if f.size().width <= 70 {
-            // Terminal too small
-        } else if f.size().height < 10 {
-            // Terminal too small
-        } else {
-            ui(f, &mut app)
+        // Nothing is drawn while the terminal is too small
+        if f.size().width > 70 && f.size().height >= 10 {
+            ui(f, app)
         }
     })?;
     Ok(())
@@ -70,9 +67,10 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         Constraint::Min(6),
         Constraint::Min(7),
         Constraint::Min(19),
+        Constraint::Min(40),
     ];
 
-    let header_cells: Vec<Cell> = vec!["Title", "Page", "Total", "Last Access"]
+    let header_cells: Vec<Cell> = ["Title", "Page", "Total", "Last Access", "Last Read"]
         .iter()
         .enumerate()
         .map(|(i, c)| {
@@ -1,14 +1,14 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, fmt};
 
-use chrono::NaiveDateTime;
-use pdf_viewer::state::Pdf;
+use chrono::{DateTime, Local, NaiveDateTime};
+use pdf_viewer::{devices::DeviceRegistry, state::Pdf};
 use ratatui::widgets::TableState;
 
 impl StatefulTable {
     pub fn with_items(items: Vec<TableItem>) -> Self {
         let mut state = TableState::default();
 
-        if items.len() > 0 {
+        if !items.is_empty() {
             state.select(Some(0));
         }
 
@@ -57,7 +57,7 @@ impl StatefulTable {
     }
 
     pub fn next_header(&mut self) {
-        let len = self.items.get(0).map_or(4, |v| v.as_vec().len()) - 1;
+        let len = self.items.first().map_or(5, |v| v.as_vec().len()) - 1;
         if self.header_index < len {
             self.header_index += 1;
         }
@@ -95,15 +95,17 @@ pub enum SortDirection {
     Descending,
 }
 
-impl SortDirection {
-    pub fn to_string(&self) -> String {
+impl fmt::Display for SortDirection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Self::Ascending => String::from(" ▼"),
-            Self::Descending => String::from(" ▲"),
-            Self::None => String::new(),
+            Self::Ascending => f.write_str(" ▼"),
+            Self::Descending => f.write_str(" ▲"),
+            Self::None => Ok(()),
         }
     }
+}
 
+impl SortDirection {
     pub fn get_comparison(
         &self,
         index: usize,
@@ -123,6 +125,9 @@ impl SortDirection {
                         .unwrap_or_default();
                     first.cmp(&second)
                 },
+                4 => |a: &TableItem, b: &TableItem| -> Ordering {
+                    a.last_read_time.cmp(&b.last_read_time)
+                },
                 _ => unreachable!(),
             }),
             SortDirection::Descending => Some(match index {
@@ -138,6 +143,9 @@ impl SortDirection {
                         .unwrap_or_default();
                     second.cmp(&first)
                 },
+                4 => |a: &TableItem, b: &TableItem| -> Ordering {
+                    b.last_read_time.cmp(&a.last_read_time)
+                },
                 _ => unreachable!(),
             }),
         }
@@ -159,15 +167,26 @@ pub struct TableItem {
     cur_page: u16,
     total_pages: u16,
     last_access: String,
+    last_read: String,
+    last_read_time: Option<DateTime<Local>>,
 }
 
 impl TableItem {
+    pub fn new(p: &Pdf, devices: &DeviceRegistry) -> Self {
+        Self {
+            last_read: devices.describe_last_read(p).unwrap_or_default(),
+            last_read_time: p.last_read().map(|(_, position)| position.time),
+            ..p.clone().into()
+        }
+    }
+
     pub fn as_vec(&self) -> Vec<String> {
         vec![
             self.pdf_name.clone(),
             self.cur_page.to_string(),
             self.total_pages.to_string(),
             self.last_access.clone(),
+            self.last_read.clone(),
         ]
     }
 
@@ -186,6 +205,10 @@ impl TableItem {
     pub fn last_access(&self) -> &str {
         self.last_access.as_ref()
     }
+
+    pub fn last_read(&self) -> &str {
+        self.last_read.as_ref()
+    }
 }
 
 impl From<Pdf> for TableItem {
@@ -195,6 +218,8 @@ impl From<Pdf> for TableItem {
             cur_page: p.current_page(),
             total_pages: p.total_pages(),
             last_access: p.last_access().to_string(),
+            last_read: String::new(),
+            last_read_time: None,
         }
     }
 }
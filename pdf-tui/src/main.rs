@@ -7,7 +7,7 @@ use std::io;
 use ratatui::{backend::CrosstermBackend, Terminal};
 
 pub mod api_client;
-use api_client::ApiClient;
+use api_client::{listen_for_updates, ApiClient};
 
 pub mod app;
 
@@ -24,12 +24,15 @@ async fn main() {
     let mut terminal = Terminal::new(backend).unwrap();
 
     let ip = String::from("http://localhost:3000");
-    let mut api_client = ApiClient::new(ip);
+    let mut api_client = ApiClient::new(ip.clone());
+
+    let (update_sender, updates) = tokio::sync::mpsc::channel(16);
+    tokio::spawn(listen_for_updates(ip, update_sender));
 
     // Ignore potential issues on first refresh
     let _ = api_client.refresh().await;
 
-    let app = app::App::new(api_client);
+    let app = app::App::new(api_client, updates);
     let res = app::run_app(&mut terminal, app).await;
 
     // restore terminal
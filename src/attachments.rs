@@ -0,0 +1,164 @@
+// Files embedded in PDFs.
+//
+// Files are attached either to the whole document, through the
+// `EmbeddedFiles` name tree of the catalog, or to a spot on a page through a
+// file attachment annotation. Both point to a file specification whose `EF`
+// entry holds the file as a stream. Attachments are numbered in the order
+// they are found: the name tree first, then the pages in order.
+
+use std::collections::HashSet;
+
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    objects::{annotations, dict_entry, name_tree},
+    text::text_string,
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Attachment {
+    /// Number of the attachment, starting at 1.
+    pub number: u16,
+    /// File name, without any directories.
+    pub name: String,
+    pub description: Option<String>,
+    pub mime: String,
+    /// Size in bytes, if the PDF tells or the file is stored uncompressed.
+    pub size: Option<u64>,
+    /// The page the file is attached to, `None` for files attached to the whole document.
+    pub page: Option<u16>,
+}
+
+/// Every file embedded in `doc`.
+pub fn list(doc: &Document) -> Vec<Attachment> {
+    find(doc)
+        .into_iter()
+        .map(|(attachment, _)| attachment)
+        .collect()
+}
+
+/// Reads attachment `number`, counting from 1.
+pub fn read(doc: &Document, number: u16) -> Result<Option<(Attachment, Vec<u8>)>, String> {
+    let Some((attachment, stream)) = find(doc)
+        .into_iter()
+        .find(|(attachment, _)| attachment.number == number)
+    else {
+        return Ok(None);
+    };
+
+    let stream = doc
+        .get_object(stream)
+        .and_then(Object::as_stream)
+        .map_err(|e| format!("Failed to read {}: {e}", attachment.name))?;
+    let contents = match stream.dict.has(b"Filter") {
+        true => stream
+            .decompressed_content()
+            .map_err(|e| format!("Failed to decompress {}: {e}", attachment.name))?,
+        false => stream.content.clone(),
+    };
+    Ok(Some((attachment, contents)))
+}
+
+/// Every attachment of `doc` along with the id of the stream holding it.
+fn find(doc: &Document) -> Vec<(Attachment, ObjectId)> {
+    let mut specs: Vec<(Option<String>, &Dictionary, Option<u16>)> = vec![];
+
+    if let Some(tree) = doc
+        .catalog()
+        .ok()
+        .and_then(|c| dict_entry(doc, c, b"Names"))
+        .and_then(|names| dict_entry(doc, names, b"EmbeddedFiles"))
+    {
+        for (key, spec) in name_tree(doc, tree) {
+            if let Ok(spec) = doc.dereference(spec).and_then(|(_, o)| o.as_dict()) {
+                specs.push((text_string(key), spec, None));
+            }
+        }
+    }
+
+    for (number, page) in doc.get_pages() {
+        for annotation in annotations(doc, page) {
+            if annotation.get(b"Subtype").and_then(Object::as_name).ok() != Some(b"FileAttachment")
+            {
+                continue;
+            }
+            if let Some(spec) = dict_entry(doc, annotation, b"FS") {
+                specs.push((None, spec, Some(number as u16)));
+            }
+        }
+    }
+
+    // The same file can be attached to the document and a page
+    let mut seen = HashSet::new();
+    let mut attachments = vec![];
+    for (key, spec, page) in specs {
+        let Some(stream_id) = dict_entry(doc, spec, b"EF")
+            .and_then(|ef| ef.get(b"UF").or_else(|_| ef.get(b"F")).ok())
+            .and_then(|o| o.as_reference().ok())
+        else {
+            continue;
+        };
+        let Ok(stream) = doc.get_object(stream_id).and_then(Object::as_stream) else {
+            continue;
+        };
+        if !seen.insert(stream_id) {
+            continue;
+        }
+
+        let number = attachments.len() as u16 + 1;
+        let name = [b"UF".as_slice(), b"F"]
+            .iter()
+            .find_map(|k| spec.get(k).ok().and_then(text_string))
+            .or(key)
+            .and_then(|name| base_name(&name))
+            .unwrap_or_else(|| format!("attachment-{number}"));
+
+        let mime = stream
+            .dict
+            .get(b"Subtype")
+            .and_then(Object::as_name_str)
+            .ok()
+            .filter(|m| m.parse::<mime_guess::mime::Mime>().is_ok())
+            .map(String::from)
+            .unwrap_or_else(|| {
+                mime_guess::from_path(&name)
+                    .first_or_octet_stream()
+                    .to_string()
+            });
+
+        let size = stream
+            .dict
+            .get(b"Params")
+            .and_then(Object::as_dict)
+            .and_then(|p| p.get(b"Size"))
+            .and_then(Object::as_i64)
+            .ok()
+            .map(|s| s as u64)
+            .or_else(|| (!stream.dict.has(b"Filter")).then_some(stream.content.len() as u64));
+
+        attachments.push((
+            Attachment {
+                number,
+                name,
+                description: spec.get(b"Desc").ok().and_then(text_string),
+                mime,
+                size,
+                page,
+            },
+            stream_id,
+        ));
+    }
+
+    attachments
+}
+
+/// The last component of `path`, file specifications may use either kind of slash.
+fn base_name(path: &str) -> Option<String> {
+    path.rsplit(['/', '\\'])
+        .next()
+        .map(str::trim)
+        .filter(|name| !name.is_empty() && *name != "." && *name != "..")
+        .map(String::from)
+}
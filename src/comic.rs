@@ -0,0 +1,138 @@
+// Reading comic book archives (CBZ).
+//
+// A CBZ is a zip archive of images, one per page. There is no index file,
+// the pages are the images sorted by their path, with runs of digits compared
+// as numbers so `page2.jpg` comes before `page10.jpg`.
+
+use std::{
+    cmp::Ordering,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use zip::ZipArchive;
+
+/// The extensions of the files which count as pages.
+const IMAGE_EXTENSIONS: [&str; 7] = ["jpg", "jpeg", "png", "gif", "webp", "avif", "bmp"];
+/// Largest page read out of an archive, in bytes. The sizes in the index of
+/// the archive are not trusted for anything.
+const MAX_PAGE_SIZE: u64 = 64 * 1024 * 1024;
+
+#[derive(Clone, Debug)]
+pub struct Comic {
+    path: PathBuf,
+    /// Paths of the pages inside the archive, in reading order.
+    pub pages: Vec<String>,
+}
+
+impl Comic {
+    /// Reads the page list of the CBZ at `path` from the index of the archive.
+    pub fn open(path: &Path) -> Result<Comic, String> {
+        let file = File::open(path).map_err(|e| format!("Failed to open {path:?}: {e}"))?;
+        let archive =
+            ZipArchive::new(file).map_err(|e| format!("{path:?} is not a zip archive: {e}"))?;
+
+        let mut pages: Vec<String> = archive
+            .file_names()
+            .filter(|name| is_page(name))
+            .map(String::from)
+            .collect();
+        pages.sort_by(|a, b| natural_cmp(a, b));
+
+        if pages.is_empty() {
+            return Err(format!("{path:?} has no images"));
+        }
+
+        Ok(Comic {
+            path: path.to_path_buf(),
+            pages,
+        })
+    }
+
+    /// Reads the image of page `number`, counting from 1. Returns its path in the archive too.
+    pub fn read_page(&self, number: u16) -> Result<(&str, Vec<u8>), String> {
+        let name = self
+            .pages
+            .get((number as usize).wrapping_sub(1))
+            .ok_or_else(|| format!("{:?} has no page {number}", self.path))?;
+
+        let file =
+            File::open(&self.path).map_err(|e| format!("Failed to open {:?}: {e}", self.path))?;
+        let mut archive = ZipArchive::new(file).map_err(|e| e.to_string())?;
+        let entry = archive
+            .by_name(name)
+            .map_err(|e| format!("Failed to find {name}: {e}"))?;
+
+        let mut contents = vec![];
+        entry
+            .take(MAX_PAGE_SIZE + 1)
+            .read_to_end(&mut contents)
+            .map_err(|e| format!("Failed to read {name}: {e}"))?;
+        if contents.len() as u64 > MAX_PAGE_SIZE {
+            return Err(format!(
+                "{name} is larger than {} MiB",
+                MAX_PAGE_SIZE / 1024 / 1024
+            ));
+        }
+        Ok((name, contents))
+    }
+}
+
+/// Whether the entry `name` is an image, leaving out directories and the
+/// metadata files some archivers add (`__MACOSX/`, `.DS_Store`, ...).
+fn is_page(name: &str) -> bool {
+    if name.ends_with('/')
+        || name
+            .split('/')
+            .any(|p| p.starts_with('.') || p == "__MACOSX")
+    {
+        return false;
+    }
+    Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| IMAGE_EXTENSIONS.iter().any(|i| i.eq_ignore_ascii_case(e)))
+}
+
+/// Compares `a` and `b` ignoring case, with runs of digits compared by their value.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = take_number(&mut a);
+                let y = take_number(&mut b);
+                // Longer runs are larger once leading zeros are gone
+                let ordering = x
+                    .trim_start_matches('0')
+                    .len()
+                    .cmp(&y.trim_start_matches('0').len())
+                    .then_with(|| x.trim_start_matches('0').cmp(y.trim_start_matches('0')));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+fn take_number(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut number = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        number.push(c);
+    }
+    number
+}
@@ -0,0 +1,247 @@
+// Cover thumbnails of the books.
+//
+// Covers are taken from the book itself: the largest JPEG on the first page
+// of a PDF, the cover image an EPUB declares or the first page of a CBZ.
+// Extracting them means reading the whole book, so the scaled down result is
+// cached on disk next to the state, keyed by the path and modification time
+// of the book. Books without a cover get an empty marker file instead, so
+// they are not read again on every request either. Whenever a book changes
+// the entries for its older versions are removed.
+
+use std::{
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use image::{imageops::FilterType, ImageOutputFormat};
+use lopdf::{Dictionary, Document, Object};
+
+use crate::{comic::Comic, epub::Epub, state::DocumentKind};
+
+/// Largest width and height of a thumbnail, in pixels.
+pub const THUMBNAIL_SIZE: (u32, u32) = (300, 450);
+const JPEG_QUALITY: u8 = 80;
+
+/// Thumbnails are stored in a directory named after the state file.
+#[derive(Clone, Debug)]
+pub struct CoverCache {
+    dir: PathBuf,
+}
+
+impl CoverCache {
+    pub fn new(dir: PathBuf) -> Self {
+        CoverCache { dir }
+    }
+
+    pub fn location_for(state_location: &Path) -> PathBuf {
+        let mut path = state_location.as_os_str().to_owned();
+        path.push(".covers");
+        PathBuf::from(path)
+    }
+
+    /// Gets the JPEG thumbnail of the book at `path`, extracting it if it is not cached yet.
+    ///
+    /// Returns `Ok(None)` for books without a cover.
+    pub fn thumbnail(&self, path: &Path) -> Result<Option<Vec<u8>>, String> {
+        let key = cache_key(path)?;
+        let cached = self.dir.join(format!("{key}.jpg"));
+        let missing = self.dir.join(format!("{key}.none"));
+
+        if let Ok(thumbnail) = fs::read(&cached) {
+            return Ok(Some(thumbnail));
+        }
+        if missing.exists() {
+            return Ok(None);
+        }
+
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create {:?}: {e}", self.dir))?;
+        // Covers which cannot be decoded are treated like missing ones
+        let thumbnail = match extract_cover(path)?.map(|cover| scale_down(&cover)) {
+            Some(Ok(thumbnail)) => thumbnail,
+            result => {
+                match result {
+                    Some(Err(e)) => tracing::warn!("Ignoring the cover of {path:?}: {e}"),
+                    _ => tracing::debug!("{path:?} has no cover"),
+                }
+                fs::write(&missing, b"")
+                    .map_err(|e| format!("Failed to write {missing:?}: {e}"))?;
+                prune_cache(&self.dir, &key);
+                return Ok(None);
+            }
+        };
+
+        // Written under another name first so readers never see half a file
+        let temp = self.dir.join(format!("{key}.part"));
+        fs::write(&temp, &thumbnail)
+            .and_then(|_| fs::rename(&temp, &cached))
+            .map_err(|e| format!("Failed to write {cached:?}: {e}"))?;
+        prune_cache(&self.dir, &key);
+        Ok(Some(thumbnail))
+    }
+}
+
+/// Identifies one version of the file at `path`, so caches notice when a book changes.
+///
+/// The key is a hash of the path and one of the modification time joined by
+/// a `-`, so the entries of every version of a book share a prefix.
+pub fn cache_key(path: &Path) -> Result<String, String> {
+    let modified = fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map_err(|e| format!("Failed to read {path:?}: {e}"))?;
+    let since_epoch = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+    Ok(format!(
+        "{}-{:x}",
+        book_key(path),
+        md5::compute(since_epoch.as_nanos().to_string())
+    ))
+}
+
+/// The part of `cache_key` which stays the same while the book at `path` changes.
+fn book_key(path: &Path) -> String {
+    format!("{:x}", md5::compute(path.display().to_string()))
+}
+
+/// Removes every entry of the cache in `dir` made for the book at `path`.
+pub fn remove_cached(dir: &Path, path: &Path) {
+    let book = format!("{}-", book_key(path));
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        if entry.file_name().to_string_lossy().starts_with(&book) {
+            if let Err(e) = fs::remove_file(entry.path()) {
+                tracing::warn!("Failed to remove {:?} from the cache: {e}", entry.path());
+            }
+        }
+    }
+}
+
+/// Removes the entries of the cache in `dir` made for other versions of the book `key` is for.
+///
+/// Entries named without a book part are left over from older versions of
+/// the server and are removed as well.
+pub fn prune_cache(dir: &Path, key: &str) {
+    let Some((book, _)) = key.split_once('-') else {
+        return;
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let stale = match name.split_once('-') {
+            Some((other, _)) => other == book && !name.starts_with(key),
+            None => true,
+        };
+        if stale {
+            if let Err(e) = fs::remove_file(entry.path()) {
+                tracing::warn!("Failed to remove {:?} from the cache: {e}", entry.path());
+            }
+        }
+    }
+}
+
+/// The encoded cover image of the book at `path`.
+fn extract_cover(path: &Path) -> Result<Option<Vec<u8>>, String> {
+    match DocumentKind::from_path(path) {
+        Some(DocumentKind::Cbz) => Comic::open(path)?
+            .read_page(1)
+            .map(|(_, image)| Some(image)),
+        Some(DocumentKind::Epub) => {
+            let epub = Epub::open(path)?;
+            epub.cover
+                .as_deref()
+                .map(|cover| epub.read_file(cover))
+                .transpose()
+        }
+        _ => pdf_cover(path),
+    }
+}
+
+/// The largest JPEG drawn on the first page of the PDF at `path`.
+///
+/// Only JPEGs are considered, as they can be decoded without knowing anything
+/// about the color spaces of the PDF. That covers most scanned books and
+/// covers made by publishers.
+fn pdf_cover(path: &Path) -> Result<Option<Vec<u8>>, String> {
+    let doc = Document::load(path).map_err(|e| format!("Failed to read PDF at {path:?}: {e}"))?;
+    let Some(page) = doc.get_pages().get(&1).copied() else {
+        return Ok(None);
+    };
+
+    let (inline, referenced) = doc.get_page_resources(page);
+    let resources = inline.into_iter().chain(
+        referenced
+            .into_iter()
+            .filter_map(|id| doc.get_dictionary(id).ok()),
+    );
+
+    let mut largest: Option<(i64, &[u8])> = None;
+    for resources in resources {
+        let Ok(xobjects) = resources
+            .get(b"XObject")
+            .and_then(|o| doc.dereference(o))
+            .and_then(|(_, o)| o.as_dict())
+        else {
+            continue;
+        };
+
+        for (_, xobject) in xobjects.iter() {
+            let Ok(stream) = doc.dereference(xobject).and_then(|(_, o)| o.as_stream()) else {
+                continue;
+            };
+            if !is_jpeg(&stream.dict) {
+                continue;
+            }
+
+            let dimension = |key| stream.dict.get(key).and_then(Object::as_i64).unwrap_or(0);
+            let area = dimension(b"Width") * dimension(b"Height");
+            if largest.is_none_or(|(a, _)| area > a) {
+                largest = Some((area, &stream.content));
+            }
+        }
+    }
+
+    Ok(largest.map(|(_, jpeg)| jpeg.to_vec()))
+}
+
+/// Whether the image XObject `dict` holds a plain JPEG.
+fn is_jpeg(dict: &Dictionary) -> bool {
+    let is_image = dict
+        .get(b"Subtype")
+        .and_then(Object::as_name)
+        .is_ok_and(|s| s == b"Image");
+
+    let filter = match dict.get(b"Filter") {
+        Ok(Object::Name(name)) => Some(name.as_slice()),
+        // Only a single filter, JPEGs which are compressed again are rare
+        Ok(Object::Array(filters)) if filters.len() == 1 => filters[0].as_name().ok(),
+        _ => None,
+    };
+
+    is_image && filter == Some(b"DCTDecode")
+}
+
+/// Decodes `image` and encodes it as a JPEG of at most `THUMBNAIL_SIZE`.
+pub fn scale_down(image: &[u8]) -> Result<Vec<u8>, String> {
+    let image =
+        image::load_from_memory(image).map_err(|e| format!("Failed to decode cover: {e}"))?;
+    let (width, height) = THUMBNAIL_SIZE;
+    let thumbnail = match image.width() > width || image.height() > height {
+        true => image.resize(width, height, FilterType::Triangle),
+        false => image,
+    };
+
+    let mut jpeg = Cursor::new(vec![]);
+    thumbnail
+        .to_rgb8()
+        .write_to(&mut jpeg, ImageOutputFormat::Jpeg(JPEG_QUALITY))
+        .map_err(|e| format!("Failed to encode thumbnail: {e}"))?;
+    Ok(jpeg.into_inner())
+}
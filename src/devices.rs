@@ -0,0 +1,185 @@
+use std::{collections::HashMap, sync::Arc};
+
+use chrono::{DateTime, Local};
+use http::{header, HeaderMap, HeaderValue};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::{
+    journal::{JournalEvent, WrappedJournal},
+    state::Pdf,
+};
+
+/// Header a client can use to identify itself, e.g. the TUI or scripts.
+pub const DEVICE_ID_HEADER: &str = "x-device-id";
+/// Header a client can use to give itself a name, defaults to one guessed from the user agent.
+pub const DEVICE_NAME_HEADER: &str = "x-device-name";
+/// Cookie the browsers get their device id stored in.
+pub const DEVICE_COOKIE: &str = "device_id";
+
+pub type WrappedDeviceRegistry = Arc<Mutex<DeviceRegistry>>;
+
+/// Every device which has turned a page, by id.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DeviceRegistry {
+    pub devices: HashMap<String, String>,
+}
+
+impl DeviceRegistry {
+    pub fn wrapped(self) -> WrappedDeviceRegistry {
+        Arc::new(Mutex::new(self))
+    }
+
+    /// Remembers the device `id` as `name`.
+    /// Returns true if this was news to the registry.
+    pub fn register(&mut self, id: &str, name: &str) -> bool {
+        if self.devices.get(id).map(String::as_str) == Some(name) {
+            return false;
+        }
+        self.devices.insert(id.to_string(), name.to_string());
+        true
+    }
+
+    /// The name of the device `id`, falls back to the id for unknown devices.
+    pub fn name<'a>(&'a self, id: &'a str) -> &'a str {
+        self.devices.get(id).map_or(id, String::as_str)
+    }
+
+    /// Describes who last moved `pdf`, like "last read on phone, 2h ago, page 120".
+    pub fn describe_last_read(&self, pdf: &Pdf) -> Option<String> {
+        let (id, position) = pdf.last_read()?;
+        Some(format!(
+            "last read on {}, {}, page {}",
+            self.name(id),
+            ago(position.time),
+            position.page
+        ))
+    }
+}
+
+/// Who is making a request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceIdentity {
+    pub id: String,
+    pub name: String,
+}
+
+impl DeviceIdentity {
+    /// Identifies the device a request came from by the `x-device-id` header
+    /// or the `device_id` cookie, `None` for anonymous clients.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let id = headers
+            .get(DEVICE_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from)
+            .or_else(|| device_cookie(headers))?;
+
+        let name = headers
+            .get(DEVICE_NAME_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from)
+            .unwrap_or_else(|| guess_name(headers));
+
+        Some(DeviceIdentity { id, name })
+    }
+}
+
+/// Identifies the device a request came from and adds it to the registry if
+/// it is new, returns its id.
+///
+/// Locks `devices` and `journal`, so callers holding the `PdfCollection` lock
+/// keep to the pdfs -> devices -> journal order.
+pub async fn register_device(
+    headers: &HeaderMap,
+    devices: &WrappedDeviceRegistry,
+    journal: &WrappedJournal,
+) -> Option<String> {
+    let identity = DeviceIdentity::from_headers(headers)?;
+
+    let mut registry = devices.lock().await;
+    if registry.register(&identity.id, &identity.name) {
+        tracing::info!("Registered device {} as {}", identity.id, identity.name);
+        journal
+            .lock()
+            .await
+            .record(JournalEvent::Device {
+                id: identity.id.clone(),
+                name: identity.name,
+            })
+            .await;
+    }
+
+    Some(identity.id)
+}
+
+/// Creates a new random device id.
+pub fn new_device_id() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(16)
+        .map(char::from)
+        .collect()
+}
+
+/// The `Set-Cookie` header handing a browser the device id `id`.
+pub fn device_cookie_header(id: &str) -> HeaderValue {
+    // Roughly ten years, the id is only forgotten if the browser forgets it.
+    HeaderValue::from_str(&format!(
+        "{DEVICE_COOKIE}={id}; Path=/; Max-Age=315360000; SameSite=Lax"
+    ))
+    .unwrap()
+}
+
+fn device_cookie(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|c| c.trim().split_once('='))
+        .find(|(k, _)| *k == DEVICE_COOKIE)
+        .map(|(_, v)| v.to_string())
+}
+
+/// Guesses a human friendly name for a device from its user agent.
+fn guess_name(headers: &HeaderMap) -> String {
+    let ua = headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+
+    let kind = if ua.contains("iPad") || ua.contains("Tablet") {
+        "tablet"
+    } else if ["Android", "iPhone", "Mobile"]
+        .iter()
+        .any(|m| ua.contains(m))
+    {
+        "phone"
+    } else if ua.contains("Mozilla") {
+        "desktop"
+    } else {
+        ua.split('/').next().unwrap_or_default()
+    };
+
+    if kind.is_empty() {
+        String::from("unknown device")
+    } else {
+        kind.to_string()
+    }
+}
+
+/// Describes how long ago `time` was, like "2h ago".
+pub fn ago(time: DateTime<Local>) -> String {
+    let since = Local::now() - time;
+
+    if since.num_minutes() < 1 {
+        String::from("just now")
+    } else if since.num_hours() < 1 {
+        format!("{}m ago", since.num_minutes())
+    } else if since.num_days() < 1 {
+        format!("{}h ago", since.num_hours())
+    } else {
+        format!("{}d ago", since.num_days())
+    }
+}
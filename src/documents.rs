@@ -0,0 +1,56 @@
+// Parsed PDFs shared between requests.
+//
+// The endpoints which look into a PDF (its text, links, page geometry, outline
+// and so on) are mostly asked about one page at a time, and the viewer asks
+// about every page it shows. Parsing the whole file for each of those requests
+// makes large books crawl, so the last few parsed documents are kept in memory,
+// keyed by `covers::cache_key` so a changed file is parsed again.
+
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use lopdf::Document;
+
+use crate::covers::cache_key;
+
+/// How many parsed documents are kept around.
+pub const CACHED_DOCUMENTS: usize = 4;
+
+/// Parsed documents by key, the most recently used last.
+type Documents = Vec<(String, Arc<Document>)>;
+
+#[derive(Clone, Debug, Default)]
+pub struct DocumentCache {
+    documents: Arc<Mutex<Documents>>,
+}
+
+impl DocumentCache {
+    /// Gets the parsed PDF at `path`, parsing it if it is not cached.
+    pub fn get(&self, path: &Path) -> Result<Arc<Document>, String> {
+        let key = cache_key(path)?;
+        {
+            let mut documents = self.documents.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(i) = documents.iter().position(|(k, _)| *k == key) {
+                let entry = documents.remove(i);
+                let doc = entry.1.clone();
+                documents.push(entry);
+                return Ok(doc);
+            }
+        }
+
+        // Parsed without holding the lock, so other books can be served meanwhile
+        let doc = Arc::new(
+            Document::load(path).map_err(|e| format!("Failed to read PDF at {path:?}: {e}"))?,
+        );
+
+        let mut documents = self.documents.lock().unwrap_or_else(|e| e.into_inner());
+        documents.retain(|(k, _)| *k != key);
+        documents.push((key, doc.clone()));
+        if documents.len() > CACHED_DOCUMENTS {
+            documents.remove(0);
+        }
+        Ok(doc)
+    }
+}
@@ -0,0 +1,269 @@
+// Reading EPUBs.
+//
+// An EPUB is a zip archive, `META-INF/container.xml` points to the OPF package
+// document which lists every file (the manifest) and the reading order (the
+// spine). Every spine item is one chapter, chapters are served as they are
+// and the browser does the reflowing.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use percent_encoding::percent_decode_str;
+use roxmltree::{Document, Node, ParsingOptions};
+use zip::ZipArchive;
+
+const CONTAINER: &str = "META-INF/container.xml";
+/// Largest file read out of an archive, in bytes. The sizes in the index of
+/// the archive cannot be trusted, they are whatever the uploader wrote there.
+const MAX_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// A chapter of an EPUB, one item of the spine.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chapter {
+    /// Path of the chapter inside the archive.
+    pub file: String,
+    pub title: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct Epub {
+    path: PathBuf,
+    pub title: Option<String>,
+    pub chapters: Vec<Chapter>,
+    /// Path of the cover image inside the archive, if the book declares one.
+    pub cover: Option<String>,
+}
+
+impl Epub {
+    /// Reads the table of contents of the EPUB at `path`.
+    pub fn open(path: &Path) -> Result<Epub, String> {
+        let mut archive = open_archive(path)?;
+
+        let container = read_text(&mut archive, CONTAINER)?;
+        let opf_path = parse_xml(&container)?
+            .descendants()
+            .find(|n| n.has_tag_name("rootfile"))
+            .and_then(|n| n.attribute("full-path"))
+            .map(String::from)
+            .ok_or_else(|| format!("{path:?} has no rootfile in its container"))?;
+
+        let opf = read_text(&mut archive, &opf_path)?;
+        let package = parse_xml(&opf)?;
+        let base = parent(&opf_path);
+
+        // id -> (path in the archive, properties)
+        let manifest: HashMap<&str, (String, &str)> = package
+            .descendants()
+            .filter(|n| n.has_tag_name("item"))
+            .filter_map(|n| {
+                Some((
+                    n.attribute("id")?,
+                    (
+                        resolve(base, n.attribute("href")?),
+                        n.attribute("properties").unwrap_or_default(),
+                    ),
+                ))
+            })
+            .collect();
+
+        let spine = package
+            .descendants()
+            .find(|n| n.has_tag_name("spine"))
+            .ok_or_else(|| format!("{path:?} has no spine"))?;
+
+        let toc = manifest
+            .values()
+            .find(|(_, properties)| properties.split_whitespace().any(|p| p == "nav"))
+            .map(|(file, _)| (file.clone(), false))
+            .or_else(|| {
+                let ncx = spine.attribute("toc")?;
+                manifest.get(ncx).map(|(file, _)| (file.clone(), true))
+            })
+            .map(|(file, is_ncx)| chapter_titles(&mut archive, &file, is_ncx))
+            .unwrap_or_default();
+
+        let chapters: Vec<Chapter> = spine
+            .children()
+            .filter(|n| n.has_tag_name("itemref"))
+            .filter_map(|n| manifest.get(n.attribute("idref")?))
+            .enumerate()
+            .map(|(i, (file, _))| Chapter {
+                title: toc
+                    .get(file)
+                    .cloned()
+                    .unwrap_or_else(|| format!("Chapter {}", i + 1)),
+                file: file.clone(),
+            })
+            .collect();
+
+        if chapters.is_empty() {
+            return Err(format!("{path:?} has no chapters"));
+        }
+
+        let title = package
+            .descendants()
+            .find(|n| n.has_tag_name("title"))
+            .and_then(|n| n.text())
+            .map(|t| t.trim().to_string());
+
+        // EPUB 3 marks the cover in the manifest, EPUB 2 through a `meta` element
+        let cover = manifest
+            .values()
+            .find(|(_, properties)| properties.split_whitespace().any(|p| p == "cover-image"))
+            .or_else(|| {
+                let id = package
+                    .descendants()
+                    .find(|n| n.has_tag_name("meta") && n.attribute("name") == Some("cover"))?
+                    .attribute("content")?;
+                manifest.get(id)
+            })
+            .map(|(file, _)| file.clone());
+
+        Ok(Epub {
+            path: path.to_path_buf(),
+            title,
+            chapters,
+            cover,
+        })
+    }
+
+    /// Reads the file `name` from the archive, e.g. a chapter or an image.
+    pub fn read_file(&self, name: &str) -> Result<Vec<u8>, String> {
+        let mut archive = open_archive(&self.path)?;
+        read_bytes(&mut archive, name)
+    }
+}
+
+fn open_archive(path: &Path) -> Result<ZipArchive<File>, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {path:?}: {e}"))?;
+    ZipArchive::new(file).map_err(|e| format!("{path:?} is not a zip archive: {e}"))
+}
+
+fn read_bytes(archive: &mut ZipArchive<File>, name: &str) -> Result<Vec<u8>, String> {
+    let file = archive
+        .by_name(name)
+        .map_err(|e| format!("Failed to find {name}: {e}"))?;
+    let mut contents = vec![];
+    file.take(MAX_FILE_SIZE + 1)
+        .read_to_end(&mut contents)
+        .map_err(|e| format!("Failed to read {name}: {e}"))?;
+    if contents.len() as u64 > MAX_FILE_SIZE {
+        return Err(format!(
+            "{name} is larger than {} MiB",
+            MAX_FILE_SIZE / 1024 / 1024
+        ));
+    }
+    Ok(contents)
+}
+
+fn read_text(archive: &mut ZipArchive<File>, name: &str) -> Result<String, String> {
+    String::from_utf8(read_bytes(archive, name)?).map_err(|e| format!("{name} is not UTF-8: {e}"))
+}
+
+fn parse_xml(text: &str) -> Result<Document<'_>, String> {
+    let options = ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    Document::parse_with_options(text, options).map_err(|e| e.to_string())
+}
+
+/// The titles the table of contents gives the files of the book.
+///
+/// The EPUB 3 navigation document and the EPUB 2 NCX are both understood,
+/// files which are listed more than once get their first title.
+fn chapter_titles(
+    archive: &mut ZipArchive<File>,
+    toc_file: &str,
+    is_ncx: bool,
+) -> HashMap<String, String> {
+    let mut titles = HashMap::new();
+    let Ok(text) = read_text(archive, toc_file) else {
+        return titles;
+    };
+    let Ok(toc) = parse_xml(&text) else {
+        tracing::debug!("Could not parse the table of contents {toc_file}");
+        return titles;
+    };
+    let base = parent(toc_file);
+
+    let entries: Vec<(&str, String)> = if is_ncx {
+        toc.descendants()
+            .filter(|n| n.has_tag_name("navPoint"))
+            .filter_map(|point| {
+                let label = point
+                    .children()
+                    .find(|n| n.has_tag_name("navLabel"))
+                    .map(text_of)?;
+                let src = point
+                    .children()
+                    .find(|n| n.has_tag_name("content"))?
+                    .attribute("src")?;
+                Some((src, label))
+            })
+            .collect()
+    } else {
+        let nav = toc
+            .descendants()
+            .filter(|n| n.has_tag_name("nav"))
+            .find(|n| {
+                n.attributes()
+                    .any(|a| a.name() == "type" && a.value() == "toc")
+            })
+            .or_else(|| toc.descendants().find(|n| n.has_tag_name("nav")));
+
+        nav.into_iter()
+            .flat_map(|nav| nav.descendants())
+            .filter(|n| n.has_tag_name("a"))
+            .filter_map(|a| Some((a.attribute("href")?, text_of(a))))
+            .collect()
+    };
+
+    for (href, title) in entries {
+        if !title.is_empty() {
+            titles.entry(resolve(base, href)).or_insert(title);
+        }
+    }
+    titles
+}
+
+/// All the text below `node`, with whitespace collapsed.
+fn text_of(node: Node) -> String {
+    let text: Vec<&str> = node
+        .descendants()
+        .filter(|n| n.is_text())
+        .filter_map(|n| n.text())
+        .flat_map(str::split_whitespace)
+        .collect();
+    text.join(" ")
+}
+
+/// The directory `file` is in, with a trailing `/` unless it is the root.
+fn parent(file: &str) -> &str {
+    file.rfind('/').map_or("", |i| &file[..=i])
+}
+
+/// Resolves the link `href` found in a file in `base` to a path in the archive.
+pub fn resolve(base: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or_default();
+    let href = percent_decode_str(href).decode_utf8_lossy();
+
+    let mut parts: Vec<&str> = match href.starts_with('/') {
+        true => vec![],
+        false => base.split('/').filter(|p| !p.is_empty()).collect(),
+    };
+    for part in href.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
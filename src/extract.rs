@@ -0,0 +1,319 @@
+// Taking a range of pages out of a PDF into a PDF of its own.
+//
+// The pages are moved under a new page tree along with everything they
+// inherited from the old one, the other pages are deleted and whatever only
+// they used is dropped. Named destinations are not carried over, so outline
+// items and links leading to the kept pages are pointed at them directly and
+// the ones leading anywhere else are left out.
+
+use std::{collections::HashSet, ops::RangeInclusive};
+
+use lopdf::{dictionary, Document, Object, ObjectId};
+
+use crate::{
+    geometry::inherited,
+    links::{Destination, Resolver},
+    objects::{dict_entry, MAX_DEPTH},
+    outline::{self, Item},
+};
+
+/// Page attributes which can be inherited from the page tree.
+const INHERITABLE: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Parts of the catalog which refer to pages by number or are not worth fixing up.
+const DROPPED: [&[u8]; 7] = [
+    b"Dests",
+    b"OpenAction",
+    b"PageLabels",
+    b"StructTreeRoot",
+    b"MarkInfo",
+    b"Threads",
+    b"AcroForm",
+];
+
+/// An outline item which stays, with where it goes.
+struct Kept {
+    id: ObjectId,
+    destination: Destination,
+    open: bool,
+    children: Vec<Kept>,
+}
+
+/// Writes pages `first` to `last` of `doc`, counting from 1, as a PDF.
+pub fn extract(mut doc: Document, first: u16, last: u16) -> Result<Vec<u8>, String> {
+    let pages = doc.get_pages();
+    if first == 0 || first > last || last as usize > pages.len() {
+        return Err(format!(
+            "Pages {first} to {last} are not in a book with {} pages",
+            pages.len()
+        ));
+    }
+    let range = first..=last;
+    let kept: Vec<ObjectId> = range.clone().map(|n| pages[&(n as u32)]).collect();
+    let new_dest = |destination| dest_array(&kept, first, destination);
+
+    let outline = keep_items(outline::items(&doc), &range);
+    let (annotations, repointed) = keep_links(&doc, &kept, &range);
+    let attributes: Vec<(ObjectId, &[u8], Object)> = kept
+        .iter()
+        .flat_map(|&page| INHERITABLE.iter().map(move |&key| (page, key)))
+        .filter(|&(page, key)| doc.get_dictionary(page).is_ok_and(|p| !p.has(key)))
+        .filter_map(|(page, key)| Some((page, key, inherited(&doc, page, key)?.clone())))
+        .collect();
+    let page_tree = page_tree_nodes(&doc, pages.values().copied());
+
+    for (page, key, value) in attributes {
+        if let Ok(page) = doc.get_dictionary_mut(page) {
+            page.set(key, value);
+        }
+    }
+    for (page, annots) in annotations {
+        if let Ok(page) = doc.get_dictionary_mut(page) {
+            page.set("Annots", annots);
+        }
+    }
+    for (annotation, destination) in repointed {
+        if let Ok(annotation) = doc.get_dictionary_mut(annotation) {
+            annotation.remove(b"A");
+            annotation.set("Dest", new_dest(destination));
+        }
+    }
+
+    // The old page tree goes along with the other pages, links from what is
+    // kept to any of them are left pointing nowhere, which readers ignore
+    for id in pages.values().chain(&page_tree) {
+        if !kept.contains(id) {
+            doc.objects.remove(id);
+        }
+    }
+    let pages_id = doc.add_object(dictionary! {
+        "Type" => "Pages",
+        "Kids" => kept.iter().map(|&id| id.into()).collect::<Vec<Object>>(),
+        "Count" => kept.len() as i64,
+    });
+    for &page in &kept {
+        if let Ok(page) = doc.get_dictionary_mut(page) {
+            page.set("Parent", pages_id);
+            // Article threads are dropped with the catalog entry
+            page.remove(b"B");
+        }
+    }
+
+    let root = doc.new_object_id();
+    let outlines =
+        write_items(&mut doc, &outline, root, &new_dest).map(|(visible, first, last)| {
+            doc.objects.insert(
+                root,
+                Object::Dictionary(dictionary! {
+                    "Type" => "Outlines",
+                    "First" => first,
+                    "Last" => last,
+                    "Count" => visible,
+                }),
+            );
+            root
+        });
+
+    let names = doc
+        .catalog()
+        .ok()
+        .and_then(|c| c.get(b"Names").ok())
+        .and_then(|n| n.as_reference().ok());
+    let catalog = doc
+        .catalog_mut()
+        .map_err(|e| format!("The book has no catalog: {e}"))?;
+    catalog.set("Pages", pages_id);
+    match outlines {
+        Some(outlines) => catalog.set("Outlines", outlines),
+        None => {
+            catalog.remove(b"Outlines");
+        }
+    }
+    for key in DROPPED {
+        catalog.remove(key);
+    }
+    if let Ok(Object::Dictionary(names)) = catalog.get_mut(b"Names") {
+        names.remove(b"Dests");
+    }
+    if let Some(names) = names.and_then(|id| doc.get_dictionary_mut(id).ok()) {
+        names.remove(b"Dests");
+    }
+
+    doc.prune_objects();
+    let mut pdf = vec![];
+    doc.save_to(&mut pdf)
+        .map_err(|e| format!("Failed to write pages {first} to {last}: {e}"))?;
+    Ok(pdf)
+}
+
+/// The outline items going into `range`, the children of the others take their place.
+fn keep_items(items: Vec<Item>, range: &RangeInclusive<u16>) -> Vec<Kept> {
+    let mut kept = vec![];
+    for item in items {
+        let children = keep_items(item.children, range);
+        match item.destination.filter(|d| range.contains(&d.page)) {
+            Some(destination) => kept.push(Kept {
+                id: item.id,
+                destination,
+                open: item.open,
+                children,
+            }),
+            None => kept.extend(children),
+        }
+    }
+    kept
+}
+
+/// The new annotations of `pages` without the links out of `range`, and the
+/// link annotations to point at their destination directly.
+#[allow(clippy::type_complexity)]
+fn keep_links(
+    doc: &Document,
+    pages: &[ObjectId],
+    range: &RangeInclusive<u16>,
+) -> (Vec<(ObjectId, Vec<Object>)>, Vec<(ObjectId, Destination)>) {
+    let resolver = Resolver::new(doc);
+    let mut annotations = vec![];
+    let mut repointed = vec![];
+
+    for &page in pages {
+        let Ok(annots) = doc
+            .get_dictionary(page)
+            .and_then(|p| p.get(b"Annots"))
+            .and_then(|o| doc.dereference(o))
+            .and_then(|(_, o)| o.as_array())
+        else {
+            continue;
+        };
+
+        let mut kept = vec![];
+        for annot in annots {
+            let Ok((id, annotation)) = doc
+                .dereference(annot)
+                .and_then(|(id, o)| Ok((id, o.as_dict()?)))
+            else {
+                continue;
+            };
+            let goes_to = annotation.has(b"Dest")
+                || dict_entry(doc, annotation, b"A")
+                    .and_then(|a| a.get(b"S").and_then(Object::as_name).ok())
+                    == Some(b"GoTo");
+            let is_link =
+                annotation.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Link");
+            if !(is_link && goes_to) {
+                kept.push(annot.clone());
+                continue;
+            }
+
+            let Some(destination) = resolver
+                .target(annotation)
+                .filter(|d| range.contains(&d.page))
+            else {
+                continue;
+            };
+            match id {
+                Some(id) => {
+                    kept.push(annot.clone());
+                    repointed.push((id, destination));
+                }
+                // Annotations written into the array are changed in place
+                None => {
+                    let mut annotation = annotation.clone();
+                    annotation.remove(b"A");
+                    annotation.set("Dest", dest_array(pages, *range.start(), destination));
+                    kept.push(annotation.into());
+                }
+            }
+        }
+        annotations.push((page, kept));
+    }
+
+    (annotations, repointed)
+}
+
+/// `destination` as an array going to one of `pages`, which start at page `first`.
+fn dest_array(pages: &[ObjectId], first: u16, destination: Destination) -> Object {
+    let top = destination.top.map_or(Object::Null, Object::Real);
+    vec![
+        pages[(destination.page - first) as usize].into(),
+        "XYZ".into(),
+        Object::Null,
+        top,
+        Object::Null,
+    ]
+    .into()
+}
+
+/// The ids of the nodes above `pages` in the page tree.
+fn page_tree_nodes(doc: &Document, pages: impl Iterator<Item = ObjectId>) -> HashSet<ObjectId> {
+    let mut nodes = HashSet::new();
+    for page in pages {
+        let mut node = page;
+        for _ in 0..MAX_DEPTH {
+            let Some(parent) = doc
+                .get_dictionary(node)
+                .and_then(|n| n.get(b"Parent"))
+                .and_then(Object::as_reference)
+                .ok()
+            else {
+                break;
+            };
+            if !nodes.insert(parent) {
+                break;
+            }
+            node = parent;
+        }
+    }
+    nodes
+}
+
+/// Links `items` up below `parent`, returning how many of them are shown
+/// to begin with and the first and the last one, or `None` without items.
+fn write_items(
+    doc: &mut Document,
+    items: &[Kept],
+    parent: ObjectId,
+    new_dest: &impl Fn(Destination) -> Object,
+) -> Option<(i64, ObjectId, ObjectId)> {
+    let (first, last) = (items.first()?.id, items.last()?.id);
+    let mut visible = 0;
+    for (i, item) in items.iter().enumerate() {
+        let children = write_items(doc, &item.children, item.id, new_dest);
+        let Ok(dict) = doc.get_dictionary_mut(item.id) else {
+            continue;
+        };
+        for key in [
+            b"Prev".as_slice(),
+            b"Next",
+            b"First",
+            b"Last",
+            b"Count",
+            b"A",
+            b"SE",
+        ] {
+            dict.remove(key);
+        }
+        dict.set("Parent", parent);
+        dict.set("Dest", new_dest(item.destination));
+        if i > 0 {
+            dict.set("Prev", items[i - 1].id);
+        }
+        if let Some(next) = items.get(i + 1) {
+            dict.set("Next", next.id);
+        }
+        visible += 1;
+        if let Some((shown, first, last)) = children {
+            dict.set("First", first);
+            dict.set("Last", last);
+            // Closed items count their children as negative
+            match item.open {
+                true => {
+                    dict.set("Count", shown);
+                    visible += shown;
+                }
+                false => dict.set("Count", -(item.children.len() as i64)),
+            }
+        }
+    }
+    Some((visible, first, last))
+}
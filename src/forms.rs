@@ -0,0 +1,613 @@
+// Fillable forms of PDFs, AcroForms.
+//
+// The fields of a form are a tree below the `AcroForm` dictionary of the
+// catalog, the full name of a field joins the partial names `T` on the way
+// down with dots. Fields without kids which are fields themselves hold a value
+// and are shown on the pages by widget annotations, which can be the field
+// itself. The type, flags and value are inherited from the fields above.
+//
+// Filling in a field sets its value `V` and redraws its widgets: checkboxes
+// and radio buttons switch to the appearance of the value, text is drawn left
+// aligned in the font of the field. `NeedAppearances` asks readers to redraw
+// everything, which is how list boxes and text in other fonts get drawn.
+
+use std::collections::{HashMap, HashSet};
+
+use lopdf::{
+    content::{Content, Operation},
+    dictionary, Dictionary, Document, Object, ObjectId, Stream, StringFormat,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    geometry::as_rect,
+    objects::{dict_entry, MAX_DEPTH},
+    text::{text_object, text_string},
+};
+
+// Field flags, bit n of `Ff` counting from 1
+const READ_ONLY: i64 = 1 << 0;
+const REQUIRED: i64 = 1 << 1;
+const MULTILINE: i64 = 1 << 12;
+const RADIO: i64 = 1 << 15;
+const PUSH_BUTTON: i64 = 1 << 16;
+const COMBO: i64 = 1 << 17;
+const EDIT: i64 = 1 << 18;
+const MULTI_SELECT: i64 = 1 << 21;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldKind {
+    Text,
+    Checkbox,
+    Radio,
+    /// A drop down list.
+    ComboBox,
+    ListBox,
+    /// A push button, which has no value.
+    Button,
+    Signature,
+}
+
+/// The value of a field: whether a checkbox is checked, the text of a text
+/// field, the option picked for radio buttons and choices, or the options
+/// picked in list boxes which allow several.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum FieldValue {
+    Checked(bool),
+    Text(String),
+    Choices(Vec<String>),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct FormField {
+    /// Full name of the field, values are filled in by it.
+    pub name: String,
+    pub kind: FieldKind,
+    /// The value stored in the PDF.
+    pub value: Option<FieldValue>,
+    /// The values of checkboxes, radio buttons and choices.
+    pub options: Vec<String>,
+    pub read_only: bool,
+    pub required: bool,
+    /// Text fields with several lines.
+    pub multiline: bool,
+    /// Combo boxes which take any text.
+    pub editable: bool,
+    /// List boxes which take several options.
+    pub multi_select: bool,
+    /// The most characters a text field takes.
+    pub max_length: Option<u32>,
+    /// The page the field is first shown on.
+    pub page: Option<u16>,
+}
+
+impl FormField {
+    /// Checks that `value` can be filled into the field.
+    pub fn check(&self, value: &FieldValue) -> Result<(), String> {
+        let name = &self.name;
+        if self.read_only || matches!(self.kind, FieldKind::Button | FieldKind::Signature) {
+            return Err(format!("{name} cannot be filled in"));
+        }
+        let option = |choice: &String| match self.options.contains(choice) {
+            true => Ok(()),
+            false => Err(format!("{choice} is not an option of {name}")),
+        };
+
+        match (self.kind, value) {
+            (FieldKind::Text, FieldValue::Text(text)) => match self.max_length {
+                Some(max) if text.chars().count() > max as usize => {
+                    Err(format!("{name} takes at most {max} characters"))
+                }
+                _ => Ok(()),
+            },
+            (FieldKind::Checkbox, FieldValue::Checked(_)) => Ok(()),
+            (FieldKind::ComboBox, FieldValue::Text(_)) if self.editable => Ok(()),
+            (FieldKind::Radio | FieldKind::ComboBox, FieldValue::Text(choice)) => option(choice),
+            (FieldKind::ListBox, FieldValue::Text(choice)) if !self.multi_select => option(choice),
+            (FieldKind::ListBox, FieldValue::Choices(choices)) if self.multi_select => {
+                choices.iter().try_for_each(option)
+            }
+            (kind, _) => {
+                let expected = match kind {
+                    FieldKind::Text | FieldKind::ComboBox => "text",
+                    FieldKind::Checkbox => "true or false",
+                    FieldKind::ListBox if self.multi_select => "a list of options",
+                    _ => "an option",
+                };
+                Err(format!("{name} takes {expected}"))
+            }
+        }
+    }
+}
+
+/// Every field of the form of `doc` which holds a value, in the order of the form.
+pub fn fields(doc: &Document) -> Vec<FormField> {
+    find(doc).into_iter().map(|field| field.form).collect()
+}
+
+/// Checks `values` by field name against the fields of a form.
+pub fn check_values(
+    fields: &[FormField],
+    values: &HashMap<String, FieldValue>,
+) -> Result<(), String> {
+    for (name, value) in values {
+        fields
+            .iter()
+            .find(|field| &field.name == name)
+            .ok_or_else(|| format!("There is no field {name}"))?
+            .check(value)?;
+    }
+    Ok(())
+}
+
+/// Writes `values` by field name into the form of `doc`, leaving out the ones which do not fit.
+pub fn fill(mut doc: Document, values: &HashMap<String, FieldValue>) -> Result<Vec<u8>, String> {
+    let resources = acro_form(&doc).and_then(|form| form.get(b"DR").ok().cloned());
+    let mut set: Vec<(ObjectId, &str, Object)> = vec![];
+    let mut cleared: Vec<(ObjectId, &[u8])> = vec![];
+    let mut drawn: Vec<(ObjectId, Option<Stream>)> = vec![];
+
+    for field in find(&doc) {
+        let Some(value) = values
+            .get(&field.form.name)
+            .filter(|value| field.form.check(value).is_ok())
+        else {
+            continue;
+        };
+
+        match value {
+            FieldValue::Checked(checked) => {
+                let on = field.widgets.iter().find_map(|&w| on_state(&doc, w));
+                let state = |on: Option<Vec<u8>>| match checked {
+                    true => Object::Name(on.unwrap_or_else(|| b"Yes".to_vec())),
+                    false => Object::Name(b"Off".to_vec()),
+                };
+                set.push((field.id, "V", state(on)));
+                for &widget in &field.widgets {
+                    set.push((widget, "AS", state(on_state(&doc, widget))));
+                }
+            }
+            FieldValue::Text(choice) if field.form.kind == FieldKind::Radio => {
+                set.push((field.id, "V", Object::Name(choice.as_bytes().to_vec())));
+                for &widget in &field.widgets {
+                    let state = match states(&doc, &[widget]).contains(choice) {
+                        true => choice.as_str(),
+                        false => "Off",
+                    };
+                    set.push((widget, "AS", Object::Name(state.as_bytes().to_vec())));
+                }
+            }
+            FieldValue::Text(text) => {
+                set.push((field.id, "V", text_object(text)));
+                if field.form.kind == FieldKind::ListBox {
+                    cleared.push((field.id, b"I"));
+                    continue;
+                }
+                for &widget in &field.widgets {
+                    let appearance = text_appearance(
+                        &doc,
+                        widget,
+                        field.appearance.as_deref(),
+                        resources.as_ref(),
+                        text,
+                        field.form.multiline,
+                    );
+                    drawn.push((widget, appearance));
+                }
+            }
+            FieldValue::Choices(choices) => {
+                let choices: Vec<Object> = choices.iter().map(|c| text_object(c)).collect();
+                set.push((field.id, "V", choices.into()));
+                cleared.push((field.id, b"I"));
+            }
+        }
+    }
+
+    for (id, key, value) in set {
+        if let Ok(dict) = doc.get_dictionary_mut(id) {
+            dict.set(key, value);
+        }
+    }
+    for (id, key) in cleared {
+        if let Ok(dict) = doc.get_dictionary_mut(id) {
+            dict.remove(key);
+        }
+    }
+    for (widget, appearance) in drawn {
+        let appearance = appearance.map(|stream| doc.add_object(stream));
+        if let Ok(dict) = doc.get_dictionary_mut(widget) {
+            match appearance {
+                Some(appearance) => dict.set("AP", dictionary! { "N" => appearance }),
+                // An outdated appearance would hide the new value from readers which redraw
+                None => {
+                    dict.remove(b"AP");
+                }
+            }
+        }
+    }
+
+    let reference = doc
+        .catalog()
+        .ok()
+        .and_then(|c| c.get(b"AcroForm").ok())
+        .and_then(|f| f.as_reference().ok());
+    let form = match reference {
+        Some(id) => doc.get_dictionary_mut(id).ok(),
+        None => doc
+            .catalog_mut()
+            .ok()
+            .and_then(|c| c.get_mut(b"AcroForm").ok())
+            .and_then(|f| f.as_dict_mut().ok()),
+    };
+    if let Some(form) = form {
+        form.set("NeedAppearances", true);
+        // Readers which know XFA forms would show those instead
+        form.remove(b"XFA");
+    }
+
+    let mut pdf = vec![];
+    doc.save_to(&mut pdf)
+        .map_err(|e| format!("Failed to write the filled in form: {e}"))?;
+    Ok(pdf)
+}
+
+/// A field holding a value, along with where it is stored.
+struct Field {
+    id: ObjectId,
+    form: FormField,
+    widgets: Vec<ObjectId>,
+    /// The font and color text is drawn in, as content stream operations.
+    appearance: Option<String>,
+}
+
+/// What fields inherit from the fields above them.
+#[derive(Clone, Default)]
+struct Inherited {
+    name: String,
+    kind: Option<Vec<u8>>,
+    flags: i64,
+    value: Option<Object>,
+    appearance: Option<String>,
+}
+
+fn acro_form(doc: &Document) -> Option<&Dictionary> {
+    dict_entry(doc, doc.catalog().ok()?, b"AcroForm")
+}
+
+fn find(doc: &Document) -> Vec<Field> {
+    let Some(form) = acro_form(doc) else {
+        return vec![];
+    };
+
+    // The page of every annotation
+    let mut pages = HashMap::new();
+    for (number, page) in doc.get_pages() {
+        if let Ok(annots) = doc
+            .get_dictionary(page)
+            .and_then(|p| p.get(b"Annots"))
+            .and_then(|o| doc.dereference(o))
+            .and_then(|(_, o)| o.as_array())
+        {
+            for id in annots.iter().filter_map(|a| a.as_reference().ok()) {
+                pages.entry(id).or_insert(number as u16);
+            }
+        }
+    }
+
+    let inherited = Inherited {
+        appearance: byte_string(doc, form.get(b"DA").ok()),
+        ..Inherited::default()
+    };
+    let mut found = vec![];
+    let mut seen = HashSet::new();
+    if let Ok(fields) = form
+        .get(b"Fields")
+        .and_then(|f| doc.dereference(f))
+        .and_then(|(_, f)| f.as_array())
+    {
+        for id in fields.iter().filter_map(|f| f.as_reference().ok()) {
+            walk(doc, id, &inherited, 0, &pages, &mut seen, &mut found);
+        }
+    }
+    found
+}
+
+fn walk(
+    doc: &Document,
+    id: ObjectId,
+    parent: &Inherited,
+    depth: usize,
+    pages: &HashMap<ObjectId, u16>,
+    seen: &mut HashSet<ObjectId>,
+    found: &mut Vec<Field>,
+) {
+    if depth > MAX_DEPTH || !seen.insert(id) {
+        return;
+    }
+    let Ok(dict) = doc.get_dictionary(id) else {
+        return;
+    };
+
+    let mut inherited = parent.clone();
+    if let Some(partial) = dict
+        .get(b"T")
+        .and_then(|t| doc.dereference(t))
+        .ok()
+        .and_then(|(_, t)| text_string(t))
+    {
+        inherited.name = match parent.name.is_empty() {
+            true => partial,
+            false => format!("{}.{partial}", parent.name),
+        };
+    }
+    if let Ok(kind) = dict.get(b"FT").and_then(Object::as_name) {
+        inherited.kind = Some(kind.to_vec());
+    }
+    if let Ok(flags) = dict.get(b"Ff").and_then(Object::as_i64) {
+        inherited.flags = flags;
+    }
+    if let Ok((_, value)) = dict.get(b"V").and_then(|v| doc.dereference(v)) {
+        inherited.value = Some(value.clone());
+    }
+    if let Some(appearance) = byte_string(doc, dict.get(b"DA").ok()) {
+        inherited.appearance = Some(appearance);
+    }
+
+    let kids: Vec<ObjectId> = dict
+        .get(b"Kids")
+        .and_then(|k| doc.dereference(k))
+        .and_then(|(_, k)| k.as_array())
+        .map(|kids| kids.iter().filter_map(|k| k.as_reference().ok()).collect())
+        .unwrap_or_default();
+    // Widgets never have a name of their own, fields merged with their widget do
+    let (mut widgets, fields): (Vec<ObjectId>, Vec<ObjectId>) =
+        kids.into_iter().partition(|&kid| {
+            doc.get_dictionary(kid)
+                .is_ok_and(|k| is_widget(k) && !k.has(b"T"))
+        });
+    if !fields.is_empty() {
+        for kid in fields {
+            walk(doc, kid, &inherited, depth + 1, pages, seen, found);
+        }
+        return;
+    }
+    if is_widget(dict) {
+        widgets.insert(0, id);
+    }
+
+    let flags = inherited.flags;
+    let kind = match inherited.kind.as_deref() {
+        Some(b"Tx") => FieldKind::Text,
+        Some(b"Btn") if flags & PUSH_BUTTON != 0 => FieldKind::Button,
+        Some(b"Btn") if flags & RADIO != 0 => FieldKind::Radio,
+        Some(b"Btn") => FieldKind::Checkbox,
+        Some(b"Ch") if flags & COMBO != 0 => FieldKind::ComboBox,
+        Some(b"Ch") => FieldKind::ListBox,
+        Some(b"Sig") => FieldKind::Signature,
+        _ => return,
+    };
+    if inherited.name.is_empty() {
+        return;
+    }
+
+    let multi_select = kind == FieldKind::ListBox && flags & MULTI_SELECT != 0;
+    let value = inherited
+        .value
+        .as_ref()
+        .and_then(|value| match (kind, value) {
+            (FieldKind::Checkbox, Object::Name(state)) => {
+                Some(FieldValue::Checked(state != b"Off"))
+            }
+            (FieldKind::Radio, Object::Name(state)) if state != b"Off" => Some(FieldValue::Text(
+                String::from_utf8_lossy(state).into_owned(),
+            )),
+            (FieldKind::ListBox, Object::Array(choices)) if multi_select => Some(
+                FieldValue::Choices(choices.iter().filter_map(text_string).collect()),
+            ),
+            (FieldKind::ListBox, Object::String(..)) if multi_select => {
+                Some(FieldValue::Choices(vec![text_string(value)?]))
+            }
+            (FieldKind::Text | FieldKind::ComboBox | FieldKind::ListBox, Object::String(..)) => {
+                text_string(value).map(FieldValue::Text)
+            }
+            _ => None,
+        });
+    let options = match kind {
+        FieldKind::Checkbox | FieldKind::Radio => states(doc, &widgets),
+        FieldKind::ComboBox | FieldKind::ListBox => choices(doc, dict),
+        _ => vec![],
+    };
+
+    found.push(Field {
+        id,
+        form: FormField {
+            name: inherited.name,
+            kind,
+            value,
+            options,
+            read_only: flags & READ_ONLY != 0,
+            required: flags & REQUIRED != 0,
+            multiline: kind == FieldKind::Text && flags & MULTILINE != 0,
+            editable: kind == FieldKind::ComboBox && flags & EDIT != 0,
+            multi_select,
+            max_length: dict
+                .get(b"MaxLen")
+                .and_then(Object::as_i64)
+                .ok()
+                .and_then(|max| u32::try_from(max).ok()),
+            page: widgets.iter().find_map(|w| pages.get(w)).copied(),
+        },
+        widgets,
+        appearance: inherited.appearance,
+    });
+}
+
+fn is_widget(dict: &Dictionary) -> bool {
+    dict.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Widget")
+}
+
+/// A string which is not text, like the operations of a default appearance.
+fn byte_string(doc: &Document, object: Option<&Object>) -> Option<String> {
+    match doc.dereference(object?).ok()?.1 {
+        Object::String(bytes, _) => Some(String::from_utf8_lossy(bytes).into_owned()),
+        _ => None,
+    }
+}
+
+/// The appearances `widgets` have for being on, which are the values of checkboxes and radio buttons.
+fn states(doc: &Document, widgets: &[ObjectId]) -> Vec<String> {
+    let mut states = vec![];
+    for &widget in widgets {
+        let Some(normal) = doc
+            .get_dictionary(widget)
+            .ok()
+            .and_then(|w| dict_entry(doc, w, b"AP"))
+            .and_then(|ap| dict_entry(doc, ap, b"N"))
+        else {
+            continue;
+        };
+        for (state, _) in normal.iter() {
+            let state = String::from_utf8_lossy(state).into_owned();
+            if state != "Off" && !states.contains(&state) {
+                states.push(state);
+            }
+        }
+    }
+    states
+}
+
+fn on_state(doc: &Document, widget: ObjectId) -> Option<Vec<u8>> {
+    states(doc, &[widget])
+        .into_iter()
+        .next()
+        .map(String::into_bytes)
+}
+
+/// The options of a choice field, options with a display text are listed by their value.
+fn choices(doc: &Document, field: &Dictionary) -> Vec<String> {
+    let Ok(options) = field
+        .get(b"Opt")
+        .and_then(|o| doc.dereference(o))
+        .and_then(|(_, o)| o.as_array())
+    else {
+        return vec![];
+    };
+    options
+        .iter()
+        .filter_map(|option| match doc.dereference(option).ok()?.1 {
+            Object::Array(pair) => text_string(doc.dereference(pair.first()?).ok()?.1),
+            option => text_string(option),
+        })
+        .collect()
+}
+
+/// Draws `text` into `widget`, in the font `appearance` sets. Only simple
+/// fonts are drawn here, as their strings are one byte per character.
+fn text_appearance(
+    doc: &Document,
+    widget: ObjectId,
+    appearance: Option<&str>,
+    resources: Option<&Object>,
+    text: &str,
+    multiline: bool,
+) -> Option<Stream> {
+    let rect = doc
+        .get_dictionary(widget)
+        .ok()?
+        .get(b"Rect")
+        .ok()
+        .and_then(|r| as_rect(doc, r))?;
+    let (width, height) = (rect.right - rect.left, rect.top - rect.bottom);
+
+    let mut operations = Content::decode(appearance?.as_bytes()).ok()?.operations;
+    let font_operation = operations.iter_mut().find(|o| o.operator == "Tf")?;
+    let font = font_operation.operands.first()?.as_name().ok()?.to_vec();
+    let simple = resources
+        .and_then(|r| doc.dereference(r).ok())
+        .and_then(|(_, r)| r.as_dict().ok())
+        .and_then(|r| dict_entry(doc, r, b"Font"))
+        .and_then(|fonts| dict_entry(doc, fonts, &font))
+        .and_then(|f| f.get(b"Subtype").and_then(Object::as_name).ok())
+        .is_some_and(|subtype| matches!(subtype, b"Type1" | b"TrueType"));
+    if !simple {
+        return None;
+    }
+
+    // Size 0 means as large as fits
+    let size = match font_operation.operands.get(1)?.as_float().ok()? {
+        size if size > 0.0 => size,
+        _ if multiline => 10.0f32.min(height - 4.0).max(1.0),
+        _ => ((height - 4.0) * 0.7).clamp(1.0, 12.0),
+    };
+    font_operation.operands[1] = Object::Real(size);
+
+    let line = |text: &str| {
+        // WinAnsiEncoding is Latin-1 for everything which is in both
+        let bytes = text
+            .chars()
+            .map(|c| match c {
+                ' '..='~' | '\u{a0}'..='\u{ff}' => c as u8,
+                _ => b'?',
+            })
+            .collect();
+        Operation::new("Tj", vec![Object::String(bytes, StringFormat::Literal)])
+    };
+    let mut content = vec![
+        Operation::new("BMC", vec!["Tx".into()]),
+        Operation::new("q", vec![]),
+        Operation::new(
+            "re",
+            vec![
+                1.into(),
+                1.into(),
+                Object::Real(width - 2.0),
+                Object::Real(height - 2.0),
+            ],
+        ),
+        Operation::new("W", vec![]),
+        Operation::new("n", vec![]),
+        Operation::new("BT", vec![]),
+    ];
+    content.extend(operations);
+    match multiline {
+        true => {
+            content.push(Operation::new("TL", vec![Object::Real(size * 1.15)]));
+            content.push(Operation::new(
+                "Td",
+                vec![2.into(), Object::Real(height - 2.0 - size)],
+            ));
+            for (i, text) in text.lines().enumerate() {
+                if i > 0 {
+                    content.push(Operation::new("T*", vec![]));
+                }
+                content.push(line(text));
+            }
+        }
+        false => {
+            content.push(Operation::new(
+                "Td",
+                vec![2.into(), Object::Real((height - size) / 2.0 + size * 0.22)],
+            ));
+            content.push(line(&text.replace(['\r', '\n'], " ")));
+        }
+    }
+    content.extend(["ET", "Q", "EMC"].map(|operator| Operation::new(operator, vec![])));
+
+    let mut dict = dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Form",
+        "BBox" => vec![0.into(), 0.into(), Object::Real(width), Object::Real(height)],
+    };
+    if let Some(resources) = resources {
+        dict.set("Resources", resources.clone());
+    }
+    let content = Content {
+        operations: content,
+    }
+    .encode()
+    .ok()?;
+    Some(Stream::new(dict, content))
+}
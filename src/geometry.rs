@@ -0,0 +1,108 @@
+// The size and orientation of PDF pages.
+//
+// The boxes and the rotation of a page can be set on the page itself or on any
+// node above it in the page tree, pages inherit them from the closest one.
+
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{objects::MAX_DEPTH, text::Rect};
+
+/// US Letter, used for pages without a `MediaBox` as most readers do.
+const DEFAULT_MEDIA_BOX: Rect = Rect {
+    left: 0.0,
+    bottom: 0.0,
+    right: 612.0,
+    top: 792.0,
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PageGeometry {
+    pub page: u16,
+    /// The whole page, in PDF points.
+    pub media_box: Rect,
+    /// The part of the page which is shown, in PDF points.
+    pub crop_box: Rect,
+    /// Clockwise rotation in degrees, one of 0, 90, 180 and 270.
+    pub rotation: u16,
+    /// The size of the page as it is shown, after cropping and rotating.
+    pub width: f32,
+    pub height: f32,
+}
+
+/// The geometry of every page of `doc`, in order.
+pub fn page_geometry(doc: &Document) -> Vec<PageGeometry> {
+    doc.get_pages()
+        .into_iter()
+        .map(|(number, page)| {
+            let media_box = inherited(doc, page, b"MediaBox")
+                .and_then(|o| as_rect(doc, o))
+                .unwrap_or(DEFAULT_MEDIA_BOX);
+            // Boxes reaching outside the media box are cut down to it
+            let crop_box = inherited(doc, page, b"CropBox")
+                .and_then(|o| as_rect(doc, o))
+                .map(|c| Rect {
+                    left: c.left.max(media_box.left),
+                    bottom: c.bottom.max(media_box.bottom),
+                    right: c.right.min(media_box.right),
+                    top: c.top.min(media_box.top),
+                })
+                .filter(|c| c.left < c.right && c.bottom < c.top)
+                .unwrap_or(media_box);
+            let rotation = inherited(doc, page, b"Rotate")
+                .and_then(|o| o.as_i64().ok())
+                .map(|r| r.rem_euclid(360) / 90 * 90)
+                .unwrap_or(0) as u16;
+
+            let (width, height) = (
+                crop_box.right - crop_box.left,
+                crop_box.top - crop_box.bottom,
+            );
+            let (width, height) = match rotation {
+                90 | 270 => (height, width),
+                _ => (width, height),
+            };
+            PageGeometry {
+                page: number as u16,
+                media_box,
+                crop_box,
+                rotation,
+                width,
+                height,
+            }
+        })
+        .collect()
+}
+
+/// The value of `key` for `page`, going up the page tree if the page does not set it.
+pub fn inherited<'a>(doc: &'a Document, page: ObjectId, key: &[u8]) -> Option<&'a Object> {
+    let mut node: &Dictionary = doc.get_dictionary(page).ok()?;
+    for _ in 0..MAX_DEPTH {
+        if let Ok(value) = node.get(key) {
+            return doc.dereference(value).ok().map(|(_, o)| o);
+        }
+        let parent = node.get(b"Parent").and_then(Object::as_reference).ok()?;
+        node = doc.get_dictionary(parent).ok()?;
+    }
+    None
+}
+
+/// Reads a rectangle, which may have any two opposite corners.
+pub fn as_rect(doc: &Document, rect: &Object) -> Option<Rect> {
+    let numbers: Vec<f32> = rect
+        .as_array()
+        .ok()?
+        .iter()
+        .filter_map(|n| doc.dereference(n).ok()?.1.as_float().ok())
+        .collect();
+    let [x0, y0, x1, y1] = numbers[..] else {
+        return None;
+    };
+    Some(Rect {
+        left: x0.min(x1),
+        bottom: y0.min(y1),
+        right: x0.max(x1),
+        top: y0.max(y1),
+    })
+}
@@ -0,0 +1,292 @@
+// Append-only log of everything that changes the reading progress.
+//
+// Each change is written as one JSON line and synced to disc right away, so a
+// crash loses at most the event being written. On startup the journal is
+// replayed on top of the last snapshot (`state.json`), a line torn by a crash
+// is cut off, and every now and then `persistence::sync_state` compacts it
+// into a new snapshot.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::{
+    forms::FieldValue,
+    persistence::DiscState,
+    state::{AccessTime, PagePosition, Pdf, ReaderPreferences},
+};
+
+/// The amount of journaled events after which the journal gets compacted.
+pub const COMPACT_AFTER: usize = 256;
+
+pub type WrappedJournal = Arc<Mutex<Journal>>;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum JournalEvent {
+    /// A book was opened or had its page turned.
+    Access { book: String },
+    /// The current page of a book was changed.
+    SetPage {
+        book: String,
+        page: u16,
+        #[serde(default)]
+        offset: f32,
+        #[serde(default)]
+        device: Option<String>,
+    },
+    /// A page was read, counts towards the reading statistics.
+    Read,
+    /// A device was seen for the first time or changed its name.
+    Device { id: String, name: String },
+    /// A book was uploaded into the library.
+    Add { path: PathBuf, total_pages: u16 },
+    /// A book was renamed or moved to another collection.
+    Relocate { book: String, path: PathBuf },
+    /// A book was deleted, its file is kept at `file` until it is purged.
+    Trash { book: String, file: PathBuf },
+    /// A deleted book was brought back.
+    Restore { book: String },
+    /// A deleted book was removed for good.
+    Purge { book: String },
+    /// A user registered for the KOReader sync.
+    SyncUser { username: String, key: String },
+    /// The viewer settings of a book were changed, the ones of `user` if given.
+    Preferences {
+        book: String,
+        #[serde(default)]
+        user: Option<String>,
+        preferences: ReaderPreferences,
+    },
+    /// A user filled in the form of a book.
+    FormValues {
+        book: String,
+        user: String,
+        values: HashMap<String, FieldValue>,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub seq: u64,
+    pub time: DateTime<Local>,
+    pub event: JournalEvent,
+}
+
+impl JournalEntry {
+    /// Applies the event to `state` as if it happened at `self.time`.
+    pub fn apply(&self, state: &mut DiscState) {
+        match &self.event {
+            JournalEvent::Access { book } => {
+                if let Some(pdf) = state.pdfs.get_book_by_name_mut(book) {
+                    pdf.access_at(AccessTime::at(self.time));
+                }
+            }
+            JournalEvent::SetPage {
+                book,
+                page,
+                offset,
+                device,
+            } => {
+                let position = PagePosition {
+                    time: self.time,
+                    page: *page,
+                    offset: *offset,
+                    device: device.clone(),
+                };
+                state.pdfs.set_page_by_name(book, position);
+            }
+            JournalEvent::Read => state.reading_history.increment_at(self.time),
+            JournalEvent::Device { id, name } => {
+                state.devices.register(id, name);
+            }
+            JournalEvent::Add { path, total_pages } => {
+                let pdf = Pdf::with_total_pages(path.clone(), *total_pages);
+                if !state.pdfs.has_book(&pdf.name().to_string()) {
+                    state.pdfs.add_book(pdf);
+                }
+            }
+            JournalEvent::Relocate { book, path } => {
+                state.pdfs.relocate(book, path.clone());
+            }
+            JournalEvent::Trash { book, file } => {
+                state.pdfs.trash(book, file.clone(), self.time);
+            }
+            JournalEvent::Restore { book } => {
+                state.pdfs.restore(book);
+            }
+            JournalEvent::Purge { book } => {
+                state.pdfs.purge(book);
+            }
+            JournalEvent::SyncUser { username, key } => {
+                state.sync_users.register(username, key);
+            }
+            JournalEvent::Preferences {
+                book,
+                user,
+                preferences,
+            } => {
+                if let Some(pdf) = state.pdfs.get_book_by_name_mut(book) {
+                    pdf.set_preferences(user.as_deref(), preferences.clone());
+                }
+            }
+            JournalEvent::FormValues { book, user, values } => {
+                if let Some(pdf) = state.pdfs.get_book_by_name_mut(book) {
+                    pdf.set_form_values(user, values.clone());
+                }
+            }
+        }
+        state.journal_seq = self.seq;
+    }
+}
+
+pub struct Journal {
+    path: PathBuf,
+    /// Shared with the blocking task every entry is written from.
+    file: Arc<File>,
+    /// Sequence number of the last written entry.
+    seq: u64,
+    /// Entries written since the last compaction.
+    len: usize,
+}
+
+impl Journal {
+    /// Opens the journal at `path`, creating it if needed.
+    ///
+    /// Returns the journal together with the entries which are newer than
+    /// `snapshot_seq`, i.e. the ones which still have to be replayed.
+    pub fn open(
+        path: PathBuf,
+        snapshot_seq: u64,
+    ) -> Result<(Self, Vec<JournalEntry>), Box<dyn Error>> {
+        let mut pending = vec![];
+        // Length of the entries which could be read, anything after them is cut off
+        let mut valid = None;
+
+        match File::open(&path) {
+            Ok(f) => {
+                let mut reader = BufReader::new(f);
+                let mut line = vec![];
+                let mut read = 0;
+                for n in 1.. {
+                    line.clear();
+                    let len = reader.read_until(b'\n', &mut line)?;
+                    if len == 0 {
+                        break;
+                    }
+
+                    let entry = match line.strip_suffix(b"\n") {
+                        Some(line) => {
+                            serde_json::from_slice::<JournalEntry>(line).map_err(|e| e.to_string())
+                        }
+                        None => Err(String::from("the line is not terminated")),
+                    };
+                    match entry {
+                        Ok(entry) if entry.seq > snapshot_seq => pending.push(entry),
+                        Ok(_) => {}
+                        Err(e) => {
+                            // Most likely a write that got cut short by a crash, nothing after it can be trusted.
+                            tracing::error!("Stopping replay of {path:?} at line {n}: {e}");
+                            valid = Some(read);
+                            break;
+                        }
+                    }
+                    read += len as u64;
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        if let Some(valid) = valid {
+            // New entries would end up behind the torn line, and be skipped on the next replay
+            tracing::warn!("Cutting {path:?} off after {valid} bytes");
+            file.set_len(valid)?;
+            file.sync_all()?;
+        }
+        let journal = Journal {
+            path,
+            file: Arc::new(file),
+            seq: pending.last().map_or(snapshot_seq, |e| e.seq),
+            len: pending.len(),
+        };
+
+        Ok((journal, pending))
+    }
+
+    /// Where the journal for the state file at `state_location` is stored.
+    pub fn location_for(state_location: &Path) -> PathBuf {
+        let mut path = state_location.as_os_str().to_owned();
+        path.push(".journal");
+        PathBuf::from(path)
+    }
+
+    pub fn wrapped(self) -> WrappedJournal {
+        Arc::new(Mutex::new(self))
+    }
+
+    /// Appends `event` to the journal and syncs it to disc.
+    ///
+    /// The write happens on a blocking thread, the journal stays locked until it is done.
+    pub async fn record(&mut self, event: JournalEvent) {
+        let entry = JournalEntry {
+            seq: self.seq + 1,
+            time: Local::now(),
+            event,
+        };
+
+        let write = match serde_json::to_string(&entry) {
+            Ok(line) => {
+                let file = self.file.clone();
+                tokio::task::spawn_blocking(move || {
+                    writeln!(&*file, "{line}")?;
+                    file.sync_data()
+                })
+                .await
+                .unwrap_or_else(|e| Err(std::io::Error::other(e)))
+            }
+            Err(e) => Err(e.into()),
+        };
+
+        match write {
+            Ok(()) => {
+                self.seq = entry.seq;
+                self.len += 1;
+            }
+            Err(e) => tracing::error!("Failed to journal {entry:?} to {:?}: {e}", self.path),
+        }
+    }
+
+    /// Sequence number of the last written entry.
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// Amount of entries written since the last compaction.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Empties the journal, should only be called once a snapshot containing
+    /// every entry has been written.
+    pub fn truncate(&mut self) -> Result<(), Box<dyn Error>> {
+        self.file.set_len(0)?;
+        self.file.sync_all()?;
+        self.len = 0;
+        Ok(())
+    }
+}
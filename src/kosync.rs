@@ -0,0 +1,147 @@
+// Support for the KOReader sync protocol.
+//
+// KOReader identifies documents by a hash of their contents (or of their file
+// name), the `DocumentIndex` maps those back to the books of a `PdfCollection`.
+// Progress is not kept per user, every user syncs against the one position a
+// book has in the library.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::state::PdfCollection;
+
+pub type WrappedSyncUsers = Arc<Mutex<SyncUsers>>;
+pub type WrappedDocumentIndex = Arc<Mutex<DocumentIndex>>;
+
+/// Users registered through the KOReader sync protocol, by name.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SyncUsers {
+    /// The key KOReader derives from the password (its MD5) for every user.
+    pub users: HashMap<String, String>,
+}
+
+impl SyncUsers {
+    pub fn wrapped(self) -> WrappedSyncUsers {
+        Arc::new(Mutex::new(self))
+    }
+
+    /// Adds a user, returns false if the name is taken.
+    pub fn register(&mut self, username: &str, key: &str) -> bool {
+        if self.users.contains_key(username) {
+            return false;
+        }
+        self.users.insert(username.to_string(), key.to_string());
+        true
+    }
+
+    pub fn authorize(&self, username: &str, key: &str) -> bool {
+        self.users.get(username).map(String::as_str) == Some(key)
+    }
+}
+
+/// The hash KOReader's "binary" document matching uses.
+///
+/// It is the MD5 of 1 KiB samples taken at 0, 1 KiB and then every fourfold
+/// offset up to 1 GiB, stopping at the end of the file.
+pub fn partial_md5(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut context = md5::Context::new();
+    let mut sample = Vec::with_capacity(1024);
+
+    for i in -1..=10 {
+        // KOReader shifts by `2 * i`, the shift by -2 wraps around to 0
+        let offset = if i < 0 { 0 } else { 1024u64 << (2 * i) };
+        file.seek(SeekFrom::Start(offset))?;
+
+        sample.clear();
+        (&mut file).take(1024).read_to_end(&mut sample)?;
+        if sample.is_empty() {
+            break;
+        }
+        context.consume(&sample);
+    }
+
+    Ok(format!("{:x}", context.compute()))
+}
+
+/// The hash KOReader's "filename" document matching uses.
+pub fn file_name_md5(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    Some(format!("{:x}", md5::compute(name)))
+}
+
+/// Whether new users may sign up through the sync protocol.
+///
+/// Registration is closed unless the server is started with `--kosync-registration`,
+/// so not everyone who can reach the server can create an account.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SyncSettings {
+    pub open_registration: bool,
+}
+
+/// The hashes KOReader may know the file at `path` by.
+pub fn document_hashes(path: &Path) -> Vec<String> {
+    let mut hashes = vec![];
+    match partial_md5(path) {
+        Ok(hash) => hashes.push(hash),
+        Err(e) => tracing::warn!("Failed to hash {path:?}: {e}"),
+    }
+    hashes.extend(file_name_md5(path));
+    hashes
+}
+
+/// Caches the document hashes of every book.
+#[derive(Debug, Default)]
+pub struct DocumentIndex {
+    /// The path every book had when it was hashed and its hashes.
+    hashes: HashMap<String, (PathBuf, Vec<String>)>,
+}
+
+impl DocumentIndex {
+    pub fn wrapped(self) -> WrappedDocumentIndex {
+        Arc::new(Mutex::new(self))
+    }
+
+    /// The books of `books` (name and path) which are new or have moved since they were hashed.
+    pub fn outdated(&self, books: &[(String, PathBuf)]) -> Vec<(String, PathBuf)> {
+        books
+            .iter()
+            .filter(|(name, path)| self.hashes.get(name).map(|(p, _)| p) != Some(path))
+            .cloned()
+            .collect()
+    }
+
+    /// Remembers the hashes of the book `name`, as it is stored at `path`.
+    pub fn insert(&mut self, name: String, path: PathBuf, hashes: Vec<String>) {
+        self.hashes.insert(name, (path, hashes));
+    }
+
+    /// Finds the name of the book of `books` with the hash `document`, among the hashed ones.
+    pub fn lookup(&self, books: &[(String, PathBuf)], document: &str) -> Option<String> {
+        let document = document.to_lowercase();
+        books
+            .iter()
+            .find(|(name, _)| {
+                self.hashes
+                    .get(name)
+                    .is_some_and(|(_, hashes)| hashes.contains(&document))
+            })
+            .map(|(name, _)| name.clone())
+    }
+}
+
+/// The name and path of every book in `pdfs`.
+pub fn library_paths(pdfs: &PdfCollection) -> Vec<(String, PathBuf)> {
+    pdfs.pdfs
+        .iter()
+        .map(|(name, pdf)| (name.clone(), pdf.path().clone()))
+        .collect()
+}
@@ -1,3 +1,19 @@
+pub mod attachments;
+pub mod comic;
+pub mod covers;
+pub mod devices;
+pub mod documents;
+pub mod epub;
+pub mod extract;
+pub mod forms;
+pub mod geometry;
+pub mod journal;
+pub mod kosync;
+pub mod links;
+pub mod objects;
+pub mod outline;
 pub mod persistence;
 pub mod routes;
 pub mod state;
+pub mod text;
+pub mod variants;
@@ -0,0 +1,227 @@
+// Links and named destinations of PDFs.
+//
+// A link annotation covers an area of a page and either jumps to a
+// destination in the document or opens a URI. A destination is a page and a
+// spot on it written out as an array, or the name of one which is looked up
+// in the `Dests` dictionary of the catalog (PDF 1.1) or the `Dests` name tree.
+
+use std::collections::HashMap;
+
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    geometry::as_rect,
+    objects::{annotations, dict_entry, name_tree},
+    text::{text_string, Rect},
+};
+
+/// How many names and `D` entries are followed to get to a destination.
+const MAX_INDIRECTIONS: usize = 4;
+
+/// A place in the book a link or name points to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Destination {
+    pub page: u16,
+    /// How high up the page to start, in PDF points, when the destination says.
+    pub top: Option<f32>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct NamedDestination {
+    pub name: String,
+    pub page: u16,
+    pub top: Option<f32>,
+}
+
+/// A link on a page, which has either `page` or `uri` set.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Link {
+    /// The area which can be clicked, in PDF points.
+    pub rect: Rect,
+    /// The page of the book the link goes to.
+    pub page: Option<u16>,
+    /// How high up `page` the link goes, in PDF points.
+    pub top: Option<f32>,
+    /// The address of a link out of the book.
+    pub uri: Option<String>,
+}
+
+/// Every named destination of `doc` which leads to a page, sorted by name.
+pub fn destinations(doc: &Document) -> Vec<NamedDestination> {
+    let resolver = Resolver::new(doc);
+    let mut destinations: Vec<NamedDestination> = resolver
+        .named
+        .keys()
+        .filter_map(|name| {
+            let destination = resolver.named(name)?;
+            Some(NamedDestination {
+                name: name.clone(),
+                page: destination.page,
+                top: destination.top,
+            })
+        })
+        .collect();
+    destinations.sort_by(|a, b| a.name.cmp(&b.name));
+    destinations
+}
+
+/// Looks up the named destination `name`.
+pub fn destination(doc: &Document, name: &str) -> Option<Destination> {
+    Resolver::new(doc).named(name)
+}
+
+/// The links on page `number` of `doc`, leaving out the ones which go nowhere the viewer can follow.
+pub fn page_links(doc: &Document, number: u16) -> Vec<Link> {
+    let resolver = Resolver::new(doc);
+    let Some(page) = doc.get_pages().get(&(number as u32)).copied() else {
+        return vec![];
+    };
+
+    let mut links = vec![];
+    for annotation in annotations(doc, page) {
+        if annotation.get(b"Subtype").and_then(Object::as_name).ok() != Some(b"Link") {
+            continue;
+        }
+        let Some(rect) = annotation.get(b"Rect").ok().and_then(|r| as_rect(doc, r)) else {
+            continue;
+        };
+
+        let mut link = Link {
+            rect,
+            page: None,
+            top: None,
+            uri: None,
+        };
+        let destination = match (annotation.has(b"Dest"), dict_entry(doc, annotation, b"A")) {
+            (true, _) => resolver.target(annotation),
+            (_, Some(action)) => match action.get(b"S").and_then(Object::as_name) {
+                Ok(b"GoTo") => resolver.target(annotation),
+                Ok(b"URI") => {
+                    link.uri = action
+                        .get(b"URI")
+                        .and_then(|o| doc.dereference(o))
+                        .ok()
+                        .and_then(|(_, o)| text_string(o));
+                    None
+                }
+                // The buttons of the reader, as links
+                Ok(b"Named") => {
+                    let target = match action.get(b"N").and_then(Object::as_name) {
+                        Ok(b"FirstPage") => Some(1),
+                        Ok(b"LastPage") => Some(resolver.pages.len() as u16),
+                        Ok(b"NextPage") => number.checked_add(1),
+                        Ok(b"PrevPage") => number.checked_sub(1),
+                        _ => None,
+                    };
+                    target
+                        .filter(|p| (1..=resolver.pages.len() as u16).contains(p))
+                        .map(|page| Destination { page, top: None })
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+
+        if let Some(destination) = destination {
+            link.page = Some(destination.page);
+            link.top = destination.top;
+        }
+        if link.page.is_some() || link.uri.is_some() {
+            links.push(link);
+        }
+    }
+
+    links
+}
+
+/// Turns destinations into page numbers.
+pub struct Resolver<'a> {
+    doc: &'a Document,
+    pages: HashMap<ObjectId, u16>,
+    named: HashMap<String, &'a Object>,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(doc: &'a Document) -> Self {
+        let pages = doc
+            .get_pages()
+            .into_iter()
+            .map(|(number, id)| (id, number as u16))
+            .collect();
+
+        let mut named = HashMap::new();
+        if let Ok(catalog) = doc.catalog() {
+            if let Some(dests) = dict_entry(doc, catalog, b"Dests") {
+                for (name, dest) in dests.iter() {
+                    named.insert(String::from_utf8_lossy(name).into_owned(), dest);
+                }
+            }
+            if let Some(tree) =
+                dict_entry(doc, catalog, b"Names").and_then(|n| dict_entry(doc, n, b"Dests"))
+            {
+                for (name, dest) in name_tree(doc, tree) {
+                    if let Some(name) = text_string(name) {
+                        named.insert(name, dest);
+                    }
+                }
+            }
+        }
+
+        Resolver { doc, pages, named }
+    }
+
+    fn named(&self, name: &str) -> Option<Destination> {
+        self.resolve(self.named.get(name)?, 0)
+    }
+
+    /// Where the link annotation or outline item `item` goes inside the book,
+    /// through its `Dest` entry or a `GoTo` action.
+    pub fn target(&self, item: &Dictionary) -> Option<Destination> {
+        if let Ok(dest) = item.get(b"Dest") {
+            return self.resolve(dest, 0);
+        }
+        let action = dict_entry(self.doc, item, b"A")?;
+        match action.get(b"S").and_then(Object::as_name) {
+            Ok(b"GoTo") => self.resolve(action.get(b"D").ok()?, 0),
+            _ => None,
+        }
+    }
+
+    /// Resolves `dest`, following names and `D` entries a few times at most.
+    fn resolve(&self, dest: &Object, depth: usize) -> Option<Destination> {
+        if depth > MAX_INDIRECTIONS {
+            return None;
+        }
+        let (_, dest) = self.doc.dereference(dest).ok()?;
+        match dest {
+            Object::Array(dest) => {
+                let page = match dest.first()? {
+                    Object::Reference(id) => *self.pages.get(id)?,
+                    // Only meant for other documents, but some writers use it for their own pages
+                    Object::Integer(index) => u16::try_from(index.checked_add(1)?).ok()?,
+                    _ => return None,
+                };
+                if page == 0 || page as usize > self.pages.len() {
+                    return None;
+                }
+
+                let number = |i: usize| dest.get(i).and_then(|n| n.as_float().ok());
+                let top = match dest.get(1).and_then(|m| m.as_name().ok()) {
+                    Some(b"XYZ") => number(3),
+                    Some(b"FitH" | b"FitBH") => number(2),
+                    Some(b"FitR") => number(5),
+                    _ => None,
+                };
+                Some(Destination { page, top })
+            }
+            Object::Dictionary(dest) => self.resolve(dest.get(b"D").ok()?, depth + 1),
+            Object::Name(name) => {
+                self.resolve(self.named.get(&*String::from_utf8_lossy(name))?, depth + 1)
+            }
+            Object::String(..) => self.resolve(self.named.get(&text_string(dest)?)?, depth + 1),
+            _ => None,
+        }
+    }
+}
@@ -1,5 +1,4 @@
 use std::{
-    collections::HashMap,
     fs::{File, OpenOptions},
     io::ErrorKind,
     net::SocketAddr,
@@ -18,21 +17,42 @@ use tokio::time::sleep;
 use tracing::{error, info, metadata::LevelFilter};
 
 use crate::{
+    covers::CoverCache,
+    documents::DocumentCache,
+    journal::Journal,
+    kosync::{DocumentIndex, SyncSettings},
     persistence::DiscState,
     routes::{
+        events::{event_channel, events as live_events},
         get_pdf::get_pdf,
         main_page::{main_page, main_page_untemplated},
         set_page::set_page,
         static_path::static_path,
-        stats::{get_last_day, get_last_month, get_last_week, ReadingStatistics},
+        stats::{get_last_day, get_last_month, get_last_week},
         view_pdf::view_pdf,
     },
-    state::PdfCollection,
+    variants::VariantCache,
 };
 
+mod attachments;
+mod comic;
+mod covers;
+mod devices;
+mod documents;
+mod epub;
+mod extract;
+mod forms;
+mod geometry;
+mod journal;
+mod kosync;
+mod links;
+mod objects;
+mod outline;
 mod persistence;
 mod routes;
 mod state;
+mod text;
+mod variants;
 
 // TODOS:
 // TODO: maybe a overall to not use pdf.js and instead split the pdfs into i&mages at start-time
@@ -52,6 +72,7 @@ async fn main() -> Result<(), hyper::Error> {
         .arg(arg!(-p --port [port] "The port number to host the server on. (defaults to 4000)"))
         .arg(Arg::new("dir").action(ArgAction::Append).value_parser(value_parser!(PathBuf)).short('c').help("Which directory to host (defaults to \"contents\""))
         .arg(arg!(-s --state [state] "The location to store the state.json file (defaults to ~/.state.json"))
+        .arg(arg!(--"kosync-registration" "Lets anyone create a KOReader sync user"))
         .get_matches();
 
     let log_level = if matches.contains_id("debug") {
@@ -90,35 +111,33 @@ async fn main() -> Result<(), hyper::Error> {
             .to_owned(),
     );
 
-    // TODO: Convert this to tokio::OpenOptions eventually
-    let fd = OpenOptions::new()
-        .read(true)
-        .open(&state_location)
-        .unwrap_or_else(|error| {
-            if let ErrorKind::NotFound = error.kind() {
-                tracing::error!("Failed to open {state_location:?}, creating file now!");
-
-                // Initialize the file with a basic state if it does not exist.
-                // First creates the file and writes to it, then reopens it and
-                // returns the file descriptor.
-                let dummy_state = DiscState {
-                    pdfs: PdfCollection {
-                        pdfs: HashMap::new(),
-                    },
-                    reading_history: ReadingStatistics::new(),
-                };
-
-                let f = File::create(&state_location).unwrap();
-                serde_json::to_writer_pretty(f, &dummy_state).unwrap();
-                OpenOptions::new().read(true).open(&state_location).unwrap()
-            } else {
-                // Panic if its a error not related to the state file being AWOL.
-                panic!("{error}");
-            }
-        });
+    if let Err(error) = OpenOptions::new().read(true).open(&state_location) {
+        if let ErrorKind::NotFound = error.kind() {
+            tracing::error!("Failed to open {state_location:?}, creating file now!");
+
+            // Initialize the file with a basic state if it does not exist.
+            let f = File::create(&state_location).unwrap();
+            serde_json::to_writer_pretty(f, &DiscState::new()).unwrap();
+        } else {
+            // Panic if its a error not related to the state file being AWOL.
+            panic!("{error}");
+        }
+    }
+
+    let mut disc_state = DiscState::load(&state_location)
+        .unwrap_or_else(|e| panic!("Could not parse {state_location:?}: {e}"));
 
-    let disc_state: DiscState =
-        serde_json::from_reader(fd).expect(format!("Could not parse {state_location:?}").as_str());
+    // Replay whatever happened after the last snapshot was written
+    let journal_location = Journal::location_for(&state_location);
+    let (journal, pending) = Journal::open(journal_location.clone(), disc_state.journal_seq)
+        .unwrap_or_else(|e| panic!("Could not read {journal_location:?}: {e}"));
+    info!("Replaying {} journal entries", pending.len());
+    for entry in pending {
+        entry.apply(&mut disc_state);
+    }
+    let journal = journal.wrapped();
+    let covers = CoverCache::new(CoverCache::location_for(&state_location));
+    let variants = VariantCache::new(VariantCache::location_for(&state_location));
 
     let unwrapped = disc_state.pdfs;
     let state = unwrapped.wrapped();
@@ -126,12 +145,19 @@ async fn main() -> Result<(), hyper::Error> {
     // spawn persistence
     let dummy = state.clone();
     let dummy_location = state_location.clone();
-    let read_stats = disc_state.reading_history.to_owned().as_wrapped();
+    let read_stats = disc_state.reading_history.to_owned().into_wrapped();
     {
         let mut w = read_stats.lock().await;
         w.update();
     }
     let read_dummy = read_stats.clone();
+    let devices = disc_state.devices.wrapped();
+    let devices_dummy = devices.clone();
+    let sync_users = disc_state.sync_users.wrapped();
+    let sync_users_dummy = sync_users.clone();
+    let journal_dummy = journal.clone();
+    let events = event_channel();
+    let events_dummy = events.clone();
     let cloned_content = content.clone();
     tokio::spawn(async move {
         loop {
@@ -141,6 +167,10 @@ async fn main() -> Result<(), hyper::Error> {
                 dummy_location.clone(),
                 dummy.clone(),
                 read_dummy.clone(),
+                devices_dummy.clone(),
+                sync_users_dummy.clone(),
+                journal_dummy.clone(),
+                events_dummy.clone(),
             )
             .await
             {
@@ -161,11 +191,28 @@ async fn main() -> Result<(), hyper::Error> {
         .route("/view/:pdf", get(view_pdf))
         .route("/view/:pdf/set_page", post(set_page))
         .route("/get_pdf/:pdf", get(get_pdf))
+        .route("/epub/:book/*file", get(routes::epub::epub_file))
         .route("/status/:pdf", get(status))
+        .route("/api/events", get(live_events))
+        .nest("/api/v1", routes::v1::router())
+        .route("/api/books/*rest", get(routes::v1::unversioned_books))
+        .nest("/opds", routes::opds::router())
+        .nest("/kosync", routes::kosync::router())
         .route("/stats/last_day", get(get_last_day))
         .route("/stats/last_month", get(get_last_month))
         .route("/stats/last_week", get(get_last_week))
         .layer(Extension(read_stats))
+        .layer(Extension(devices))
+        .layer(Extension(sync_users))
+        .layer(Extension(SyncSettings {
+            open_registration: matches.contains_id("kosync-registration"),
+        }))
+        .layer(Extension(DocumentIndex::default().wrapped()))
+        .layer(Extension(journal))
+        .layer(Extension(covers))
+        .layer(Extension(variants))
+        .layer(Extension(DocumentCache::default()))
+        .layer(Extension(events))
         .layer(Extension(content.clone()))
         .layer(Extension(state));
 
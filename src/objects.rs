@@ -0,0 +1,76 @@
+// Walking the structures PDFs are built from.
+//
+// Helpers for the parts of a PDF several features read, written to skip
+// broken objects rather than fail, since all of them are optional.
+
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+/// How deep trees of objects are followed at most.
+///
+/// Objects may refer back to one of their ancestors. Every walk down (or up) a
+/// tree stops at this depth, so a tree with a cycle cannot hang the server.
+pub const MAX_DEPTH: usize = 32;
+
+/// The dictionary `key` of `dict` points to, if it is one.
+pub fn dict_entry<'a>(
+    doc: &'a Document,
+    dict: &'a Dictionary,
+    key: &[u8],
+) -> Option<&'a Dictionary> {
+    dict.get(key)
+        .and_then(|o| doc.dereference(o))
+        .and_then(|(_, o)| o.as_dict())
+        .ok()
+}
+
+/// The annotations of the page with the id `page`, leaving out broken ones.
+pub fn annotations(doc: &Document, page: ObjectId) -> Vec<&Dictionary> {
+    let Ok(annots) = doc
+        .get_dictionary(page)
+        .and_then(|p| p.get(b"Annots"))
+        .and_then(|o| doc.dereference(o))
+        .and_then(|(_, o)| o.as_array())
+    else {
+        return vec![];
+    };
+    annots
+        .iter()
+        .filter_map(|a| doc.dereference(a).and_then(|(_, o)| o.as_dict()).ok())
+        .collect()
+}
+
+/// The keys and values of the name tree `root`, in order.
+pub fn name_tree<'a>(doc: &'a Document, root: &'a Dictionary) -> Vec<(&'a Object, &'a Object)> {
+    let mut entries = vec![];
+    collect_names(doc, root, 0, &mut entries);
+    entries
+}
+
+fn collect_names<'a>(
+    doc: &'a Document,
+    node: &'a Dictionary,
+    depth: usize,
+    entries: &mut Vec<(&'a Object, &'a Object)>,
+) {
+    if depth > MAX_DEPTH {
+        return;
+    }
+    if let Ok(names) = node
+        .get(b"Names")
+        .and_then(|o| doc.dereference(o))
+        .and_then(|(_, o)| o.as_array())
+    {
+        entries.extend(names.chunks_exact(2).map(|pair| (&pair[0], &pair[1])));
+    }
+    if let Ok(kids) = node
+        .get(b"Kids")
+        .and_then(|o| doc.dereference(o))
+        .and_then(|(_, o)| o.as_array())
+    {
+        for kid in kids {
+            if let Ok(kid) = doc.dereference(kid).and_then(|(_, o)| o.as_dict()) {
+                collect_names(doc, kid, depth + 1, entries);
+            }
+        }
+    }
+}
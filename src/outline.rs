@@ -0,0 +1,142 @@
+// The outline of PDFs, the table of contents readers show next to the pages.
+//
+// The outline is a tree of items linked through `First` and `Next`, each
+// going to a destination in the book. A chapter runs from the page of its item
+// up to where the next item at the same or a higher level starts.
+
+use std::collections::HashSet;
+
+use lopdf::{Document, Object, ObjectId};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    geometry::{page_geometry, PageGeometry},
+    links::{Destination, Resolver},
+    objects::{dict_entry, MAX_DEPTH},
+    text::text_string,
+};
+
+/// An item of the outline as it is stored in the PDF.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Item {
+    pub id: ObjectId,
+    pub title: String,
+    /// Where the item goes, if it goes anywhere in the book.
+    pub destination: Option<Destination>,
+    /// Whether readers show the children of the item to begin with.
+    pub open: bool,
+    pub children: Vec<Item>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct OutlineEntry {
+    /// Number of the entry, counting from 1 in reading order.
+    pub number: u16,
+    pub title: String,
+    /// How deep the entry is nested, 0 for the top level.
+    pub level: u16,
+    pub page: u16,
+    /// The last page of the chapter the entry starts.
+    pub last_page: u16,
+}
+
+/// The top level items of the outline of `doc`.
+pub fn items(doc: &Document) -> Vec<Item> {
+    let Some(first) = doc
+        .catalog()
+        .ok()
+        .and_then(|c| dict_entry(doc, c, b"Outlines"))
+        .and_then(|o| o.get(b"First").and_then(Object::as_reference).ok())
+    else {
+        return vec![];
+    };
+    siblings(doc, &Resolver::new(doc), first, 0, &mut HashSet::new())
+}
+
+/// The entries of the outline of `doc` which go to a page, in reading order.
+pub fn outline(doc: &Document) -> Vec<OutlineEntry> {
+    let mut flat = vec![];
+    flatten(&items(doc), 0, &mut flat);
+    let geometry = page_geometry(doc);
+    let page_count = geometry.len() as u16;
+
+    flat.iter()
+        .enumerate()
+        .map(|(i, (title, level, destination))| {
+            let end = match flat[i + 1..].iter().find(|(_, l, _)| l <= level) {
+                Some((_, _, next)) if starts_lower(&geometry, next) => next.page,
+                Some((_, _, next)) => next.page.saturating_sub(1),
+                None => page_count,
+            };
+            OutlineEntry {
+                number: i as u16 + 1,
+                title: title.clone(),
+                level: *level,
+                page: destination.page,
+                last_page: end.max(destination.page),
+            }
+        })
+        .collect()
+}
+
+fn siblings(
+    doc: &Document,
+    resolver: &Resolver,
+    first: ObjectId,
+    depth: usize,
+    seen: &mut HashSet<ObjectId>,
+) -> Vec<Item> {
+    let mut items = vec![];
+    if depth > MAX_DEPTH {
+        return items;
+    }
+
+    let mut next = Some(first);
+    // Items linked in a loop are only read once
+    while let Some(id) = next.filter(|id| seen.insert(*id)) {
+        let Ok(item) = doc.get_dictionary(id) else {
+            break;
+        };
+        let children = match item.get(b"First").and_then(Object::as_reference) {
+            Ok(first) => siblings(doc, resolver, first, depth + 1, seen),
+            Err(_) => vec![],
+        };
+        items.push(Item {
+            id,
+            title: item
+                .get(b"Title")
+                .and_then(|t| doc.dereference(t))
+                .ok()
+                .and_then(|(_, t)| text_string(t))
+                .map(|t| t.trim().to_string())
+                .unwrap_or_default(),
+            destination: resolver.target(item),
+            open: item.get(b"Count").and_then(Object::as_i64).unwrap_or(0) > 0,
+            children,
+        });
+        next = item.get(b"Next").and_then(Object::as_reference).ok();
+    }
+    items
+}
+
+/// Lists the items which go somewhere, children of the ones which do not are kept at their level.
+fn flatten(items: &[Item], level: u16, flat: &mut Vec<(String, u16, Destination)>) {
+    for item in items {
+        if let Some(destination) = item.destination {
+            flat.push((item.title.clone(), level, destination));
+        }
+        flatten(&item.children, level + 1, flat);
+    }
+}
+
+/// Whether `destination` is below the top fifth of its page, so its page
+/// still belongs to the chapter before it.
+fn starts_lower(geometry: &[PageGeometry], destination: &Destination) -> bool {
+    let (Some(top), Some(page)) = (destination.top, geometry.get(destination.page as usize - 1))
+    else {
+        return false;
+    };
+    let crop = page.crop_box;
+    top < crop.top - (crop.top - crop.bottom) / 5.0
+}
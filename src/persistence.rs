@@ -5,76 +5,428 @@
 // so that new pdfs can be appended at runtime
 
 use serde::{Deserialize, Serialize};
-use std::fs::OpenOptions;
-use std::path::PathBuf;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use std::{error::Error, ffi::OsStr};
 use tokio::fs::read_dir;
 
 use crate::{
-    routes::stats::{ReadingStatistics, WrappedReadingStatistics},
-    state::{Pdf, PdfCollection, WrappedPdfCollection},
+    devices::{DeviceRegistry, WrappedDeviceRegistry},
+    journal::{WrappedJournal, COMPACT_AFTER},
+    kosync::{SyncUsers, WrappedSyncUsers},
+    routes::{
+        events::{publish, EventSender, LiveEvent},
+        stats::{ReadingStatistics, WrappedReadingStatistics},
+    },
+    state::{DocumentKind, Pdf, PdfCollection, WrappedPdfCollection},
 };
 
+/// The schema version written by this build of the server.
+///
+/// Bump this and append a function to `MIGRATIONS` whenever `DiscState`
+/// (or anything it contains) changes shape.
+pub const STATE_VERSION: u32 = 12;
+
+/// A migration takes a state file of version `n` and returns it as version `n + 1`.
+type Migration = fn(Value) -> Result<Value, Box<dyn Error>>;
+
+/// Chain of migrations, `MIGRATIONS[n]` upgrades a version `n` state to version `n + 1`.
+const MIGRATIONS: [Migration; STATE_VERSION as usize] = [
+    migrate_v0_to_v1,
+    migrate_v1_to_v2,
+    migrate_v2_to_v3,
+    migrate_v3_to_v4,
+    migrate_v4_to_v5,
+    migrate_v5_to_v6,
+    migrate_v6_to_v7,
+    migrate_v7_to_v8,
+    migrate_v8_to_v9,
+    migrate_v9_to_v10,
+    migrate_v10_to_v11,
+    migrate_v11_to_v12,
+];
+
 // TODO: Maybe implement Drop for this so we dont get halfwrites when exiting the program
 #[derive(Serialize, Deserialize)]
 /// Struct for writing BOTH a pdfcollection and a readingstatistics to disc as one.
 pub struct DiscState {
+    pub version: u32,
+    /// Sequence number of the last journal entry contained in this snapshot.
+    pub journal_seq: u64,
     pub pdfs: PdfCollection,
     pub reading_history: ReadingStatistics,
+    pub devices: DeviceRegistry,
+    pub sync_users: SyncUsers,
+}
+
+impl DiscState {
+    /// Creates a empty state of the current version.
+    pub fn new() -> Self {
+        DiscState {
+            version: STATE_VERSION,
+            journal_seq: 0,
+            pdfs: PdfCollection {
+                pdfs: HashMap::new(),
+                trash: HashMap::new(),
+            },
+            reading_history: ReadingStatistics::new(),
+            devices: DeviceRegistry::default(),
+            sync_users: SyncUsers::default(),
+        }
+    }
+
+    /// Parses a state file of any known version, migrating it to `STATE_VERSION`.
+    pub fn parse(s: &str) -> Result<Self, Box<dyn Error>> {
+        let mut value: Value = serde_json::from_str(s)?;
+        let version = state_version(&value);
+
+        if version > STATE_VERSION {
+            return Err(format!(
+                "state has version {version} but this build only understands up to {STATE_VERSION}"
+            )
+            .into());
+        }
+
+        for migration in &MIGRATIONS[version as usize..] {
+            value = migration(value)?;
+        }
+
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Loads the state file at `path`.
+    ///
+    /// If the file is of an older version a copy of it is kept next to it as
+    /// `<file>.v<version>.bak` before anything is migrated, so no progress is
+    /// lost should the migration turn out to be faulty.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = fs::read_to_string(path)?;
+        let version = state_version(&serde_json::from_str(&contents)?);
+
+        if version < STATE_VERSION {
+            let mut backup = path.as_os_str().to_owned();
+            backup.push(format!(".v{version}.bak"));
+            fs::write(&backup, &contents)?;
+            tracing::info!(
+                "Migrating {path:?} from version {version} to {STATE_VERSION}, backup stored at {backup:?}"
+            );
+        }
+
+        DiscState::parse(&contents)
+    }
+
+    /// Writes the state to `path`.
+    ///
+    /// The state is first written to a temporary file which then replaces
+    /// `path`, so a crash never leaves a half written state behind.
+    pub fn store(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        let fd = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp)?;
+        serde_json::to_writer_pretty(&fd, self)?;
+        fd.sync_all()?;
+
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+impl Default for DiscState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Figures out which version a raw state file is.
+///
+/// Files written before the version field existed are told apart by their shape.
+fn state_version(value: &Value) -> u32 {
+    match value.get("version").and_then(Value::as_u64) {
+        Some(v) => v as u32,
+        None if value.get("pdfs").is_some() => 1,
+        None => 0,
+    }
+}
+
+/// Version 0 was a flat map from the pdf file name to its current page.
+///
+/// The path and page count are not known here, they get filled in by
+/// `sync_state` the first time the file is seen in a content directory.
+fn migrate_v0_to_v1(value: Value) -> Result<Value, Box<dyn Error>> {
+    let old = value
+        .as_object()
+        .ok_or("version 0 state is not a JSON object")?;
+
+    let mut pdfs = Map::new();
+    for (file_name, page) in old {
+        let page = page
+            .as_u64()
+            .ok_or(format!("invalid page for {file_name}: {page}"))?;
+        let name = file_name.strip_suffix(".pdf").unwrap_or(file_name);
+
+        pdfs.insert(
+            name.to_string(),
+            json!({
+                "last_access": "Never",
+                "name": name,
+                "path": file_name,
+                "current_page": page,
+                "total_pages": 0,
+            }),
+        );
+    }
+
+    Ok(json!({
+        "pdfs": { "pdfs": pdfs },
+        "reading_history": { "events": [] },
+    }))
+}
+
+/// Version 1 is identical to version 2 except for the missing version field.
+fn migrate_v1_to_v2(mut value: Value) -> Result<Value, Box<dyn Error>> {
+    value
+        .as_object_mut()
+        .ok_or("version 1 state is not a JSON object")?
+        .insert("version".into(), json!(2));
+    Ok(value)
+}
+
+/// Version 3 tracks which journal entries are contained in the snapshot,
+/// older snapshots had no journal at all.
+fn migrate_v2_to_v3(mut value: Value) -> Result<Value, Box<dyn Error>> {
+    let state = value
+        .as_object_mut()
+        .ok_or("version 2 state is not a JSON object")?;
+    state.insert("version".into(), json!(3));
+    state.insert("journal_seq".into(), json!(0));
+    Ok(value)
+}
+
+/// Version 4 keeps a history of positions for every book.
+fn migrate_v3_to_v4(value: Value) -> Result<Value, Box<dyn Error>> {
+    add_pdf_field(value, 3, "history", json!([]))
+}
+
+/// Version 5 counts the page changes of every book for optimistic concurrency.
+fn migrate_v4_to_v5(value: Value) -> Result<Value, Box<dyn Error>> {
+    add_pdf_field(value, 4, "revision", json!(0))
+}
+
+/// Version 6 knows which device every page change came from.
+fn migrate_v5_to_v6(value: Value) -> Result<Value, Box<dyn Error>> {
+    let mut value = add_pdf_field(value, 5, "device_positions", json!({}))?;
+    value["devices"] = json!({ "devices": {} });
+    Ok(value)
+}
+
+/// Version 7 keeps deleted books in a trash so they can be restored.
+fn migrate_v6_to_v7(mut value: Value) -> Result<Value, Box<dyn Error>> {
+    value
+        .get_mut("pdfs")
+        .and_then(Value::as_object_mut)
+        .ok_or("version 6 state has no pdfs")?
+        .insert("trash".into(), json!({}));
+    value["version"] = json!(7);
+    Ok(value)
+}
+
+/// Version 8 knows the users of the KOReader sync protocol.
+fn migrate_v7_to_v8(mut value: Value) -> Result<Value, Box<dyn Error>> {
+    value["sync_users"] = json!({ "users": {} });
+    value["version"] = json!(8);
+    Ok(value)
+}
+
+/// Version 9 knows how far into a page (or chapter) a book is.
+fn migrate_v8_to_v9(value: Value) -> Result<Value, Box<dyn Error>> {
+    add_pdf_field(value, 8, "offset", json!(0.0))
+}
+
+/// Version 10 remembers how every book is shown in the viewer.
+fn migrate_v9_to_v10(value: Value) -> Result<Value, Box<dyn Error>> {
+    let preferences = json!({
+        "zoom": "auto",
+        "scale": 1.4,
+        "rotation": 0,
+        "invert": false,
+        "spread": false,
+        "continuous": false,
+    });
+    add_pdf_field(value, 9, "preferences", preferences)
+}
+
+/// Version 11 keeps what users filled into the forms of books.
+fn migrate_v10_to_v11(value: Value) -> Result<Value, Box<dyn Error>> {
+    add_pdf_field(value, 10, "form_values", json!({}))
+}
+
+/// Version 12 lets every sync user pick their own viewer settings.
+fn migrate_v11_to_v12(value: Value) -> Result<Value, Box<dyn Error>> {
+    add_pdf_field(value, 11, "user_preferences", json!({}))
+}
+
+/// Adds `key` with the value `default` to every pdf in a state of version `from`,
+/// including the ones in the trash.
+fn add_pdf_field(
+    mut value: Value,
+    from: u32,
+    key: &str,
+    default: Value,
+) -> Result<Value, Box<dyn Error>> {
+    let pdfs = value
+        .pointer_mut("/pdfs/pdfs")
+        .and_then(Value::as_object_mut)
+        .ok_or(format!("version {from} state has no pdfs"))?;
+    for pdf in pdfs.values_mut() {
+        pdf.as_object_mut()
+            .ok_or(format!("version {from} pdf is not a JSON object"))?
+            .insert(key.into(), default.clone());
+    }
+
+    // States from before the trash existed have none
+    if let Some(trash) = value
+        .pointer_mut("/pdfs/trash")
+        .and_then(Value::as_object_mut)
+    {
+        for trashed in trash.values_mut() {
+            trashed
+                .get_mut("book")
+                .and_then(Value::as_object_mut)
+                .ok_or(format!("version {from} trashed pdf has no book"))?
+                .insert(key.into(), default.clone());
+        }
+    }
+
+    value["version"] = json!(from + 1);
+    Ok(value)
+}
+
+/// Files which were not added because their name is taken, so they are only reported once.
+static COLLISIONS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Warns that the file at `path` is not added since a book called `name` exists already.
+fn report_collision(path: &Path, name: &str) {
+    let mut reported = COLLISIONS.lock().unwrap_or_else(|e| e.into_inner());
+    if !reported.iter().any(|p| p == path) {
+        tracing::warn!("Not adding {path:?}, there already is a book called {name}");
+        reported.push(path.to_path_buf());
+    }
 }
 
 /// Syncs the state in memory with the state on disk.
 /// Should run in the background continously.
+///
+/// Picks up new books from `content_dirs` and compacts the journal into a new
+/// snapshot at `state_location` once it has grown large enough, or right away
+/// if new books were found since those are not journaled.
+#[allow(clippy::too_many_arguments)]
 pub async fn sync_state(
     content_dirs: Vec<PathBuf>,
     state_location: PathBuf,
     pdfs: WrappedPdfCollection,
     reading_history: WrappedReadingStatistics,
+    devices: WrappedDeviceRegistry,
+    sync_users: WrappedSyncUsers,
+    journal: WrappedJournal,
+    events: EventSender,
 ) -> Result<(), Box<dyn Error>> {
-    // check `content_dir` for pdfs not in `state` and add them
+    // check `content_dir` for books not in `state` and add them
     let mut state_ref = pdfs.lock().await;
+    let mut changed = false;
 
     for content_dir in content_dirs {
         let mut files = read_dir(content_dir).await?;
 
         // TODO: Remove things from the state which are NOT within the directory.
         while let Ok(Some(f)) = files.next_entry().await {
-            if f.path().extension() != Some(OsStr::new("pdf")) {
+            let path = f.path();
+            if DocumentKind::from_path(&path).is_none() {
                 continue;
             }
+            let Some(name) = path.file_stem().and_then(OsStr::to_str).map(String::from) else {
+                continue;
+            };
 
-            let name = f.file_name().into_string().unwrap();
-            let path = f.path();
+            // Books keyed by something other than their file stem, like the part
+            // before the first dot older versions used, get their proper key
+            let registered = state_ref
+                .pdfs
+                .iter()
+                .find(|(_, pdf)| pdf.path() == &path)
+                .map(|(key, _)| key.clone());
+            if let Some(key) = registered {
+                if key != name && state_ref.pdfs.contains_key(&name) {
+                    report_collision(&path, &name);
+                } else if key != name {
+                    tracing::info!("Renaming {key} to {name}");
+                    let mut pdf = state_ref.pdfs.remove(&key).unwrap();
+                    pdf.relocate(path);
+                    state_ref.add_book(pdf);
+                    changed = true;
+                }
+                continue;
+            }
 
-            if !state_ref.has_book(&name.split('.').next().unwrap()) {
-                tracing::info!("Added new book {path:?}");
-                let doc = Pdf::new(path);
-                state_ref.add_book(doc);
+            match state_ref.get_book_by_name_mut(&name) {
+                // Books migrated from old state files lack a path and page count.
+                Some(book) if book.total_pages() == 0 => {
+                    tracing::info!("Refreshing migrated book {path:?}");
+                    book.refresh(path);
+                    changed = true;
+                }
+                // Another file with the same stem, `foo.pdf` next to `foo.epub`
+                Some(_) => report_collision(&path, &name),
+                None => {
+                    // One broken file must not stop the library from being synced
+                    let total_pages = match Pdf::get_total_pages(&path) {
+                        Ok(total_pages) => total_pages,
+                        Err(e) => {
+                            tracing::warn!("Skipping {path:?}: {e}");
+                            continue;
+                        }
+                    };
+                    tracing::info!("Added new book {path:?}");
+                    let doc = Pdf::with_total_pages(path, total_pages);
+                    publish(&events, LiveEvent::NewBook { book: doc.clone() });
+                    state_ref.add_book(doc);
+                    changed = true;
+                }
             }
         }
     }
 
-    drop(state_ref);
-
-    // write state to file
-    // TODO: investigate if this not being async gives issues
-    //       also how to make it async
-    let fd = OpenOptions::new()
-        .write(true)
-        .read(false)
-        .truncate(true)
-        // TODO: enforce data location through a exported const
-        //       also need to enforce the location of a specific
-        //       directory to store the state in, like `~/.config``
-        .open(state_location)?;
-
-    let reading_history = &*reading_history.lock().await;
-    let pdfs = &*pdfs.lock().await;
+    // Locks are taken in the same order as the routes do, pdfs -> stats -> devices -> users -> journal
+    let reading_history = reading_history.lock().await;
+    let devices = devices.lock().await;
+    let sync_users = sync_users.lock().await;
+    let mut journal = journal.lock().await;
+
+    if !changed && journal.len() < COMPACT_AFTER {
+        return Ok(());
+    }
+
+    tracing::debug!(
+        "Compacting {} journal entries into {state_location:?}",
+        journal.len()
+    );
     let state = DiscState {
-        pdfs: pdfs.clone(),
+        version: STATE_VERSION,
+        journal_seq: journal.seq(),
+        pdfs: state_ref.clone(),
         reading_history: reading_history.clone(),
+        devices: devices.clone(),
+        sync_users: sync_users.clone(),
     };
+    state.store(&state_location)?;
+    journal.truncate()?;
 
-    serde_json::to_writer_pretty(fd, &state)?;
     Ok(())
 }
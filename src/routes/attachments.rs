@@ -0,0 +1,102 @@
+use std::path::PathBuf;
+
+use axum::{
+    extract::{rejection::PathRejection, Path},
+    response::IntoResponse,
+    Extension, Json,
+};
+use http::{header, HeaderMap, HeaderValue};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+
+use crate::{
+    attachments::{self, Attachment},
+    documents::DocumentCache,
+    state::WrappedPdfCollection,
+};
+
+use super::{error::ApiError, get_pdf::load_document};
+
+/// Lists the files embedded in a PDF.
+#[utoipa::path(
+    get,
+    path = "/api/v1/books/{book}/attachments",
+    params(("book" = String, Path, description = "Name of the book")),
+    responses(
+        (status = 200, description = "Every embedded file", body = [Attachment]),
+        (status = 400, description = "The book is not a PDF", body = ErrorBody),
+        (status = 404, description = "No such book", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn list_attachments(
+    Path(book): Path<String>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+    Extension(documents): Extension<DocumentCache>,
+) -> Result<Json<Vec<Attachment>>, ApiError> {
+    let doc = load_document(&book_state, &content_dirs, &documents, &book).await?;
+    Ok(Json(attachments::list(&doc)))
+}
+
+/// Downloads a file embedded in a PDF.
+#[utoipa::path(
+    get,
+    path = "/api/v1/books/{book}/attachments/{number}",
+    params(
+        ("book" = String, Path, description = "Name of the book"),
+        ("number" = u16, Path, description = "Number of the attachment, starting at 1"),
+    ),
+    responses(
+        (status = 200, description = "The file, with the type the PDF gives for it"),
+        (status = 400, description = "The book is not a PDF", body = ErrorBody),
+        (status = 404, description = "No such book or attachment", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn get_attachment(
+    path: Result<Path<(String, u16)>, PathRejection>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+    Extension(documents): Extension<DocumentCache>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Path((book, number)) = path?;
+    let doc = load_document(&book_state, &content_dirs, &documents, &book).await?;
+    let (attachment, contents) =
+        tokio::task::spawn_blocking(move || attachments::read(&doc, number))
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?
+            .map_err(ApiError::Internal)?
+            .ok_or_else(|| ApiError::not_found(format!("attachment {number} of {book}")))?;
+
+    // Browsers which do not know `filename*` fall back to the ASCII name
+    let ascii_name: String = attachment
+        .name
+        .chars()
+        .map(|c| match c.is_ascii_graphic() || c == ' ' {
+            true if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect();
+    let disposition = format!(
+        "attachment; filename=\"{ascii_name}\"; filename*=UTF-8''{}",
+        utf8_percent_encode(&attachment.name, NON_ALPHANUMERIC)
+    );
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(&attachment.mime)
+            .unwrap_or_else(|_| HeaderValue::from_static("application/octet-stream")),
+    );
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&disposition)
+            .unwrap_or_else(|_| HeaderValue::from_static("attachment")),
+    );
+    // Attachments come from whoever made the PDF, never let the browser guess a type to run
+    headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    Ok((headers, contents))
+}
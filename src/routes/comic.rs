@@ -0,0 +1,79 @@
+use std::path::PathBuf;
+
+use axum::{
+    extract::{rejection::PathRejection, Path},
+    response::IntoResponse,
+    Extension,
+};
+use http::{header, HeaderMap, HeaderValue};
+
+use crate::{
+    comic::Comic,
+    state::{DocumentKind, WrappedPdfCollection},
+};
+
+use super::{error::ApiError, get_pdf::resolve_pdf};
+
+/// Opens the comic book archive `name` of the library.
+pub async fn open_comic(
+    book_state: &WrappedPdfCollection,
+    content_dirs: &[PathBuf],
+    name: &str,
+) -> Result<Comic, ApiError> {
+    let path = resolve_pdf(&*book_state.lock().await, content_dirs, name)?;
+    if DocumentKind::from_path(&path) != Some(DocumentKind::Cbz) {
+        return Err(ApiError::BadRequest(format!(
+            "{name} is not a comic book archive"
+        )));
+    }
+
+    tokio::task::spawn_blocking(move || Comic::open(&path))
+        .await
+        .map_err(|e| ApiError::Internal(e.to_string()))?
+        .map_err(ApiError::Internal)
+}
+
+/// Gets the image of one page of a comic book archive.
+#[utoipa::path(
+    get,
+    path = "/api/v1/books/{book}/pages/{number}/image",
+    params(
+        ("book" = String, Path, description = "Name of the book"),
+        ("number" = u16, Path, description = "Number of the page, starting at 1"),
+    ),
+    responses(
+        (status = 200, description = "The image of the page, in the format it is stored in"),
+        (status = 400, description = "The book is not a comic book archive", body = ErrorBody),
+        (status = 404, description = "No such book or page", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn page_image(
+    path: Result<Path<(String, u16)>, PathRejection>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Path((book, number)) = path?;
+    let comic = open_comic(&book_state, &content_dirs, &book).await?;
+    if number == 0 || number as usize > comic.pages.len() {
+        return Err(ApiError::not_found(format!("page {number} of {book}")));
+    }
+
+    let (mime, contents) = tokio::task::spawn_blocking(move || {
+        comic.read_page(number).map(|(name, contents)| {
+            (
+                mime_guess::from_path(name).first_or_octet_stream(),
+                contents,
+            )
+        })
+    })
+    .await
+    .map_err(|e| ApiError::Internal(e.to_string()))?
+    .map_err(ApiError::Internal)?;
+
+    let mut headers = HeaderMap::new();
+    if let Ok(mime) = HeaderValue::from_str(mime.as_ref()) {
+        headers.insert(header::CONTENT_TYPE, mime);
+    }
+    Ok((headers, contents))
+}
@@ -0,0 +1,48 @@
+use std::path::PathBuf;
+
+use axum::{extract::Path, response::IntoResponse, Extension};
+use http::{header, HeaderValue};
+
+use crate::{covers::CoverCache, state::WrappedPdfCollection};
+
+use super::{error::ApiError, get_pdf::resolve_pdf};
+
+/// Gets a thumbnail of the cover of a book.
+///
+/// Covers are extracted from the book the first time they are asked for and
+/// cached from then on.
+#[utoipa::path(
+    get,
+    path = "/api/v1/books/{book}/cover",
+    params(("book" = String, Path, description = "Name of the book")),
+    responses(
+        (status = 200, description = "The cover as a JPEG", content_type = "image/jpeg"),
+        (status = 404, description = "No such book, or the book has no cover", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn get_cover(
+    Path(book): Path<String>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+    Extension(covers): Extension<CoverCache>,
+) -> Result<impl IntoResponse, ApiError> {
+    let path = resolve_pdf(&*book_state.lock().await, &content_dirs, &book)?;
+
+    let thumbnail = tokio::task::spawn_blocking(move || covers.thumbnail(&path))
+        .await
+        .map_err(|e| ApiError::Internal(e.to_string()))?
+        .map_err(ApiError::Internal)?
+        .ok_or_else(|| ApiError::not_found(format!("cover of {book}")))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static("image/jpeg")),
+            (
+                header::CACHE_CONTROL,
+                HeaderValue::from_static("max-age=3600"),
+            ),
+        ],
+        thumbnail,
+    ))
+}
@@ -0,0 +1,158 @@
+use std::path::PathBuf;
+
+use axum::{
+    extract::{rejection::PathRejection, Path},
+    response::{IntoResponse, Redirect},
+    Extension, Json,
+};
+use http::{header, HeaderMap, HeaderValue};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    epub::Epub,
+    state::{book_name, DocumentKind, WrappedPdfCollection},
+};
+
+use super::{error::ApiError, get_pdf::resolve_pdf};
+
+/// A chapter of an EPUB.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct ChapterInfo {
+    /// The number of the chapter, used as the page in the progress of the book.
+    pub number: u16,
+    pub title: String,
+    /// Where the XHTML of the chapter is served.
+    pub url: String,
+}
+
+/// Characters encoded in the path segments of `file_url`.
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'.')
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Where the file `file` of the archive of `book` is served.
+pub fn file_url(book: &str, file: &str) -> String {
+    let segments: Vec<String> = file
+        .split('/')
+        .map(|s| utf8_percent_encode(s, SEGMENT).to_string())
+        .collect();
+    format!(
+        "/epub/{}/{}",
+        utf8_percent_encode(book, SEGMENT),
+        segments.join("/")
+    )
+}
+
+/// Opens the EPUB `name` of the library.
+pub async fn open_epub(
+    book_state: &WrappedPdfCollection,
+    content_dirs: &[PathBuf],
+    name: &str,
+) -> Result<Epub, ApiError> {
+    let path = resolve_pdf(&*book_state.lock().await, content_dirs, name)?;
+    if DocumentKind::from_path(&path) != Some(DocumentKind::Epub) {
+        return Err(ApiError::BadRequest(format!("{name} is not an EPUB")));
+    }
+
+    tokio::task::spawn_blocking(move || Epub::open(&path))
+        .await
+        .map_err(|e| ApiError::Internal(e.to_string()))?
+        .map_err(ApiError::Internal)
+}
+
+/// Lists the chapters of an EPUB in reading order.
+#[utoipa::path(
+    get,
+    path = "/api/v1/books/{book}/chapters",
+    params(("book" = String, Path, description = "Name of the book")),
+    responses(
+        (status = 200, description = "The chapters of the book", body = [ChapterInfo]),
+        (status = 400, description = "The book is not an EPUB", body = ErrorBody),
+        (status = 404, description = "No such book", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn list_chapters(
+    Path(book): Path<String>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+) -> Result<Json<Vec<ChapterInfo>>, ApiError> {
+    let epub = open_epub(&book_state, &content_dirs, &book).await?;
+    let name = book_name(&book);
+
+    Ok(Json(
+        epub.chapters
+            .iter()
+            .enumerate()
+            .map(|(i, chapter)| ChapterInfo {
+                number: i as u16 + 1,
+                title: chapter.title.clone(),
+                url: file_url(name, &chapter.file),
+            })
+            .collect(),
+    ))
+}
+
+/// Redirects to the XHTML of a chapter.
+#[utoipa::path(
+    get,
+    path = "/api/v1/books/{book}/chapters/{number}",
+    params(
+        ("book" = String, Path, description = "Name of the book"),
+        ("number" = u16, Path, description = "Number of the chapter, starting at 1"),
+    ),
+    responses(
+        (status = 303, description = "The chapter is served at `Location`"),
+        (status = 400, description = "The book is not an EPUB", body = ErrorBody),
+        (status = 404, description = "No such book or chapter", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn get_chapter(
+    path: Result<Path<(String, u16)>, PathRejection>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+) -> Result<Redirect, ApiError> {
+    let Path((book, number)) = path?;
+    let epub = open_epub(&book_state, &content_dirs, &book).await?;
+    let chapter = epub
+        .chapters
+        .get((number as usize).wrapping_sub(1))
+        .ok_or_else(|| ApiError::not_found(format!("chapter {number} of {book}")))?;
+
+    Ok(Redirect::to(&file_url(book_name(&book), &chapter.file)))
+}
+
+/// Serves a file from inside an EPUB, like a chapter or an image used by one.
+///
+/// Scripts in books are not allowed to run, they would run with access to the API.
+pub async fn epub_file(
+    Path((book, file)): Path<(String, String)>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let epub = open_epub(&book_state, &content_dirs, &book).await?;
+    let file = file.trim_start_matches('/').to_string();
+
+    let name = file.clone();
+    let contents = tokio::task::spawn_blocking(move || epub.read_file(&name))
+        .await
+        .map_err(|e| ApiError::Internal(e.to_string()))?
+        .map_err(|_| ApiError::not_found(format!("{file} in {book}")))?;
+
+    let mime = mime_guess::from_path(&file).first_or_octet_stream();
+    let mut headers = HeaderMap::new();
+    if let Ok(mime) = HeaderValue::from_str(mime.as_ref()) {
+        headers.insert(header::CONTENT_TYPE, mime);
+    }
+    headers.insert(
+        header::CONTENT_SECURITY_POLICY,
+        HeaderValue::from_static("script-src 'none'; object-src 'none'"),
+    );
+
+    Ok((headers, contents))
+}
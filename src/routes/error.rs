@@ -0,0 +1,130 @@
+use axum::{
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    response::{IntoResponse, Response},
+    Json,
+};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+use utoipa::ToSchema;
+
+use super::status::BookStatus;
+
+/// Everything that can go wrong in a request, turned into a status code and a `ErrorBody`.
+#[derive(Debug)]
+pub enum ApiError {
+    /// The requested thing does not exist.
+    NotFound(String),
+    /// The request itself is faulty.
+    BadRequest(String),
+    /// The request lacks valid credentials.
+    Unauthorized(String),
+    /// The request is not allowed on this server.
+    Forbidden(String),
+    /// The request was based on an outdated revision of a book.
+    Conflict(BookStatus),
+    /// The request would overwrite something which already exists.
+    AlreadyExists(String),
+    /// The request body is larger than allowed.
+    TooLarge(String),
+    /// Something broke on our end.
+    Internal(String),
+}
+
+/// The JSON body of every error response.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ErrorBody {
+    /// Machine readable kind of error, one of `not_found`, `bad_request`,
+    /// `unauthorized`, `forbidden`, `conflict`, `already_exists`, `too_large` and `internal`.
+    pub error: String,
+    /// Human readable description of what went wrong.
+    pub message: String,
+    /// The current state of the book, only present on conflicts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current: Option<BookStatus>,
+}
+
+impl ApiError {
+    pub fn not_found(what: impl std::fmt::Display) -> Self {
+        ApiError::NotFound(format!("Request for non-existent content: {what}"))
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::Conflict(_) | ApiError::AlreadyExists(_) => StatusCode::CONFLICT,
+            ApiError::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn body(self) -> ErrorBody {
+        let (error, message, current) = match self {
+            ApiError::NotFound(m) => ("not_found", m, None),
+            ApiError::BadRequest(m) => ("bad_request", m, None),
+            ApiError::Unauthorized(m) => ("unauthorized", m, None),
+            ApiError::Forbidden(m) => ("forbidden", m, None),
+            ApiError::Conflict(status) => (
+                "conflict",
+                format!(
+                    "Book has been changed since, it is at page {} revision {}",
+                    status.progress.page, status.progress.revision
+                ),
+                Some(status),
+            ),
+            ApiError::AlreadyExists(m) => ("already_exists", m, None),
+            ApiError::TooLarge(m) => ("too_large", m, None),
+            ApiError::Internal(m) => ("internal", m, None),
+        };
+
+        ErrorBody {
+            error: error.to_string(),
+            message,
+            current,
+        }
+    }
+}
+
+/// Malformed JSON bodies, take the body as `Result<Json<T>, JsonRejection>`
+/// and use `?` to answer with a `ErrorBody` instead of axum's plain text.
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::BadRequest(rejection.to_string())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::BadRequest(rejection.to_string())
+    }
+}
+
+/// Path parameters of the wrong type, like a page number which is not a number.
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::BadRequest(rejection.to_string())
+    }
+}
+
+impl From<std::io::Error> for ApiError {
+    fn from(e: std::io::Error) -> Self {
+        ApiError::Internal(e.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status_code();
+        let body = self.body();
+        if status.is_server_error() {
+            error!("{}", body.message);
+        } else {
+            debug!("{status}: {}", body.message);
+        }
+
+        (status, Json(body)).into_response()
+    }
+}
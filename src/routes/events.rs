@@ -0,0 +1,91 @@
+// Route for pushing changes of the shared state to clients as they happen
+
+use std::convert::Infallible;
+
+use axum::{
+    response::sse::{Event, KeepAlive, Sse},
+    Extension,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+use tracing::{debug, error};
+
+use crate::state::Pdf;
+
+use super::{stats::ReadingStatistics, status::BookStatus};
+
+/// How many events a slow client may fall behind before it starts missing some.
+const EVENT_BACKLOG: usize = 64;
+
+pub type EventSender = broadcast::Sender<LiveEvent>;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveEvent {
+    /// A book was moved to another page.
+    Progress { book: String, status: BookStatus },
+    /// A book was found in one of the content directories.
+    NewBook { book: Pdf },
+    /// A book was renamed or moved to another collection.
+    Relocated { book: String, to: Pdf },
+    /// A book was deleted.
+    Removed { book: String },
+    /// The reading statistics changed.
+    Stats {
+        today: usize,
+        week: usize,
+        month: usize,
+    },
+}
+
+impl LiveEvent {
+    pub fn stats(stats: &ReadingStatistics) -> Self {
+        LiveEvent::Stats {
+            today: stats.last_day(),
+            week: stats.last_week(),
+            month: stats.last_month(),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            LiveEvent::Progress { .. } => "progress",
+            LiveEvent::NewBook { .. } => "new_book",
+            LiveEvent::Relocated { .. } => "relocated",
+            LiveEvent::Removed { .. } => "removed",
+            LiveEvent::Stats { .. } => "stats",
+        }
+    }
+}
+
+pub fn event_channel() -> EventSender {
+    broadcast::channel(EVENT_BACKLOG).0
+}
+
+/// Sends `event` to every connected client, it is fine if there are none.
+pub fn publish(events: &EventSender, event: LiveEvent) {
+    debug!("Publishing {} event", event.name());
+    let _ = events.send(event);
+}
+
+/// Server-Sent Events stream of every `LiveEvent`.
+pub async fn events(
+    Extension(events): Extension<EventSender>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    debug!("Client subscribed to events");
+
+    let stream = BroadcastStream::new(events.subscribe()).filter_map(|event| {
+        // Clients that lag behind simply miss the events they could not keep up with
+        let event = event.ok()?;
+        match Event::default().event(event.name()).json_data(&event) {
+            Ok(e) => Some(Ok(e)),
+            Err(e) => {
+                error!("Failed to serialize {event:?}: {e}");
+                None
+            }
+        }
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
@@ -0,0 +1,182 @@
+use std::{collections::HashMap, ffi::OsStr, path::PathBuf, sync::Arc};
+
+use axum::{
+    extract::{rejection::JsonRejection, Path},
+    response::IntoResponse,
+    Extension, Json,
+};
+use http::{header, HeaderMap, HeaderValue};
+use tracing::info;
+
+use crate::{
+    documents::DocumentCache,
+    forms::{self, check_values, FieldValue, FormField},
+    journal::{JournalEvent, WrappedJournal},
+    kosync::WrappedSyncUsers,
+    state::{DocumentKind, WrappedPdfCollection},
+};
+
+use super::{error::ApiError, get_pdf::load_document, kosync::authorize};
+
+/// Lists the fields of the form of a PDF, with the values stored in the file.
+#[utoipa::path(
+    get,
+    path = "/api/v1/books/{book}/form",
+    params(("book" = String, Path, description = "Name of the book")),
+    responses(
+        (status = 200, description = "Every field, empty for books without a form", body = [FormField]),
+        (status = 400, description = "The book is not a PDF", body = ErrorBody),
+        (status = 404, description = "No such book", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn list_fields(
+    Path(book): Path<String>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+    Extension(documents): Extension<DocumentCache>,
+) -> Result<Json<Vec<FormField>>, ApiError> {
+    let doc = load_document(&book_state, &content_dirs, &documents, &book).await?;
+    Ok(Json(forms::fields(&doc)))
+}
+
+/// Gets what a user filled into the form of a book, by field name.
+///
+/// Users sign in like KOReader does, with the `x-auth-user` and `x-auth-key` headers.
+#[utoipa::path(
+    get,
+    path = "/api/v1/books/{book}/form/values",
+    params(
+        ("book" = String, Path, description = "Name of the book"),
+        ("x-auth-user" = String, Header, description = "Name of the sync user"),
+        ("x-auth-key" = String, Header, description = "Key of the sync user"),
+    ),
+    responses(
+        (status = 200, description = "The values, fields which were not filled in are left out", body = HashMap<String, FieldValue>),
+        (status = 401, description = "No or wrong credentials", body = ErrorBody),
+        (status = 404, description = "No such book", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn get_form_values(
+    Path(book): Path<String>,
+    headers: HeaderMap,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(users): Extension<WrappedSyncUsers>,
+) -> Result<Json<HashMap<String, FieldValue>>, ApiError> {
+    let user = authorize(&headers, &users).await?;
+    let g = book_state.lock().await;
+
+    g.get_book_by_name(&book)
+        .map(|pdf| Json(pdf.form_values(&user)))
+        .ok_or_else(|| ApiError::not_found(book))
+}
+
+/// Replaces what a user filled into the form of a book.
+#[utoipa::path(
+    put,
+    path = "/api/v1/books/{book}/form/values",
+    params(
+        ("book" = String, Path, description = "Name of the book"),
+        ("x-auth-user" = String, Header, description = "Name of the sync user"),
+        ("x-auth-key" = String, Header, description = "Key of the sync user"),
+    ),
+    request_body = HashMap<String, FieldValue>,
+    responses(
+        (status = 200, description = "The values were saved", body = HashMap<String, FieldValue>),
+        (status = 400, description = "A field does not exist or does not take its value", body = ErrorBody),
+        (status = 401, description = "No or wrong credentials", body = ErrorBody),
+        (status = 404, description = "No such book", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn put_form_values(
+    Path(book): Path<String>,
+    headers: HeaderMap,
+    values: Result<Json<HashMap<String, FieldValue>>, JsonRejection>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+    Extension(documents): Extension<DocumentCache>,
+    Extension(users): Extension<WrappedSyncUsers>,
+    Extension(journal): Extension<WrappedJournal>,
+) -> Result<Json<HashMap<String, FieldValue>>, ApiError> {
+    let user = authorize(&headers, &users).await?;
+    let Json(values) = values?;
+    let doc = load_document(&book_state, &content_dirs, &documents, &book).await?;
+    check_values(&forms::fields(&doc), &values).map_err(ApiError::BadRequest)?;
+
+    let mut g = book_state.lock().await;
+    let pdf = g
+        .get_book_by_name_mut(&book)
+        .ok_or_else(|| ApiError::not_found(&book))?;
+    pdf.set_form_values(&user, values.clone());
+    journal
+        .lock()
+        .await
+        .record(JournalEvent::FormValues {
+            book: pdf.name().to_string(),
+            user: user.clone(),
+            values: values.clone(),
+        })
+        .await;
+
+    info!("{user} filled in the form of {book}");
+    Ok(Json(values))
+}
+
+/// Downloads a PDF with what a user filled into its form.
+#[utoipa::path(
+    get,
+    path = "/api/v1/books/{book}/form/filled",
+    params(
+        ("book" = String, Path, description = "Name of the book"),
+        ("x-auth-user" = String, Header, description = "Name of the sync user"),
+        ("x-auth-key" = String, Header, description = "Key of the sync user"),
+    ),
+    responses(
+        (status = 200, description = "The PDF with the values written into its fields", content_type = "application/pdf"),
+        (status = 400, description = "The book is not a PDF", body = ErrorBody),
+        (status = 401, description = "No or wrong credentials", body = ErrorBody),
+        (status = 404, description = "No such book", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn get_filled_pdf(
+    Path(book): Path<String>,
+    headers: HeaderMap,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+    Extension(documents): Extension<DocumentCache>,
+    Extension(users): Extension<WrappedSyncUsers>,
+) -> Result<impl IntoResponse, ApiError> {
+    let user = authorize(&headers, &users).await?;
+    let (values, stem) = {
+        let g = book_state.lock().await;
+        let pdf = g
+            .get_book_by_name(&book)
+            .ok_or_else(|| ApiError::not_found(&book))?;
+        let stem = pdf
+            .path()
+            .file_stem()
+            .and_then(OsStr::to_str)
+            .unwrap_or("book")
+            .replace('"', "");
+        (pdf.form_values(&user), stem)
+    };
+    let doc = load_document(&book_state, &content_dirs, &documents, &book).await?;
+    let pdf = tokio::task::spawn_blocking(move || forms::fill(Arc::unwrap_or_clone(doc), &values))
+        .await
+        .map_err(|e| ApiError::Internal(e.to_string()))?
+        .map_err(ApiError::Internal)?;
+
+    let disposition = HeaderValue::from_str(&format!("attachment; filename=\"{stem}-filled.pdf\""))
+        .unwrap_or_else(|_| HeaderValue::from_static("attachment"));
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(DocumentKind::Pdf.mime()),
+    );
+    headers.insert(header::CONTENT_DISPOSITION, disposition);
+    Ok((headers, pdf))
+}
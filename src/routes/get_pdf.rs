@@ -1,46 +1,246 @@
-use std::path::PathBuf;
+use std::{
+    ffi::OsStr,
+    fs,
+    io::Read,
+    path::{Path as FsPath, PathBuf},
+    sync::Arc,
+};
 
-use axum::{body::StreamBody, extract::Path, response::IntoResponse, Extension};
-use http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::{
+    body::StreamBody,
+    extract::{rejection::QueryRejection, Path, Query},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use http::{header, HeaderMap, HeaderValue};
+use serde::Deserialize;
 use tokio::fs::File;
 use tokio_util::io::ReaderStream;
-use tracing::info;
+use tracing::{info, warn};
+
+use crate::{
+    documents::DocumentCache,
+    extract::extract,
+    outline::outline,
+    state::{DocumentKind, PdfCollection, WrappedPdfCollection},
+    variants::{Variant, VariantCache},
+};
+
+use super::error::ApiError;
+
+/// Finds the file of the book `name` on disc.
+///
+/// Only books registered in `pdfs` are considered, and their path has to
+/// resolve (after following symlinks) to a supported document inside one of `content_dirs`.
+/// Anything else is reported as not found, so requests cannot be used to
+/// probe for files outside of the library.
+pub fn resolve_pdf(
+    pdfs: &PdfCollection,
+    content_dirs: &[PathBuf],
+    name: &str,
+) -> Result<PathBuf, ApiError> {
+    let book = pdfs
+        .get_book_by_name(&name)
+        .ok_or_else(|| ApiError::not_found(name))?;
+
+    let path = fs::canonicalize(book.path()).map_err(|_| ApiError::not_found(name))?;
+    let in_library = content_dirs
+        .iter()
+        .filter_map(|dir| fs::canonicalize(dir).ok())
+        .any(|dir| path.starts_with(dir));
+
+    if !in_library {
+        warn!("Refusing to serve {name}, {path:?} is outside of the content directories");
+        return Err(ApiError::not_found(name));
+    }
+
+    match DocumentKind::from_path(&path) {
+        Some(kind) if has_magic(&path, kind.magic()) => Ok(path),
+        _ => {
+            warn!("Refusing to serve {name}, {path:?} is not a supported document");
+            Err(ApiError::not_found(name))
+        }
+    }
+}
+
+/// Loads the PDF `name` of the library for inspection, parsed once and shared through `documents`.
+pub async fn load_document(
+    book_state: &WrappedPdfCollection,
+    content_dirs: &[PathBuf],
+    documents: &DocumentCache,
+    name: &str,
+) -> Result<Arc<lopdf::Document>, ApiError> {
+    let path = resolve_pdf(&*book_state.lock().await, content_dirs, name)?;
+    if DocumentKind::from_path(&path) != Some(DocumentKind::Pdf) {
+        return Err(ApiError::BadRequest(format!("{name} is not a PDF")));
+    }
+
+    let documents = documents.clone();
+    tokio::task::spawn_blocking(move || documents.get(&path))
+        .await
+        .map_err(|e| ApiError::Internal(e.to_string()))?
+        .map_err(|e| ApiError::Internal(format!("Failed to read {name}: {e}")))
+}
+
+/// Checks for the header every file of a kind starts with, like `%PDF-` for PDFs.
+fn has_magic(path: &FsPath, expected: &[u8]) -> bool {
+    let mut magic = vec![0; expected.len()];
+    fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .map(|_| magic == expected)
+        .unwrap_or(false)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DownloadQuery {
+    /// A reduced version of the book to get instead of the original.
+    variant: Option<Variant>,
+    /// Only these pages, like `7` or `3-10`.
+    pages: Option<String>,
+    /// Only the pages of this entry of the outline.
+    chapter: Option<u16>,
+}
+
+/// Some of the pages of a book.
+#[derive(Clone, Copy, Debug)]
+enum Selection {
+    Pages(u16, u16),
+    Chapter(u16),
+}
+
+impl DownloadQuery {
+    /// The pages asked for if it is not all of them, without checking they exist.
+    fn selection(&self) -> Result<Option<Selection>, ApiError> {
+        match (&self.pages, self.chapter) {
+            (Some(_), Some(_)) => Err(ApiError::BadRequest(
+                "Ask for either pages or a chapter, not both".to_string(),
+            )),
+            (Some(pages), None) => {
+                let (first, last) = pages.split_once('-').unwrap_or((pages, pages));
+                match (first.trim().parse(), last.trim().parse()) {
+                    (Ok(first), Ok(last)) => Ok(Some(Selection::Pages(first, last))),
+                    _ => Err(ApiError::BadRequest(format!(
+                        "Pages should look like 7 or 3-10, not {pages}"
+                    ))),
+                }
+            }
+            (None, Some(chapter)) => Ok(Some(Selection::Chapter(chapter))),
+            (None, None) => Ok(None),
+        }
+    }
+}
 
 /// Helper method for downloading a specified PDF from the server.
+///
+/// `?variant=mobile` gets a smaller version of a PDF, made the first time it is asked for.
+/// `?pages=3-10` or `?chapter=2` get only some pages of a PDF, as a PDF of their own.
 pub async fn get_pdf(
     Path(pdf): Path<String>,
+    query: Result<Query<DownloadQuery>, QueryRejection>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
     Extension(content_dirs): Extension<Vec<PathBuf>>,
-) -> impl IntoResponse {
-    // Add check for pdf extension
+    Extension(variants): Extension<VariantCache>,
+) -> Result<Response, ApiError> {
     info!("Someone wants to download pdf: {pdf}");
+    let Query(query) = query?;
+    let selection = query.selection()?;
 
-    let mut maybe_file: Option<File> = None;
-
-    for dir in content_dirs {
-        maybe_file = tokio::fs::File::open(dir.join(&pdf)).await.ok();
-
-        if maybe_file.is_some() {
-            break;
+    let original = resolve_pdf(&*book_state.lock().await, &content_dirs, &pdf)?;
+    let path = match query.variant {
+        Some(variant) if DocumentKind::from_path(&original) == Some(DocumentKind::Pdf) => {
+            let original = original.clone();
+            tokio::task::spawn_blocking(move || variants.get(&original, variant))
+                .await
+                .map_err(|e| ApiError::Internal(e.to_string()))?
+                .map_err(ApiError::Internal)?
         }
-    }
-
-    let file = match maybe_file {
-        Some(f) => f,
-        None => return Err((StatusCode::NOT_FOUND, format!("File not found: {}", pdf))),
+        Some(_) => {
+            return Err(ApiError::BadRequest(format!(
+                "Only PDFs have variants, {pdf} is not one"
+            )))
+        }
+        None => original.clone(),
     };
+    if let Some(selection) = selection {
+        return extract_pages(&pdf, path, &original, selection).await;
+    }
+    let file = File::open(&path).await?;
 
     // convert the `AsyncRead` into a `Stream`
     let stream = ReaderStream::new(file);
     // convert the `Stream` into an `axum::body::HttpBody`
     let body = StreamBody::new(stream);
 
-    // Create appropriate headers
-    let disposition = HeaderValue::from_str(&format!("attachment; filename={}", &pdf)).unwrap();
-    let ctype = HeaderValue::from_str("application/x-www-form-urlencoded").unwrap();
+    // Create appropriate headers, the name comes from disc rather than the request
+    let kind = DocumentKind::from_path(&original).unwrap_or(DocumentKind::Pdf);
+    let file_name = original
+        .file_name()
+        .and_then(OsStr::to_str)
+        .unwrap_or("book.pdf")
+        .replace('"', "");
+    let disposition = HeaderValue::from_str(&format!("attachment; filename=\"{file_name}\""))
+        .unwrap_or_else(|_| HeaderValue::from_static("attachment"));
+    let ctype = HeaderValue::from_static(kind.mime());
 
     let mut headers = HeaderMap::new();
     headers.insert(header::CONTENT_TYPE, ctype);
     headers.insert(header::CONTENT_DISPOSITION, disposition);
 
-    Ok((headers, body))
+    Ok((headers, body).into_response())
+}
+
+/// Sends the pages `selection` of the PDF at `path`.
+async fn extract_pages(
+    name: &str,
+    path: PathBuf,
+    original: &FsPath,
+    selection: Selection,
+) -> Result<Response, ApiError> {
+    if DocumentKind::from_path(original) != Some(DocumentKind::Pdf) {
+        return Err(ApiError::BadRequest(format!(
+            "Only pages of PDFs can be downloaded, {name} is not one"
+        )));
+    }
+
+    let name = name.to_string();
+    let (first, last, pdf) = tokio::task::spawn_blocking(move || {
+        let doc = lopdf::Document::load(&path)
+            .map_err(|e| ApiError::Internal(format!("Failed to read {name}: {e}")))?;
+        let (first, last) = match selection {
+            Selection::Pages(first, last) => (first, last),
+            Selection::Chapter(chapter) => outline(&doc)
+                .into_iter()
+                .find(|entry| entry.number == chapter)
+                .map(|entry| (entry.page, entry.last_page))
+                .ok_or_else(|| ApiError::not_found(format!("chapter {chapter} of {name}")))?,
+        };
+        let page_count = doc.get_pages().len();
+        if first == 0 || first > last || last as usize > page_count {
+            return Err(ApiError::BadRequest(format!(
+                "Pages {first} to {last} are not in {name}, which has {page_count} pages"
+            )));
+        }
+        let pdf = extract(doc, first, last).map_err(ApiError::Internal)?;
+        Ok((first, last, pdf))
+    })
+    .await
+    .map_err(|e| ApiError::Internal(e.to_string()))??;
+
+    let stem = original
+        .file_stem()
+        .and_then(OsStr::to_str)
+        .unwrap_or("book")
+        .replace('"', "");
+    let disposition = HeaderValue::from_str(&format!(
+        "attachment; filename=\"{stem}-pages-{first}-{last}.pdf\""
+    ))
+    .unwrap_or_else(|_| HeaderValue::from_static("attachment"));
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(DocumentKind::Pdf.mime()),
+    );
+    headers.insert(header::CONTENT_DISPOSITION, disposition);
+    Ok((headers, pdf).into_response())
 }
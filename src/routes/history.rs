@@ -0,0 +1,95 @@
+use axum::{extract::Path, Extension, Json};
+use http::HeaderMap;
+use tracing::info;
+
+use crate::{
+    devices::{register_device, WrappedDeviceRegistry},
+    journal::{JournalEvent, WrappedJournal},
+    state::{PagePosition, Progress, WrappedPdfCollection},
+};
+
+use super::{
+    error::ApiError,
+    events::{publish, EventSender, LiveEvent},
+    status::book_status,
+};
+
+/// Gets the latest positions of a book, oldest first.
+#[utoipa::path(
+    get,
+    path = "/api/v1/books/{book}/history",
+    params(("book" = String, Path, description = "Name of the book")),
+    responses(
+        (status = 200, description = "Latest positions, oldest first", body = [PagePosition]),
+        (status = 404, description = "No such book", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn get_history(
+    Path(pdf): Path<String>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+) -> Result<Json<Vec<PagePosition>>, ApiError> {
+    let g = book_state.lock().await;
+
+    g.get_book_by_name(&pdf)
+        .map(|book| Json(book.history().to_vec()))
+        .ok_or_else(|| ApiError::not_found(pdf))
+}
+
+/// Moves a book back to the last position it had on another page.
+///
+/// The move is added to the history like any other page change, so going
+/// back twice returns to where the book was to begin with.
+#[utoipa::path(
+    post,
+    path = "/api/v1/books/{book}/history/back",
+    params(("book" = String, Path, description = "Name of the book")),
+    responses(
+        (status = 200, description = "The book was moved back", body = Progress),
+        (status = 404, description = "No such book or no previous position", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn go_back(
+    Path(pdf): Path<String>,
+    headers: HeaderMap,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(devices): Extension<WrappedDeviceRegistry>,
+    Extension(journal): Extension<WrappedJournal>,
+    Extension(events): Extension<EventSender>,
+) -> Result<Json<Progress>, ApiError> {
+    let mut g = book_state.lock().await;
+    let device = register_device(&headers, &devices, &journal).await;
+    let book = g
+        .get_book_by_name_mut(&pdf)
+        .ok_or_else(|| ApiError::not_found(&pdf))?;
+
+    let (page, offset) = book
+        .previous_position()
+        .map(|p| (p.page, p.offset))
+        .ok_or_else(|| ApiError::NotFound(format!("No previous position for {pdf}")))?;
+    info!("Going back to page {page} in {pdf}");
+
+    let position = PagePosition::now(page, device.clone()).with_offset(offset);
+    book.access();
+    book.set_page(position);
+    let progress = book.progress();
+    let name = book.name().to_string();
+    let event = LiveEvent::Progress {
+        book: name.clone(),
+        status: book_status(book, &*devices.lock().await),
+    };
+
+    let mut j = journal.lock().await;
+    j.record(JournalEvent::Access { book: name.clone() }).await;
+    j.record(JournalEvent::SetPage {
+        book: name,
+        page,
+        offset,
+        device,
+    })
+    .await;
+
+    publish(&events, event);
+    Ok(Json(progress))
+}
@@ -0,0 +1,275 @@
+// The KOReader sync server protocol, served below `/kosync`.
+//
+// Point KOReader's progress sync at `http://<server>:<port>/kosync` and books
+// read on an e-reader stay in sync with the web viewer.
+
+use axum::{
+    extract::{rejection::JsonRejection, Path},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Extension, Json, Router,
+};
+use chrono::Local;
+use http::{HeaderMap, HeaderValue, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{debug, info};
+
+use crate::{
+    devices::{WrappedDeviceRegistry, DEVICE_ID_HEADER, DEVICE_NAME_HEADER},
+    journal::{JournalEvent, WrappedJournal},
+    kosync::{
+        document_hashes, library_paths, SyncSettings, WrappedDocumentIndex, WrappedSyncUsers,
+    },
+    state::{Pdf, WrappedPdfCollection},
+};
+
+use super::{
+    error::ApiError, events::EventSender, set_page::move_book, stats::WrappedReadingStatistics,
+};
+
+const AUTH_USER_HEADER: &str = "x-auth-user";
+const AUTH_KEY_HEADER: &str = "x-auth-key";
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/users/create", post(create_user))
+        .route("/users/auth", get(auth_user))
+        .route("/syncs/progress", put(update_progress))
+        .route("/syncs/progress/:document", get(get_progress))
+        .route("/healthcheck", get(healthcheck))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Credentials {
+    username: String,
+    password: String,
+}
+
+/// The position of a document as KOReader sends and expects it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DocumentProgress {
+    document: String,
+    /// The page for paged documents like PDFs.
+    progress: String,
+    percentage: f64,
+    device: String,
+    device_id: String,
+    #[serde(default)]
+    timestamp: i64,
+}
+
+/// Checks the credentials KOReader sends along with every request, returns the user.
+pub async fn authorize(headers: &HeaderMap, users: &WrappedSyncUsers) -> Result<String, ApiError> {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+
+    match (header(AUTH_USER_HEADER), header(AUTH_KEY_HEADER)) {
+        (Some(user), Some(key)) if users.lock().await.authorize(user, key) => Ok(user.to_string()),
+        _ => Err(ApiError::Unauthorized(String::from("Unauthorized"))),
+    }
+}
+
+/// Like `authorize`, but requests without any credentials are let through without a user.
+pub async fn optional_user(
+    headers: &HeaderMap,
+    users: &WrappedSyncUsers,
+) -> Result<Option<String>, ApiError> {
+    if !headers.contains_key(AUTH_USER_HEADER) && !headers.contains_key(AUTH_KEY_HEADER) {
+        return Ok(None);
+    }
+    authorize(headers, users).await.map(Some)
+}
+
+/// Finds the book KOReader knows as `document`.
+///
+/// New books are hashed without holding on to the library or the index,
+/// reading large files can take a while.
+async fn find_book(
+    document: &str,
+    pdfs: &WrappedPdfCollection,
+    index: &WrappedDocumentIndex,
+) -> Result<Pdf, ApiError> {
+    let books = library_paths(&*pdfs.lock().await);
+    let outdated = index.lock().await.outdated(&books);
+
+    if !outdated.is_empty() {
+        let hashed = tokio::task::spawn_blocking(move || {
+            outdated
+                .into_iter()
+                .map(|(name, path)| {
+                    let hashes = document_hashes(&path);
+                    (name, path, hashes)
+                })
+                .collect::<Vec<_>>()
+        })
+        .await
+        .map_err(|e| ApiError::Internal(format!("Failed to hash books: {e}")))?;
+
+        let mut index = index.lock().await;
+        for (name, path, hashes) in hashed {
+            index.insert(name, path, hashes);
+        }
+    }
+
+    let book = match index.lock().await.lookup(&books, document) {
+        Some(name) => pdfs.lock().await.get_book_by_name(&name),
+        None => None,
+    };
+    book.ok_or_else(|| ApiError::not_found(format!("document {document}")))
+}
+
+/// Registers a new user, if registration is open.
+pub async fn create_user(
+    credentials: Result<Json<Credentials>, JsonRejection>,
+    Extension(users): Extension<WrappedSyncUsers>,
+    Extension(settings): Extension<SyncSettings>,
+    Extension(journal): Extension<WrappedJournal>,
+) -> Result<impl IntoResponse, ApiError> {
+    if !settings.open_registration {
+        return Err(ApiError::Forbidden(String::from(
+            "Registration is closed on this server",
+        )));
+    }
+
+    let Json(credentials) = credentials?;
+    if credentials.username.is_empty() || credentials.password.is_empty() {
+        return Err(ApiError::BadRequest(String::from(
+            "Username and password must not be empty",
+        )));
+    }
+
+    if !users
+        .lock()
+        .await
+        .register(&credentials.username, &credentials.password)
+    {
+        return Err(ApiError::AlreadyExists(String::from(
+            "Username is already registered",
+        )));
+    }
+
+    info!("Registered sync user {}", credentials.username);
+    journal
+        .lock()
+        .await
+        .record(JournalEvent::SyncUser {
+            username: credentials.username.clone(),
+            key: credentials.password,
+        })
+        .await;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({ "username": credentials.username })),
+    ))
+}
+
+/// Lets KOReader check its credentials.
+pub async fn auth_user(
+    headers: HeaderMap,
+    Extension(users): Extension<WrappedSyncUsers>,
+) -> Result<impl IntoResponse, ApiError> {
+    authorize(&headers, &users).await?;
+    Ok(Json(json!({ "authorized": "OK" })))
+}
+
+/// Moves a book to the page KOReader is at.
+///
+/// KOReader does not know about revisions, the latest update wins.
+#[allow(clippy::too_many_arguments)]
+pub async fn update_progress(
+    headers: HeaderMap,
+    progress: Result<Json<DocumentProgress>, JsonRejection>,
+    Extension(users): Extension<WrappedSyncUsers>,
+    Extension(index): Extension<WrappedDocumentIndex>,
+    Extension(pdfs): Extension<WrappedPdfCollection>,
+    Extension(stats): Extension<WrappedReadingStatistics>,
+    Extension(devices): Extension<WrappedDeviceRegistry>,
+    Extension(journal): Extension<WrappedJournal>,
+    Extension(events): Extension<EventSender>,
+) -> Result<impl IntoResponse, ApiError> {
+    let user = authorize(&headers, &users).await?;
+    let Json(progress) = progress?;
+    let book = find_book(&progress.document, &pdfs, &index).await?;
+
+    let page = progress
+        .progress
+        .parse::<u16>()
+        .unwrap_or_else(|_| (progress.percentage * book.total_pages() as f64).round() as u16);
+    let page = page.clamp(1, book.total_pages().max(1));
+
+    if page != book.current_page() {
+        debug!("{user} moved {} to page {page} in KOReader", book.name());
+        // The device is identified through the same headers every other client uses
+        let mut device = HeaderMap::new();
+        if let (Ok(id), Ok(name)) = (
+            HeaderValue::from_str(&progress.device_id),
+            HeaderValue::from_str(&progress.device),
+        ) {
+            device.insert(DEVICE_ID_HEADER, id);
+            device.insert(DEVICE_NAME_HEADER, name);
+        }
+
+        move_book(
+            book.name(),
+            page,
+            0.0,
+            None,
+            &device,
+            &pdfs,
+            &stats,
+            &devices,
+            &journal,
+            &events,
+        )
+        .await?;
+    }
+
+    Ok(Json(json!({
+        "document": progress.document,
+        "timestamp": Local::now().timestamp(),
+    })))
+}
+
+/// Gets the position of a book in the form KOReader expects.
+pub async fn get_progress(
+    Path(document): Path<String>,
+    headers: HeaderMap,
+    Extension(users): Extension<WrappedSyncUsers>,
+    Extension(index): Extension<WrappedDocumentIndex>,
+    Extension(pdfs): Extension<WrappedPdfCollection>,
+    Extension(devices): Extension<WrappedDeviceRegistry>,
+) -> Result<Response, ApiError> {
+    authorize(&headers, &users).await?;
+    let book = match find_book(&document, &pdfs, &index).await {
+        Ok(book) => book,
+        // Unknown documents simply have no progress yet
+        Err(ApiError::NotFound(_)) => return Ok(Json(json!({})).into_response()),
+        Err(e) => return Err(e),
+    };
+
+    let (device_id, device) = match book.last_read() {
+        Some((id, _)) => (id.clone(), devices.lock().await.name(id).to_string()),
+        None => (String::new(), String::from("pdf-viewer")),
+    };
+    let timestamp = book
+        .history()
+        .last()
+        .map(|p| p.time)
+        .or_else(|| book.last_access().time())
+        .map_or(0, |t| t.timestamp());
+
+    Ok(Json(DocumentProgress {
+        document,
+        progress: book.current_page().to_string(),
+        percentage: book.current_page() as f64 / book.total_pages().max(1) as f64,
+        device,
+        device_id,
+        timestamp,
+    })
+    .into_response())
+}
+
+pub async fn healthcheck() -> impl IntoResponse {
+    Json(json!({ "state": "OK" }))
+}
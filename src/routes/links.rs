@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+
+use axum::{
+    extract::{rejection::PathRejection, Path},
+    Extension, Json,
+};
+
+use crate::{
+    documents::DocumentCache,
+    links::{destinations, page_links, Link, NamedDestination},
+    state::WrappedPdfCollection,
+};
+
+use super::{error::ApiError, get_pdf::load_document};
+
+/// Lists the links on a page of a PDF.
+///
+/// Links within the book are given as a page, links out of it as a URI.
+#[utoipa::path(
+    get,
+    path = "/api/v1/books/{book}/pages/{number}/links",
+    params(
+        ("book" = String, Path, description = "Name of the book"),
+        ("number" = u16, Path, description = "Number of the page, starting at 1"),
+    ),
+    responses(
+        (status = 200, description = "The links on the page", body = [Link]),
+        (status = 400, description = "The book is not a PDF", body = ErrorBody),
+        (status = 404, description = "No such book or page", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn list_links(
+    path: Result<Path<(String, u16)>, PathRejection>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+    Extension(documents): Extension<DocumentCache>,
+) -> Result<Json<Vec<Link>>, ApiError> {
+    let Path((book, number)) = path?;
+    let doc = load_document(&book_state, &content_dirs, &documents, &book).await?;
+    if !doc.get_pages().contains_key(&(number as u32)) {
+        return Err(ApiError::not_found(format!("page {number} of {book}")));
+    }
+    Ok(Json(page_links(&doc, number)))
+}
+
+/// Lists the named destinations of a PDF.
+///
+/// Any of the names can be given to `set_page` instead of a page.
+#[utoipa::path(
+    get,
+    path = "/api/v1/books/{book}/destinations",
+    params(("book" = String, Path, description = "Name of the book")),
+    responses(
+        (status = 200, description = "Every named destination, sorted by name", body = [NamedDestination]),
+        (status = 400, description = "The book is not a PDF", body = ErrorBody),
+        (status = 404, description = "No such book", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn list_destinations(
+    Path(book): Path<String>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+    Extension(documents): Extension<DocumentCache>,
+) -> Result<Json<Vec<NamedDestination>>, ApiError> {
+    let doc = load_document(&book_state, &content_dirs, &documents, &book).await?;
+    Ok(Json(destinations(&doc)))
+}
@@ -1,12 +1,17 @@
 use askama::Template;
 use axum::{response::IntoResponse, Extension, Json};
 use chrono::NaiveDateTime;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
 use rand::seq::SliceRandom;
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
-use crate::state::{AccessTime, Pdf, WrappedPdfCollection};
+use crate::{
+    devices::{DeviceRegistry, WrappedDeviceRegistry},
+    state::{AccessTime, Pdf, WrappedPdfCollection},
+};
 
-use super::stats::WrappedReadingStatistics;
+use super::{stats::WrappedReadingStatistics, v1::collections::collection_name};
 
 #[derive(Template, Serialize, Deserialize)]
 #[template(path = "index.html")]
@@ -16,6 +21,10 @@ pub struct MainTemplate {
     week: usize,
     month: usize,
     message: String,
+    devices: DeviceRegistry,
+    /// Names of the collections books can be uploaded to.
+    #[serde(default)]
+    collections: Vec<String>,
 }
 
 #[allow(dead_code)]
@@ -35,12 +44,30 @@ impl MainTemplate {
     pub fn month(&self) -> usize {
         self.month
     }
+
+    pub fn devices(&self) -> &DeviceRegistry {
+        &self.devices
+    }
+
+    pub fn collections(&self) -> &[String] {
+        self.collections.as_ref()
+    }
+
+    /// The name of `pdf` percent encoded for use in a link.
+    pub fn url_name(&self, pdf: &Pdf) -> String {
+        utf8_percent_encode(pdf.name(), NON_ALPHANUMERIC).to_string()
+    }
+
+    /// Describes who last moved `pdf`, empty if no known device ever has.
+    pub fn last_read(&self, pdf: &Pdf) -> String {
+        self.devices.describe_last_read(pdf).unwrap_or_default()
+    }
 }
 
 // Should ONLY be used to get a random message, not for any other members of the struct.
 impl Default for MainTemplate {
     fn default() -> Self {
-        let messages = vec![
+        let messages = [
             "WOW",
             "study!",
             "stuDYING",
@@ -58,6 +85,8 @@ impl Default for MainTemplate {
             week: Default::default(),
             month: Default::default(),
             message,
+            devices: Default::default(),
+            collections: Default::default(),
         }
     }
 }
@@ -65,6 +94,8 @@ impl Default for MainTemplate {
 async fn get_template(
     book_state: WrappedPdfCollection,
     stats: WrappedReadingStatistics,
+    devices: WrappedDeviceRegistry,
+    content_dirs: &[PathBuf],
 ) -> MainTemplate {
     let guard = book_state.lock().await;
     let mut pdfs: Vec<Pdf> = guard.pdfs().values().cloned().collect();
@@ -90,12 +121,17 @@ async fn get_template(
     let today = stats.last_day();
     let week = stats.last_week();
     let month = stats.last_month();
+    drop(stats);
+
+    let devices = devices.lock().await.clone();
 
     MainTemplate {
         pdfs,
         today,
         week,
         month,
+        devices,
+        collections: content_dirs.iter().map(|d| collection_name(d)).collect(),
         ..Default::default()
     }
 }
@@ -104,8 +140,10 @@ async fn get_template(
 pub async fn main_page(
     Extension(book_state): Extension<WrappedPdfCollection>,
     Extension(stats): Extension<WrappedReadingStatistics>,
+    Extension(devices): Extension<WrappedDeviceRegistry>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
 ) -> impl IntoResponse {
-    let template = get_template(book_state, stats).await;
+    let template = get_template(book_state, stats, devices, &content_dirs).await;
     askama_axum::IntoResponse::into_response(template)
 }
 
@@ -116,7 +154,9 @@ pub async fn main_page(
 pub async fn main_page_untemplated(
     Extension(book_state): Extension<WrappedPdfCollection>,
     Extension(stats): Extension<WrappedReadingStatistics>,
+    Extension(devices): Extension<WrappedDeviceRegistry>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
 ) -> Json<MainTemplate> {
     tracing::info!("Request for the maintemplate API");
-    Json(get_template(book_state, stats).await)
+    Json(get_template(book_state, stats, devices, &content_dirs).await)
 }
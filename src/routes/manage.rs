@@ -0,0 +1,378 @@
+use std::{
+    io,
+    path::{Path as FsPath, PathBuf},
+};
+
+use axum::{
+    extract::{rejection::JsonRejection, Path},
+    Extension, Json,
+};
+use chrono::Local;
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::{
+    devices::WrappedDeviceRegistry,
+    journal::{JournalEvent, WrappedJournal},
+    routes::{
+        error::ApiError,
+        events::{publish, EventSender, LiveEvent},
+        get_pdf::resolve_pdf,
+        upload::sanitize_file_name,
+        v1::{
+            books::Book,
+            collections::{collection_name, collection_of},
+        },
+    },
+    state::{book_name, Progress, TrashedPdf, WrappedPdfCollection},
+    variants::VariantCache,
+};
+
+/// Directory inside every collection deleted books are moved to.
+pub const TRASH_DIR: &str = ".trash";
+
+/// Changes to the name or collection of a book, missing fields stay as they are.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct BookChange {
+    /// The new name of the book, without the extension of its file.
+    pub name: Option<String>,
+    /// The collection to move the book to.
+    pub collection: Option<String>,
+}
+
+/// A deleted book waiting in the trash.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct TrashedBook {
+    pub name: String,
+    /// The collection the book gets restored to.
+    pub collection: Option<String>,
+    pub total_pages: u16,
+    pub progress: Progress,
+    /// Local time the book was deleted at.
+    pub deleted: String,
+}
+
+impl TrashedBook {
+    pub fn new(trashed: &TrashedPdf, content_dirs: &[PathBuf]) -> Self {
+        TrashedBook {
+            name: trashed.book.name().to_string(),
+            collection: collection_of(trashed.book.path(), content_dirs)
+                .map(|d| collection_name(d)),
+            total_pages: trashed.book.total_pages(),
+            progress: trashed.book.progress(),
+            deleted: trashed.deleted.format("%Y-%m-%d %H:%M:%S").to_string(),
+        }
+    }
+}
+
+/// Moves a file, falling back to copying for moves between file systems.
+pub async fn move_file(from: &FsPath, to: &FsPath) -> io::Result<()> {
+    if fs::rename(from, to).await.is_err() {
+        fs::copy(from, to).await?;
+        fs::remove_file(from).await?;
+    }
+    Ok(())
+}
+
+fn find_collection<'a>(name: &str, content_dirs: &'a [PathBuf]) -> Result<&'a PathBuf, ApiError> {
+    content_dirs
+        .iter()
+        .find(|dir| collection_name(dir) == name)
+        .ok_or_else(|| ApiError::not_found(format!("collection {name}")))
+}
+
+/// Renames a book and/or moves it to another collection.
+///
+/// The progress of the book moves along with it.
+#[utoipa::path(
+    patch,
+    path = "/api/v1/books/{book}",
+    params(("book" = String, Path, description = "Name of the book")),
+    request_body = BookChange,
+    responses(
+        (status = 200, description = "The book after the change", body = Book),
+        (status = 400, description = "The new name is not valid", body = ErrorBody),
+        (status = 404, description = "No such book or collection", body = ErrorBody),
+        (status = 409, description = "A book with the new name exists", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn update_book(
+    Path(book): Path<String>,
+    change: Result<Json<BookChange>, JsonRejection>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(devices): Extension<WrappedDeviceRegistry>,
+    Extension(variants): Extension<VariantCache>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+    Extension(journal): Extension<WrappedJournal>,
+    Extension(events): Extension<EventSender>,
+) -> Result<Json<Book>, ApiError> {
+    let Json(change) = change?;
+    let mut g = book_state.lock().await;
+    let file = resolve_pdf(&g, &content_dirs, &book)?;
+    let pdf = g
+        .get_book_by_name(&book)
+        .ok_or_else(|| ApiError::not_found(&book))?;
+
+    let extension = pdf.kind().extension();
+    let file_name = match &change.name {
+        Some(name) => {
+            let file_name = format!("{name}.{extension}");
+            if sanitize_file_name(&file_name)? != file_name {
+                return Err(ApiError::BadRequest(format!(
+                    "{name:?} is not a valid name for a book"
+                )));
+            }
+            file_name
+        }
+        None => format!("{}.{extension}", pdf.name()),
+    };
+    let dir = match &change.collection {
+        Some(name) => find_collection(name, &content_dirs)?,
+        None => collection_of(pdf.path(), &content_dirs).unwrap_or(&content_dirs[0]),
+    };
+
+    let path = dir.join(&file_name);
+    if &path == pdf.path() {
+        return Ok(Json(Book::new(&pdf, &*devices.lock().await, &content_dirs)));
+    }
+
+    let new_name = book_name(&file_name);
+    let taken = new_name != pdf.name() && g.get_book_by_name(&new_name).is_some();
+    if taken || fs::metadata(&path).await.is_ok() {
+        return Err(ApiError::AlreadyExists(format!(
+            "A book called {new_name} already exists"
+        )));
+    }
+
+    info!("Moving {book} from {file:?} to {path:?}");
+    move_file(&file, &path).await?;
+    variants.forget(pdf.path());
+    let moved = g
+        .relocate(&book, path.clone())
+        .cloned()
+        .ok_or_else(|| ApiError::not_found(&book))?;
+
+    let response = Book::new(&moved, &*devices.lock().await, &content_dirs);
+    journal
+        .lock()
+        .await
+        .record(JournalEvent::Relocate {
+            book: pdf.name().to_string(),
+            path,
+        })
+        .await;
+    drop(g);
+
+    publish(
+        &events,
+        LiveEvent::Relocated {
+            book: pdf.name().to_string(),
+            to: moved,
+        },
+    );
+    Ok(Json(response))
+}
+
+/// Deletes a book.
+///
+/// The file is moved to the trash of its collection and can be restored
+/// with all its progress until it is purged.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/books/{book}",
+    params(("book" = String, Path, description = "Name of the book")),
+    responses(
+        (status = 200, description = "The book was moved to the trash", body = TrashedBook),
+        (status = 404, description = "No such book", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn delete_book(
+    Path(book): Path<String>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(variants): Extension<VariantCache>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+    Extension(journal): Extension<WrappedJournal>,
+    Extension(events): Extension<EventSender>,
+) -> Result<Json<TrashedBook>, ApiError> {
+    let mut g = book_state.lock().await;
+    let file = resolve_pdf(&g, &content_dirs, &book)?;
+    let pdf = g
+        .get_book_by_name(&book)
+        .ok_or_else(|| ApiError::not_found(&book))?;
+
+    let dir = collection_of(pdf.path(), &content_dirs)
+        .unwrap_or(&content_dirs[0])
+        .join(TRASH_DIR);
+    fs::create_dir_all(&dir).await?;
+
+    // A book of the same name deleted earlier makes room for this one
+    if let Some(old) = g.purge(&pdf.name()) {
+        remove_trashed(&old, &variants).await?;
+    }
+
+    let trash_file = dir.join(format!("{}.{}", pdf.name(), pdf.kind().extension()));
+    info!("Moving {book} to the trash at {trash_file:?}");
+    move_file(&file, &trash_file).await?;
+    let trashed = g
+        .trash(&pdf.name(), trash_file.clone(), Local::now())
+        .map(|t| TrashedBook::new(t, &content_dirs))
+        .ok_or_else(|| ApiError::not_found(&book))?;
+
+    journal
+        .lock()
+        .await
+        .record(JournalEvent::Trash {
+            book: pdf.name().to_string(),
+            file: trash_file,
+        })
+        .await;
+    drop(g);
+
+    publish(
+        &events,
+        LiveEvent::Removed {
+            book: pdf.name().to_string(),
+        },
+    );
+    Ok(Json(trashed))
+}
+
+/// Lists the deleted books, most recently deleted first.
+#[utoipa::path(
+    get,
+    path = "/api/v1/trash",
+    responses((status = 200, description = "Every book in the trash", body = [TrashedBook])),
+    tag = "trash"
+)]
+pub async fn list_trash(
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+) -> Json<Vec<TrashedBook>> {
+    let g = book_state.lock().await;
+    let mut trashed: Vec<&TrashedPdf> = g.trash.values().collect();
+    trashed.sort_by_key(|t| std::cmp::Reverse(t.deleted));
+
+    Json(
+        trashed
+            .into_iter()
+            .map(|t| TrashedBook::new(t, &content_dirs))
+            .collect(),
+    )
+}
+
+/// Brings a deleted book back to where it was, with all its progress.
+#[utoipa::path(
+    post,
+    path = "/api/v1/trash/{book}/restore",
+    params(("book" = String, Path, description = "Name of the book")),
+    responses(
+        (status = 200, description = "The restored book", body = Book),
+        (status = 404, description = "No such book in the trash", body = ErrorBody),
+        (status = 409, description = "A book with the same name exists", body = ErrorBody),
+    ),
+    tag = "trash"
+)]
+pub async fn restore_book(
+    Path(book): Path<String>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(devices): Extension<WrappedDeviceRegistry>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+    Extension(journal): Extension<WrappedJournal>,
+    Extension(events): Extension<EventSender>,
+) -> Result<Json<Book>, ApiError> {
+    let mut g = book_state.lock().await;
+    let name = book_name(&book).to_string();
+    let trashed = g
+        .trash
+        .get(&name)
+        .cloned()
+        .ok_or_else(|| ApiError::not_found(&book))?;
+
+    if g.get_book_by_name(&name).is_some() || fs::metadata(trashed.book.path()).await.is_ok() {
+        return Err(ApiError::AlreadyExists(format!(
+            "A book called {name} already exists"
+        )));
+    }
+
+    let original = trashed.book.path();
+    let path = match collection_of(original, &content_dirs) {
+        Some(_) => original.to_path_buf(),
+        // The collection is gone, fall back to the first one
+        None => content_dirs[0].join(format!("{name}.{}", trashed.book.kind().extension())),
+    };
+
+    info!("Restoring {name} to {path:?}");
+    move_file(&trashed.file, &path).await?;
+    g.restore(&name);
+    let mut j = journal.lock().await;
+    j.record(JournalEvent::Restore { book: name.clone() }).await;
+    if &path != original {
+        g.relocate(&name, path.clone());
+        j.record(JournalEvent::Relocate {
+            book: name.clone(),
+            path,
+        })
+        .await;
+    }
+    drop(j);
+
+    let pdf = g
+        .get_book_by_name(&name)
+        .ok_or_else(|| ApiError::not_found(&name))?;
+    let response = Book::new(&pdf, &*devices.lock().await, &content_dirs);
+    drop(g);
+
+    publish(&events, LiveEvent::NewBook { book: pdf });
+    Ok(Json(response))
+}
+
+/// Removes a deleted book for good.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/trash/{book}",
+    params(("book" = String, Path, description = "Name of the book")),
+    responses(
+        (status = 204, description = "The book is gone"),
+        (status = 404, description = "No such book in the trash", body = ErrorBody),
+    ),
+    tag = "trash"
+)]
+pub async fn purge_book(
+    Path(book): Path<String>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(variants): Extension<VariantCache>,
+    Extension(journal): Extension<WrappedJournal>,
+) -> Result<StatusCode, ApiError> {
+    let mut g = book_state.lock().await;
+    let name = book_name(&book).to_string();
+    let trashed = g
+        .trash
+        .get(&name)
+        .ok_or_else(|| ApiError::not_found(&book))?;
+
+    info!("Purging {name} from the trash");
+    remove_trashed(trashed, &variants).await?;
+    g.purge(&name);
+    journal
+        .lock()
+        .await
+        .record(JournalEvent::Purge { book: name })
+        .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Deletes the file of a trashed book and its cached variants, it being gone already is fine.
+async fn remove_trashed(trashed: &TrashedPdf, variants: &VariantCache) -> io::Result<()> {
+    variants.forget(trashed.book.path());
+    match fs::remove_file(&trashed.file).await {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
@@ -1,7 +1,26 @@
+pub mod attachments;
+pub mod comic;
+pub mod cover;
+pub mod epub;
+pub mod error;
+pub mod events;
+pub mod forms;
 pub mod get_pdf;
+pub mod history;
+pub mod kosync;
+pub mod links;
 pub mod main_page;
+pub mod manage;
+pub mod opds;
+pub mod outline;
+pub mod pages;
+pub mod preferences;
+pub mod search;
 pub mod set_page;
 pub mod static_path;
 pub mod stats;
 pub mod status;
+pub mod text;
+pub mod upload;
+pub mod v1;
 pub mod view_pdf;
@@ -0,0 +1,359 @@
+// OPDS 1.2 catalog of the library, so e-reader apps like KOReader or Librera
+// can browse and download books without going through the web interface.
+//
+// The root is a navigation feed linking to acquisition feeds of books, every
+// book links to `get_pdf` for the download.
+
+use std::{cmp::Reverse, path::PathBuf};
+
+use askama::Template;
+use axum::{
+    extract::{Path, Query},
+    response::IntoResponse,
+    routing::get,
+    Extension, Router,
+};
+use chrono::Local;
+use http::{header, HeaderValue};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Deserialize;
+
+use crate::state::{Pdf, WrappedPdfCollection};
+
+use super::{
+    error::ApiError,
+    v1::collections::{collection_name, collection_of},
+};
+
+const NAVIGATION: &str = "application/atom+xml;profile=opds-catalog;kind=navigation";
+const ACQUISITION: &str = "application/atom+xml;profile=opds-catalog;kind=acquisition";
+
+/// How many books the feed of recently read books holds.
+pub const RECENT_BOOKS: usize = 25;
+
+const SEARCH_DESCRIPTION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<OpenSearchDescription xmlns="http://a9.com/-/spec/opensearch/1.1/">
+	<ShortName>pdf-viewer</ShortName>
+	<Description>Search the books by name</Description>
+	<InputEncoding>UTF-8</InputEncoding>
+	<OutputEncoding>UTF-8</OutputEncoding>
+	<Url type="application/atom+xml;profile=opds-catalog;kind=acquisition" template="/opds/search?q={searchTerms}"/>
+</OpenSearchDescription>
+"#;
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/", get(root))
+        .route("/books", get(all_books))
+        .route("/recent", get(recent_books))
+        .route("/in-progress", get(books_in_progress))
+        .route("/collections/:collection", get(collection_books))
+        .route("/search", get(search))
+        .route("/search.xml", get(search_description))
+}
+
+/// Percent encodes `s` for use in a path segment.
+fn encode(s: &str) -> String {
+    utf8_percent_encode(s, NON_ALPHANUMERIC).to_string()
+}
+
+struct NavigationEntry {
+    id: String,
+    title: String,
+    content: String,
+    rel: &'static str,
+    href: String,
+}
+
+#[derive(Template)]
+#[template(path = "opds/navigation.xml")]
+struct NavigationFeed {
+    id: String,
+    title: String,
+    updated: String,
+    entries: Vec<NavigationEntry>,
+}
+
+/// A book as an entry of an acquisition feed.
+struct OpdsBook {
+    id: String,
+    title: String,
+    updated: String,
+    collection: Option<String>,
+    summary: String,
+    download: String,
+    mime: &'static str,
+    view: String,
+    cover: String,
+}
+
+impl OpdsBook {
+    fn new(pdf: &Pdf, content_dirs: &[PathBuf]) -> Self {
+        let name = encode(pdf.name());
+        OpdsBook {
+            id: format!("urn:pdf-viewer:book:{name}"),
+            title: pdf.name().to_string(),
+            updated: pdf
+                .last_access()
+                .time()
+                .unwrap_or_else(Local::now)
+                .to_rfc3339(),
+            collection: collection_of(pdf.path(), content_dirs).map(|d| collection_name(d)),
+            summary: format!(
+                "Page {} of {} ({}% read)",
+                pdf.current_page(),
+                pdf.total_pages(),
+                pdf.percentage_read()
+            ),
+            download: format!("/get_pdf/{name}.{}", pdf.kind().extension()),
+            mime: pdf.kind().mime(),
+            view: format!("/view/{name}.{}", pdf.kind().extension()),
+            cover: format!("/api/v1/books/{name}/cover"),
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "opds/acquisition.xml")]
+struct AcquisitionFeed {
+    id: String,
+    title: String,
+    updated: String,
+    self_href: String,
+    books: Vec<OpdsBook>,
+}
+
+impl AcquisitionFeed {
+    fn new(
+        id: &str,
+        title: impl Into<String>,
+        self_href: String,
+        pdfs: &[&Pdf],
+        content_dirs: &[PathBuf],
+    ) -> Self {
+        AcquisitionFeed {
+            id: format!("urn:pdf-viewer:{id}"),
+            title: title.into(),
+            updated: Local::now().to_rfc3339(),
+            self_href,
+            books: pdfs
+                .iter()
+                .map(|pdf| OpdsBook::new(pdf, content_dirs))
+                .collect(),
+        }
+    }
+}
+
+/// Renders `template` as a response of the OPDS `content_type`.
+fn feed(
+    template: impl Template,
+    content_type: &'static str,
+) -> Result<impl IntoResponse, ApiError> {
+    let body = template
+        .render()
+        .map_err(|e| ApiError::Internal(format!("Failed to render OPDS feed: {e}")))?;
+
+    Ok((
+        [(header::CONTENT_TYPE, HeaderValue::from_static(content_type))],
+        body,
+    ))
+}
+
+/// The books matching `filter`, sorted by name.
+fn sorted_books(pdfs: &[Pdf], filter: impl Fn(&Pdf) -> bool) -> Vec<&Pdf> {
+    let mut books: Vec<&Pdf> = pdfs.iter().filter(|pdf| filter(pdf)).collect();
+    books.sort_by(|a, b| a.name().cmp(b.name()));
+    books
+}
+
+async fn all_pdfs(book_state: &WrappedPdfCollection) -> Vec<Pdf> {
+    book_state.lock().await.pdfs.values().cloned().collect()
+}
+
+/// The root of the catalog.
+pub async fn root(
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mut entries = vec![
+        NavigationEntry {
+            id: "urn:pdf-viewer:books".into(),
+            title: "All books".into(),
+            content: "Every book in the library".into(),
+            rel: "subsection",
+            href: "/opds/books".into(),
+        },
+        NavigationEntry {
+            id: "urn:pdf-viewer:recent".into(),
+            title: "Recently read".into(),
+            content: "The books which were opened last".into(),
+            rel: "http://opds-spec.org/sort/new",
+            href: "/opds/recent".into(),
+        },
+        NavigationEntry {
+            id: "urn:pdf-viewer:in-progress".into(),
+            title: "In progress".into(),
+            content: "Books which are started but not finished".into(),
+            rel: "subsection",
+            href: "/opds/in-progress".into(),
+        },
+    ];
+
+    entries.extend(content_dirs.iter().map(|dir| {
+        let name = collection_name(dir);
+        NavigationEntry {
+            id: format!("urn:pdf-viewer:collection:{}", encode(&name)),
+            content: format!("The books in {name}"),
+            rel: "subsection",
+            href: format!("/opds/collections/{}", encode(&name)),
+            title: name,
+        }
+    }));
+
+    feed(
+        NavigationFeed {
+            id: "urn:pdf-viewer:root".into(),
+            title: "pdf-viewer".into(),
+            updated: Local::now().to_rfc3339(),
+            entries,
+        },
+        NAVIGATION,
+    )
+}
+
+/// Every book.
+pub async fn all_books(
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let pdfs = all_pdfs(&book_state).await;
+    let books = sorted_books(&pdfs, |_| true);
+
+    feed(
+        AcquisitionFeed::new(
+            "books",
+            "All books",
+            "/opds/books".into(),
+            &books,
+            &content_dirs,
+        ),
+        ACQUISITION,
+    )
+}
+
+/// The books opened last, most recent first.
+pub async fn recent_books(
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let pdfs = all_pdfs(&book_state).await;
+    let mut books: Vec<&Pdf> = pdfs
+        .iter()
+        .filter(|pdf| pdf.last_access().time().is_some())
+        .collect();
+    books.sort_by_key(|pdf| Reverse(pdf.last_access().time()));
+    books.truncate(RECENT_BOOKS);
+
+    feed(
+        AcquisitionFeed::new(
+            "recent",
+            "Recently read",
+            "/opds/recent".into(),
+            &books,
+            &content_dirs,
+        ),
+        ACQUISITION,
+    )
+}
+
+/// Books which are started but not finished.
+pub async fn books_in_progress(
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let pdfs = all_pdfs(&book_state).await;
+    let books = sorted_books(&pdfs, |pdf| {
+        pdf.current_page() > 1 && pdf.current_page() < pdf.total_pages()
+    });
+
+    feed(
+        AcquisitionFeed::new(
+            "in-progress",
+            "In progress",
+            "/opds/in-progress".into(),
+            &books,
+            &content_dirs,
+        ),
+        ACQUISITION,
+    )
+}
+
+/// The books of one collection.
+pub async fn collection_books(
+    Path(collection): Path<String>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let dir = content_dirs
+        .iter()
+        .find(|dir| collection_name(dir) == collection)
+        .ok_or_else(|| ApiError::not_found(format!("collection {collection}")))?;
+
+    let pdfs = all_pdfs(&book_state).await;
+    let books = sorted_books(&pdfs, |pdf| {
+        collection_of(pdf.path(), &content_dirs) == Some(dir)
+    });
+
+    feed(
+        AcquisitionFeed::new(
+            &format!("collection:{}", encode(&collection)),
+            collection.clone(),
+            format!("/opds/collections/{}", encode(&collection)),
+            &books,
+            &content_dirs,
+        ),
+        ACQUISITION,
+    )
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    q: String,
+}
+
+/// Books whose name contains the search terms, ignoring case.
+pub async fn search(
+    Query(query): Query<SearchQuery>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let terms: Vec<String> = query.q.split_whitespace().map(str::to_lowercase).collect();
+
+    let pdfs = all_pdfs(&book_state).await;
+    let books = sorted_books(&pdfs, |pdf| {
+        let name = pdf.name().to_lowercase();
+        !terms.is_empty() && terms.iter().all(|t| name.contains(t))
+    });
+
+    feed(
+        AcquisitionFeed::new(
+            "search",
+            format!("Search for {}", query.q),
+            format!("/opds/search?q={}", encode(&query.q)),
+            &books,
+            &content_dirs,
+        ),
+        ACQUISITION,
+    )
+}
+
+/// The OpenSearch description telling clients how to search the catalog.
+pub async fn search_description() -> impl IntoResponse {
+    (
+        [(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/opensearchdescription+xml"),
+        )],
+        SEARCH_DESCRIPTION,
+    )
+}
@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+
+use axum::{extract::Path, Extension, Json};
+
+use crate::{
+    documents::DocumentCache,
+    outline::{outline, OutlineEntry},
+    state::WrappedPdfCollection,
+};
+
+use super::{error::ApiError, get_pdf::load_document};
+
+/// Lists the outline of a PDF with the pages each chapter spans.
+///
+/// A chapter can be downloaded on its own with `/get_pdf/{book}?chapter={number}`.
+#[utoipa::path(
+    get,
+    path = "/api/v1/books/{book}/outline",
+    params(("book" = String, Path, description = "Name of the book")),
+    responses(
+        (status = 200, description = "Every entry going to a page, in reading order", body = [OutlineEntry]),
+        (status = 400, description = "The book is not a PDF", body = ErrorBody),
+        (status = 404, description = "No such book", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn list_outline(
+    Path(book): Path<String>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+    Extension(documents): Extension<DocumentCache>,
+) -> Result<Json<Vec<OutlineEntry>>, ApiError> {
+    let doc = load_document(&book_state, &content_dirs, &documents, &book).await?;
+    Ok(Json(outline(&doc)))
+}
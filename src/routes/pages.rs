@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+
+use axum::{extract::Path, Extension, Json};
+
+use crate::{
+    documents::DocumentCache,
+    geometry::{page_geometry, PageGeometry},
+    state::WrappedPdfCollection,
+};
+
+use super::{error::ApiError, get_pdf::load_document};
+
+/// Lists the size and rotation of every page of a PDF.
+///
+/// Lets clients size the canvas and pick a zoom before a page is rendered.
+#[utoipa::path(
+    get,
+    path = "/api/v1/books/{book}/pages",
+    params(("book" = String, Path, description = "Name of the book")),
+    responses(
+        (status = 200, description = "Every page, in order", body = [PageGeometry]),
+        (status = 400, description = "The book is not a PDF", body = ErrorBody),
+        (status = 404, description = "No such book", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn list_pages(
+    Path(book): Path<String>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+    Extension(documents): Extension<DocumentCache>,
+) -> Result<Json<Vec<PageGeometry>>, ApiError> {
+    let doc = load_document(&book_state, &content_dirs, &documents, &book).await?;
+    Ok(Json(page_geometry(&doc)))
+}
@@ -0,0 +1,101 @@
+use axum::{
+    extract::{rejection::JsonRejection, Path},
+    Extension, Json,
+};
+use http::HeaderMap;
+use tracing::info;
+
+use crate::{
+    journal::{JournalEvent, WrappedJournal},
+    kosync::WrappedSyncUsers,
+    state::{ReaderPreferences, WrappedPdfCollection},
+};
+
+use super::{error::ApiError, kosync::optional_user};
+
+/// Gets how a book is shown in the viewer.
+///
+/// Sync users who sign in with the `x-auth-user` and `x-auth-key` headers get
+/// their own settings, everyone else gets the ones of the book.
+#[utoipa::path(
+    get,
+    path = "/api/v1/books/{book}/preferences",
+    params(
+        ("book" = String, Path, description = "Name of the book"),
+        ("x-auth-user" = Option<String>, Header, description = "Name of the sync user"),
+        ("x-auth-key" = Option<String>, Header, description = "Key of the sync user"),
+    ),
+    responses(
+        (status = 200, description = "The viewer settings of the user, or of the book if the user has none", body = ReaderPreferences),
+        (status = 401, description = "Wrong credentials", body = ErrorBody),
+        (status = 404, description = "No such book", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn get_preferences(
+    Path(book): Path<String>,
+    headers: HeaderMap,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(users): Extension<WrappedSyncUsers>,
+) -> Result<Json<ReaderPreferences>, ApiError> {
+    let user = optional_user(&headers, &users).await?;
+    let g = book_state.lock().await;
+
+    g.get_book_by_name(&book)
+        .map(|pdf| Json(pdf.preferences(user.as_deref()).clone()))
+        .ok_or_else(|| ApiError::not_found(book))
+}
+
+/// Changes how a book is shown in the viewer, on every device.
+///
+/// Signed in sync users change their own settings, everyone else the ones of the book.
+#[utoipa::path(
+    put,
+    path = "/api/v1/books/{book}/preferences",
+    params(
+        ("book" = String, Path, description = "Name of the book"),
+        ("x-auth-user" = Option<String>, Header, description = "Name of the sync user"),
+        ("x-auth-key" = Option<String>, Header, description = "Key of the sync user"),
+    ),
+    request_body = ReaderPreferences,
+    responses(
+        (status = 200, description = "The settings were saved", body = ReaderPreferences),
+        (status = 400, description = "A setting is out of range", body = ErrorBody),
+        (status = 401, description = "Wrong credentials", body = ErrorBody),
+        (status = 404, description = "No such book", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn put_preferences(
+    Path(book): Path<String>,
+    headers: HeaderMap,
+    preferences: Result<Json<ReaderPreferences>, JsonRejection>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(users): Extension<WrappedSyncUsers>,
+    Extension(journal): Extension<WrappedJournal>,
+) -> Result<Json<ReaderPreferences>, ApiError> {
+    let user = optional_user(&headers, &users).await?;
+    let Json(preferences) = preferences?;
+    preferences.validate().map_err(ApiError::BadRequest)?;
+
+    let mut g = book_state.lock().await;
+    let pdf = g
+        .get_book_by_name_mut(&book)
+        .ok_or_else(|| ApiError::not_found(&book))?;
+    pdf.set_preferences(user.as_deref(), preferences.clone());
+    journal
+        .lock()
+        .await
+        .record(JournalEvent::Preferences {
+            book: pdf.name().to_string(),
+            user: user.clone(),
+            preferences: preferences.clone(),
+        })
+        .await;
+
+    match user {
+        Some(user) => info!("{user} changed their viewer settings of {book}"),
+        None => info!("Changed the viewer settings of {book}"),
+    }
+    Ok(Json(preferences))
+}
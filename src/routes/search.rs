@@ -0,0 +1,115 @@
+use std::path::PathBuf;
+
+use axum::{
+    extract::{rejection::QueryRejection, Path, Query},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    documents::DocumentCache,
+    state::WrappedPdfCollection,
+    text::{page_lines, search, Hit},
+};
+
+use super::{error::ApiError, get_pdf::load_document};
+
+/// Searches stop after this many hits, so common words do not make huge responses.
+const MAX_HITS: usize = 500;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    q: String,
+}
+
+/// The hits of a search on one page.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct PageHits {
+    pub page: u16,
+    pub hits: Vec<Hit>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct SearchResults {
+    pub query: String,
+    /// The pages with at least one hit, in order.
+    pub pages: Vec<PageHits>,
+    /// The number of hits on all pages.
+    pub total: usize,
+    /// Whether the search stopped before the end of the book because there were too many hits.
+    pub truncated: bool,
+}
+
+/// Searches the text of a PDF.
+///
+/// Case and line breaks are ignored. Boxes are in PDF points from the bottom
+/// left corner of the page, so they can be highlighted on the rendered page.
+#[utoipa::path(
+    get,
+    path = "/api/v1/books/{book}/search",
+    params(
+        ("book" = String, Path, description = "Name of the book"),
+        ("q" = String, Query, description = "The text to search for"),
+    ),
+    responses(
+        (status = 200, description = "Every hit, page by page", body = SearchResults),
+        (status = 400, description = "The book is not a PDF or the query is empty", body = ErrorBody),
+        (status = 404, description = "No such book", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn search_book(
+    Path(book): Path<String>,
+    query: Result<Query<SearchQuery>, QueryRejection>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+    Extension(documents): Extension<DocumentCache>,
+) -> Result<Json<SearchResults>, ApiError> {
+    let Query(SearchQuery { q }) = query?;
+    if q.trim().is_empty() {
+        return Err(ApiError::BadRequest(String::from("The query is empty")));
+    }
+    let doc = load_document(&book_state, &content_dirs, &documents, &book).await?;
+
+    tokio::task::spawn_blocking(move || {
+        let mut results = SearchResults {
+            query: q,
+            pages: vec![],
+            total: 0,
+            truncated: false,
+        };
+        for (number, page) in doc.get_pages() {
+            let lines = match page_lines(&doc, page) {
+                Ok(lines) => lines,
+                Err(e) => {
+                    tracing::debug!("Skipping page {number} of {book}: {e}");
+                    continue;
+                }
+            };
+
+            let mut hits = search(&lines, &results.query);
+            if hits.is_empty() {
+                continue;
+            }
+            if results.total + hits.len() > MAX_HITS {
+                hits.truncate(MAX_HITS - results.total);
+                results.truncated = true;
+            }
+            if !hits.is_empty() {
+                results.total += hits.len();
+                results.pages.push(PageHits {
+                    page: number as u16,
+                    hits,
+                });
+            }
+            if results.truncated {
+                break;
+            }
+        }
+        results
+    })
+    .await
+    .map(Json)
+    .map_err(|e| ApiError::Internal(e.to_string()))
+}
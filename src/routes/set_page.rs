@@ -1,49 +1,175 @@
-use axum::{extract::Path, response::IntoResponse, Extension, Json};
+use std::path::PathBuf;
+
+use axum::{
+    extract::{rejection::JsonRejection, Path},
+    Extension, Json,
+};
+use http::HeaderMap;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, error};
 
-use crate::state::WrappedPdfCollection;
+use crate::{
+    devices::{register_device, WrappedDeviceRegistry},
+    documents::DocumentCache,
+    journal::{JournalEvent, WrappedJournal},
+    links::destination,
+    state::{PagePosition, Progress, WrappedPdfCollection},
+};
 
-use super::stats::WrappedReadingStatistics;
+use super::{
+    error::ApiError,
+    events::{publish, EventSender, LiveEvent},
+    get_pdf::load_document,
+    stats::WrappedReadingStatistics,
+    status::book_status,
+};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct SetPageData {
     token: String,
     // Some redundancy never hurt
     pdf_name: String,
-    new_page: u16,
+    /// Can be left out when `destination` is given.
+    #[serde(default)]
+    new_page: Option<u16>,
+    /// A named destination of the PDF to go to instead of `new_page`.
+    #[serde(default)]
+    destination: Option<String>,
+    /// How far into the new page the reader is, see `PagePosition::offset`.
+    #[serde(default)]
+    offset: f32,
+    /// The revision of the book the client based the change on.
+    revision: u64,
 }
 
-pub async fn set_page(
-    Path(pdf): Path<String>,
-    Json(json): Json<SetPageData>,
-    Extension(pdfs): Extension<WrappedPdfCollection>,
-    Extension(state): Extension<WrappedReadingStatistics>,
-) -> impl IntoResponse {
+/// Moves `pdf` to `new_page` (and `offset` into it) on behalf of the device the request came from.
+///
+/// The change is only accepted if `revision` is the latest revision of the
+/// book, otherwise `ApiError::Conflict` with the current `BookStatus` is
+/// returned so the client can decide what to do. Without a `revision` the
+/// change is applied to whatever revision the book is at.
+#[allow(clippy::too_many_arguments)]
+pub async fn move_book(
+    pdf: &str,
+    new_page: u16,
+    offset: f32,
+    revision: Option<u64>,
+    headers: &HeaderMap,
+    pdfs: &WrappedPdfCollection,
+    stats: &WrappedReadingStatistics,
+    devices: &WrappedDeviceRegistry,
+    journal: &WrappedJournal,
+    events: &EventSender,
+) -> Result<Progress, ApiError> {
     let mut g = pdfs.lock().await;
-    let old_page = match g.get_book_by_name_mut(&json.pdf_name) {
-        Some(b) => {
-            b.access();
-            b.current_page()
-        }
-        None => {
-            error!("Request for page on non-existent content: {pdf}");
-            return Err("Request for page on non-existent content: {pdf}");
-        }
-    };
-    debug!("Setting page to {} for {}", json.new_page, json.pdf_name);
-
-    if g.set_page_by_name(&pdf, json.new_page).is_none() {
+    let device = register_device(headers, devices, journal).await;
+    let book = g.get_book_by_name_mut(&pdf).ok_or_else(|| {
         error!("Request for page on non-existent content: {pdf}");
-        return Err("Request for page on non-existent content: {pdf}");
+        ApiError::not_found(pdf)
+    })?;
+
+    if let Some(revision) = revision.filter(|&r| r != book.revision()) {
+        debug!(
+            "Rejecting outdated page change for {pdf} (revision {revision}, server is at {})",
+            book.revision()
+        );
+        return Err(ApiError::Conflict(book_status(
+            book,
+            &*devices.lock().await,
+        )));
+    }
+
+    // Books migrated from old state files have no page count until `sync_state` sees them
+    let unknown_length = book.total_pages() == 0;
+    if new_page == 0 || (!unknown_length && new_page > book.total_pages()) {
+        return Err(ApiError::BadRequest(format!(
+            "Page {new_page} is out of range for {pdf}, it has {} pages",
+            book.total_pages()
+        )));
+    }
+
+    if !(0.0..=1.0).contains(&offset) {
+        return Err(ApiError::BadRequest(format!(
+            "Offset {offset} is out of range, it has to be between 0 and 1"
+        )));
     }
+
+    debug!("Setting page to {new_page} for {pdf}");
+    let old_page = book.current_page();
+    book.access();
+    book.set_page(PagePosition::now(new_page, device.clone()).with_offset(offset));
+
+    let name = book.name().to_string();
+    let progress = book.progress();
+    let status = book_status(book, &*devices.lock().await);
+
+    let mut j = journal.lock().await;
+    j.record(JournalEvent::Access { book: name.clone() }).await;
+    j.record(JournalEvent::SetPage {
+        book: name.clone(),
+        page: new_page,
+        offset,
+        device,
+    })
+    .await;
+    drop(j);
     drop(g);
 
-    if old_page < json.new_page {
-        let mut g = state.lock().await;
+    publish(events, LiveEvent::Progress { book: name, status });
+
+    if old_page < new_page {
+        let mut g = stats.lock().await;
         g.increment();
         g.update();
+        journal.lock().await.record(JournalEvent::Read).await;
+        publish(events, LiveEvent::stats(&g));
     }
 
-    Ok(())
+    Ok(progress)
+}
+
+/// Sets the page of a book, given as a number or a named destination of the PDF.
+#[allow(clippy::too_many_arguments)]
+pub async fn set_page(
+    Path(pdf): Path<String>,
+    headers: HeaderMap,
+    json: Result<Json<SetPageData>, JsonRejection>,
+    Extension(pdfs): Extension<WrappedPdfCollection>,
+    Extension(state): Extension<WrappedReadingStatistics>,
+    Extension(devices): Extension<WrappedDeviceRegistry>,
+    Extension(journal): Extension<WrappedJournal>,
+    Extension(events): Extension<EventSender>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+    Extension(documents): Extension<DocumentCache>,
+) -> Result<Json<Progress>, ApiError> {
+    let Json(json) = json?;
+    let new_page = match (&json.destination, json.new_page) {
+        (Some(name), _) => {
+            let doc = load_document(&pdfs, &content_dirs, &documents, &pdf).await?;
+            destination(&doc, name)
+                .ok_or_else(|| ApiError::not_found(format!("destination {name} in {pdf}")))?
+                .page
+        }
+        (None, Some(page)) => page,
+        (None, None) => {
+            return Err(ApiError::BadRequest(String::from(
+                "Either new_page or destination has to be given",
+            )))
+        }
+    };
+
+    move_book(
+        &pdf,
+        new_page,
+        json.offset,
+        Some(json.revision),
+        &headers,
+        &pdfs,
+        &state,
+        &devices,
+        &journal,
+        &events,
+    )
+    .await
+    .map(Json)
 }
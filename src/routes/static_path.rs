@@ -1,32 +1,32 @@
 use axum::{
-    body::{self, Empty, Full},
+    body::{self, Full},
     extract::Path,
-    response::{IntoResponse, Response},
+    response::Response,
 };
 use http::{header, HeaderValue, StatusCode};
 use include_dir::{include_dir, Dir};
 use tracing::debug;
 
+use super::error::ApiError;
+
 /// Helper method for getting static files such as CSS.
-pub async fn static_path(Path(path): Path<String>) -> impl IntoResponse {
+pub async fn static_path(Path(path): Path<String>) -> Result<Response, ApiError> {
     static STATIC_DIR: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/static");
     debug!("giving static path: {path}");
 
     let path = path.trim_start_matches('/');
     let mime_type = mime_guess::from_path(path).first_or_text_plain();
 
-    match STATIC_DIR.get_file(path) {
-        None => Response::builder()
-            .status(StatusCode::NOT_FOUND)
-            .body(body::boxed(Empty::new()))
-            .unwrap(),
-        Some(file) => Response::builder()
-            .status(StatusCode::OK)
-            .header(
-                header::CONTENT_TYPE,
-                HeaderValue::from_str(mime_type.as_ref()).unwrap(),
-            )
-            .body(body::boxed(Full::from(file.contents())))
-            .unwrap(),
-    }
+    let file = STATIC_DIR
+        .get_file(path)
+        .ok_or_else(|| ApiError::not_found(path))?;
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(
+            header::CONTENT_TYPE,
+            HeaderValue::from_str(mime_type.as_ref()).unwrap(),
+        )
+        .body(body::boxed(Full::from(file.contents())))
+        .unwrap())
 }
@@ -50,7 +50,7 @@ impl ReadingEvent {
 }
 
 pub type WrappedReadingStatistics = Arc<Mutex<ReadingStatistics>>;
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct ReadingStatistics {
     events: Vec<ReadingEvent>,
 }
@@ -66,7 +66,7 @@ impl ReadingStatistics {
         Arc::new(Mutex::new(Self::new()))
     }
 
-    pub fn as_wrapped(self) -> Arc<Mutex<Self>> {
+    pub fn into_wrapped(self) -> Arc<Mutex<Self>> {
         Arc::new(Mutex::new(self))
     }
 
@@ -74,6 +74,14 @@ impl ReadingStatistics {
         self.events.push(ReadingEvent::default())
     }
 
+    /// Records a page read at `time`, call `update` afterwards to categorize it.
+    pub fn increment_at(&mut self, time: DateTime<Local>) {
+        self.events.push(ReadingEvent {
+            time,
+            ..Default::default()
+        })
+    }
+
     pub fn update(&mut self) {
         let now = Local::now();
 
@@ -1,19 +1,98 @@
-use axum::{extract::Path, response::IntoResponse, Extension};
-
+use axum::{
+    extract::Path,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{DateTime, Local};
+use http::{header, HeaderMap};
+use serde::{Deserialize, Serialize};
 use tracing::error;
+use utoipa::ToSchema;
+
+use crate::{
+    devices::{DeviceRegistry, WrappedDeviceRegistry},
+    state::{Pdf, Progress, WrappedPdfCollection},
+};
+
+use super::error::ApiError;
+
+/// Where a device left off in a book.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct DevicePosition {
+    pub device: String,
+    pub name: String,
+    pub page: u16,
+    pub time: DateTime<Local>,
+}
+
+/// The progress of a book together with where every device is at.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct BookStatus {
+    #[serde(flatten)]
+    pub progress: Progress,
+    /// Human readable description of the last page change,
+    /// like "last read on phone, 2h ago, page 120".
+    pub last_read: Option<String>,
+    /// Latest first.
+    pub devices: Vec<DevicePosition>,
+}
 
-use crate::state::WrappedPdfCollection;
+pub fn book_status(pdf: &Pdf, devices: &DeviceRegistry) -> BookStatus {
+    let mut positions: Vec<DevicePosition> = pdf
+        .device_positions()
+        .iter()
+        .map(|(id, p)| DevicePosition {
+            device: id.clone(),
+            name: devices.name(id).to_string(),
+            page: p.page,
+            time: p.time,
+        })
+        .collect();
+    positions.sort_by_key(|p| std::cmp::Reverse(p.time));
 
+    BookStatus {
+        progress: pdf.progress(),
+        last_read: devices.describe_last_read(pdf),
+        devices: positions,
+    }
+}
+
+/// The progress of a book along with where every device left off in it, and
+/// a description of the last page change like "last read on phone, 2h ago, page 120".
+///
+/// Clients which only accept `text/plain` get the current page as plain text
+/// instead, or `-1` if there is no such book, like before devices were tracked.
 pub async fn status(
     Path(pdf): Path<String>,
+    headers: HeaderMap,
     Extension(content_state): Extension<WrappedPdfCollection>,
-) -> impl IntoResponse {
+    Extension(devices): Extension<WrappedDeviceRegistry>,
+) -> Result<Response, ApiError> {
     let g = content_state.lock().await;
+    let book = g.get_book_by_name(&pdf);
 
-    if let Some(n) = g.get_book_by_name(&pdf) {
-        n.current_page().to_string()
-    } else {
-        error!("Request for status for non-existent content: {pdf}");
-        String::from("-1")
+    if wants_plain_text(&headers) {
+        return Ok(match book {
+            Some(book) => book.current_page().to_string(),
+            None => {
+                error!("Request for status for non-existent content: {pdf}");
+                String::from("-1")
+            }
+        }
+        .into_response());
     }
+
+    let book = book.ok_or_else(|| ApiError::not_found(pdf))?;
+    Ok(Json(book_status(&book, &*devices.lock().await)).into_response())
+}
+
+/// Whether the `Accept` header asks for plain text and not for JSON.
+fn wants_plain_text(headers: &HeaderMap) -> bool {
+    let accept = headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(",");
+    accept.contains("text/plain") && !accept.contains("application/json")
 }
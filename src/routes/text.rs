@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+
+use axum::{
+    extract::{rejection::PathRejection, Path},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    documents::DocumentCache,
+    state::WrappedPdfCollection,
+    text::{page_lines, paragraphs},
+};
+
+use super::{error::ApiError, get_pdf::load_document};
+
+/// The text of a page, in reading order.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct PageText {
+    pub page: u16,
+    /// The paragraphs joined by blank lines.
+    pub text: String,
+    pub paragraphs: Vec<String>,
+}
+
+/// Extracts the text of a page of a PDF.
+///
+/// Only text drawn with fonts the server can decode is found, scanned pages have none.
+#[utoipa::path(
+    get,
+    path = "/api/v1/books/{book}/pages/{number}/text",
+    params(
+        ("book" = String, Path, description = "Name of the book"),
+        ("number" = u16, Path, description = "Number of the page, starting at 1"),
+    ),
+    responses(
+        (status = 200, description = "The text of the page", body = PageText),
+        (status = 400, description = "The book is not a PDF", body = ErrorBody),
+        (status = 404, description = "No such book or page", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn page_text(
+    path: Result<Path<(String, u16)>, PathRejection>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+    Extension(documents): Extension<DocumentCache>,
+) -> Result<Json<PageText>, ApiError> {
+    let Path((book, number)) = path?;
+    let doc = load_document(&book_state, &content_dirs, &documents, &book).await?;
+    let page = *doc
+        .get_pages()
+        .get(&(number as u32))
+        .ok_or_else(|| ApiError::not_found(format!("page {number} of {book}")))?;
+
+    let paragraphs =
+        tokio::task::spawn_blocking(move || page_lines(&doc, page).map(|lines| paragraphs(&lines)))
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?
+            .map_err(ApiError::Internal)?;
+
+    Ok(Json(PageText {
+        page: number,
+        text: paragraphs.join("\n\n"),
+        paragraphs,
+    }))
+}
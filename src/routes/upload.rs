@@ -0,0 +1,261 @@
+use std::path::{Path as FsPath, PathBuf};
+
+use axum::{
+    extract::{multipart::Field, Multipart},
+    response::{IntoResponse, Redirect, Response},
+    Extension, Json,
+};
+use http::StatusCode;
+use tokio::{fs, io::AsyncWriteExt};
+use tracing::{info, warn};
+
+use crate::{
+    devices::{new_device_id, WrappedDeviceRegistry},
+    journal::{JournalEvent, WrappedJournal},
+    routes::{
+        error::ApiError,
+        events::{publish, EventSender, LiveEvent},
+        manage::move_file,
+        v1::{books::Book, collections::collection_name},
+    },
+    state::{book_name, DocumentKind, Pdf, WrappedPdfCollection},
+};
+
+/// Largest book accepted by `upload_book`, in bytes.
+pub const MAX_UPLOAD_SIZE: usize = 256 * 1024 * 1024;
+
+/// Uploads a new PDF, EPUB or CBZ into one of the collections.
+///
+/// Send a `multipart/form-data` body with the book in the `file` field. The
+/// optional `collection` field names the collection to store it in, the first
+/// one is used otherwise. Browsers can set `redirect` to a local path to be
+/// sent there instead of getting the JSON body.
+#[utoipa::path(
+    post,
+    path = "/api/v1/books",
+    request_body(content = String, description = "Multipart form with `file`, and optionally `collection` and `redirect`", content_type = "multipart/form-data"),
+    responses(
+        (status = 201, description = "The book was added", body = Book),
+        (status = 303, description = "The book was added, going to `redirect`"),
+        (status = 400, description = "The upload is not a supported book or the form is faulty", body = ErrorBody),
+        (status = 404, description = "No such collection", body = ErrorBody),
+        (status = 409, description = "A book with that name exists", body = ErrorBody),
+        (status = 413, description = "The upload is too large", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn upload_book(
+    multipart: Multipart,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(devices): Extension<WrappedDeviceRegistry>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+    Extension(journal): Extension<WrappedJournal>,
+    Extension(events): Extension<EventSender>,
+) -> Result<Response, ApiError> {
+    let staging = content_dirs
+        .first()
+        .ok_or_else(|| ApiError::Internal(String::from("No content directory to upload to")))?;
+    let upload = Upload::receive(multipart, staging).await?;
+
+    let result = upload.store(&book_state, &content_dirs, &journal).await;
+    if result.is_err() {
+        let _ = fs::remove_file(&upload.temp).await;
+    }
+    let pdf = result?;
+
+    info!("Uploaded {:?}", pdf.path());
+    let book = Book::new(&pdf, &*devices.lock().await, &content_dirs);
+    publish(&events, LiveEvent::NewBook { book: pdf });
+
+    Ok(match upload.redirect {
+        Some(to) => Redirect::to(&to).into_response(),
+        None => (StatusCode::CREATED, Json(book)).into_response(),
+    })
+}
+
+/// An uploaded book, staged in a temporary file until it has been checked.
+struct Upload {
+    temp: PathBuf,
+    file_name: String,
+    collection: Option<String>,
+    redirect: Option<String>,
+}
+
+impl Upload {
+    /// Reads the form, writing the file to a hidden temporary file in `staging`.
+    async fn receive(mut multipart: Multipart, staging: &FsPath) -> Result<Self, ApiError> {
+        let mut upload = Upload {
+            temp: staging.join(temp_name()),
+            file_name: String::new(),
+            collection: None,
+            redirect: None,
+        };
+
+        let result = upload.read_fields(&mut multipart).await;
+        if result.is_err() {
+            let _ = fs::remove_file(&upload.temp).await;
+        }
+        result.map(|_| upload)
+    }
+
+    async fn read_fields(&mut self, multipart: &mut Multipart) -> Result<(), ApiError> {
+        let mut received = false;
+
+        while let Some(field) = multipart.next_field().await.map_err(bad_form)? {
+            match field.name() {
+                Some("file") if !received => {
+                    self.file_name = sanitize_file_name(field.file_name().unwrap_or_default())?;
+                    write_limited(field, &self.temp).await?;
+                    received = true;
+                }
+                Some("collection") => {
+                    let name = field.text().await.map_err(bad_form)?;
+                    self.collection = Some(name).filter(|n| !n.is_empty());
+                }
+                Some("redirect") => {
+                    let to = field.text().await.map_err(bad_form)?;
+                    // Only ever send browsers back to this server
+                    if to.starts_with('/') && !to.starts_with("//") {
+                        self.redirect = Some(to);
+                    }
+                }
+                name => {
+                    return Err(ApiError::BadRequest(format!(
+                        "Unexpected form field {name:?}"
+                    )))
+                }
+            }
+        }
+
+        if !received {
+            return Err(ApiError::BadRequest(String::from(
+                "The form has no `file` field",
+            )));
+        }
+        Ok(())
+    }
+
+    /// Checks the upload is a readable book, moves it into its collection and registers it.
+    ///
+    /// The book is journaled, the next snapshot would not know it otherwise:
+    /// `sync_state` finds it already registered and has nothing to write.
+    async fn store(
+        &self,
+        book_state: &WrappedPdfCollection,
+        content_dirs: &[PathBuf],
+        journal: &WrappedJournal,
+    ) -> Result<Pdf, ApiError> {
+        let dir = match &self.collection {
+            Some(name) => content_dirs
+                .iter()
+                .find(|dir| &collection_name(dir) == name)
+                .ok_or_else(|| ApiError::not_found(format!("collection {name}")))?,
+            None => &content_dirs[0],
+        };
+
+        // `sanitize_file_name` only lets through known extensions
+        let kind =
+            DocumentKind::from_path(FsPath::new(&self.file_name)).unwrap_or(DocumentKind::Pdf);
+        let mut magic = vec![0; kind.magic().len()];
+        let mut file = fs::File::open(&self.temp).await?;
+        if tokio::io::AsyncReadExt::read_exact(&mut file, &mut magic)
+            .await
+            .is_err()
+            || magic != kind.magic()
+        {
+            return Err(ApiError::BadRequest(format!(
+                "{} is not a valid .{} file",
+                self.file_name,
+                kind.extension()
+            )));
+        }
+        drop(file);
+
+        let temp = self.temp.clone();
+        let total_pages = tokio::task::spawn_blocking(move || Pdf::count_pages(&temp, kind))
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?
+            .map_err(|e| {
+                warn!("Rejecting upload of {}: {e}", self.file_name);
+                ApiError::BadRequest(format!(
+                    "{} is not a readable .{} file",
+                    self.file_name,
+                    kind.extension()
+                ))
+            })?;
+
+        let path = dir.join(&self.file_name);
+        let mut g = book_state.lock().await;
+        let name = book_name(&self.file_name);
+        if g.get_book_by_name(&name).is_some() || fs::metadata(&path).await.is_ok() {
+            return Err(ApiError::AlreadyExists(format!(
+                "A book called {name} already exists"
+            )));
+        }
+
+        move_file(&self.temp, &path).await?;
+
+        let pdf = Pdf::with_total_pages(path.clone(), total_pages);
+        g.add_book(pdf.clone());
+        journal
+            .lock()
+            .await
+            .record(JournalEvent::Add { path, total_pages })
+            .await;
+        Ok(pdf)
+    }
+}
+
+/// Copies the contents of `field` to `path`, failing once `MAX_UPLOAD_SIZE` is exceeded.
+async fn write_limited(mut field: Field<'_>, path: &FsPath) -> Result<(), ApiError> {
+    let mut file = fs::File::create(path).await?;
+    let mut size = 0;
+
+    while let Some(chunk) = field.chunk().await.map_err(bad_form)? {
+        size += chunk.len();
+        if size > MAX_UPLOAD_SIZE {
+            return Err(ApiError::TooLarge(format!(
+                "Uploads are limited to {} MiB",
+                MAX_UPLOAD_SIZE / 1024 / 1024
+            )));
+        }
+        file.write_all(&chunk).await?;
+    }
+
+    file.flush().await?;
+    Ok(())
+}
+
+/// Makes sure the name a client sent is a plain file name with a supported extension, like `book.pdf`.
+///
+/// The extension is matched ignoring case and written in lower case, so
+/// `Book.PDF` is stored as `Book.pdf`. Books are named after their file name
+/// without the extension, further dots are not allowed so the name of an
+/// uploaded book never looks like a file name itself.
+pub fn sanitize_file_name(name: &str) -> Result<String, ApiError> {
+    // Some browsers send the full path of the file
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+    let kind = DocumentKind::from_path(FsPath::new(name));
+    let stem = name
+        .rsplit_once('.')
+        .map(|(stem, _)| stem)
+        .unwrap_or_default();
+    let valid = !stem.is_empty() && !stem.contains('.') && !stem.chars().any(char::is_control);
+
+    let Some(kind) = kind.filter(|_| valid) else {
+        return Err(ApiError::BadRequest(format!(
+            "{name:?} is not a valid file name, expected something like \"book.pdf\""
+        )));
+    };
+
+    Ok(format!("{stem}.{}", kind.extension()))
+}
+
+/// A hidden file name no content directory scan picks up.
+fn temp_name() -> String {
+    format!(".upload-{}.part", new_device_id())
+}
+
+fn bad_form(e: axum::extract::multipart::MultipartError) -> ApiError {
+    ApiError::BadRequest(e.to_string())
+}
@@ -0,0 +1,239 @@
+use std::path::PathBuf;
+
+use axum::{
+    extract::{rejection::JsonRejection, Path},
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    devices::{DeviceRegistry, WrappedDeviceRegistry},
+    journal::WrappedJournal,
+    routes::{
+        attachments::{get_attachment, list_attachments},
+        comic::page_image,
+        cover::get_cover,
+        epub::{get_chapter, list_chapters},
+        error::ApiError,
+        events::EventSender,
+        forms::{get_filled_pdf, get_form_values, list_fields, put_form_values},
+        history::{get_history, go_back},
+        links::{list_destinations, list_links},
+        manage::{delete_book, update_book},
+        outline::list_outline,
+        pages::list_pages,
+        preferences::{get_preferences, put_preferences},
+        search::search_book,
+        set_page::move_book,
+        stats::WrappedReadingStatistics,
+        status::{book_status, BookStatus},
+        text::page_text,
+        upload::upload_book,
+    },
+    state::{AccessTime, DocumentKind, Pdf, Progress, WrappedPdfCollection},
+};
+
+use super::collections::{collection_name, collection_of};
+
+/// A book in the library.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct Book {
+    pub name: String,
+    pub kind: DocumentKind,
+    /// The collection the book is stored in.
+    pub collection: Option<String>,
+    /// Pages for PDFs and comics, chapters for EPUBs.
+    pub total_pages: u16,
+    pub percentage_read: u32,
+    /// Local time of the last access, `None` if the book has never been opened.
+    pub last_access: Option<String>,
+    pub progress: Progress,
+    /// Human readable description of the last page change.
+    pub last_read: Option<String>,
+    /// Where the book can be downloaded from.
+    pub download: String,
+}
+
+impl Book {
+    pub fn new(pdf: &Pdf, devices: &DeviceRegistry, content_dirs: &[PathBuf]) -> Self {
+        Book {
+            name: pdf.name().to_string(),
+            kind: pdf.kind(),
+            collection: collection_of(pdf.path(), content_dirs).map(|d| collection_name(d)),
+            total_pages: pdf.total_pages(),
+            percentage_read: pdf.percentage_read(),
+            last_access: match pdf.last_access() {
+                AccessTime::Never => None,
+                AccessTime::Once(t) => Some(t.clone()),
+            },
+            progress: pdf.progress(),
+            last_read: devices.describe_last_read(pdf),
+            download: format!("/get_pdf/{}.{}", pdf.name(), pdf.kind().extension()),
+        }
+    }
+}
+
+/// A page change, based on the revision of the book the client last saw.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct ProgressUpdate {
+    pub page: u16,
+    /// How far into the page the reader is, from 0 to 1.
+    #[serde(default)]
+    pub offset: f32,
+    pub revision: u64,
+}
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/", get(list_books).post(upload_book))
+        .route(
+            "/:book",
+            get(get_book).patch(update_book).delete(delete_book),
+        )
+        .route("/:book/progress", get(get_progress).put(put_progress))
+        .route(
+            "/:book/preferences",
+            get(get_preferences).put(put_preferences),
+        )
+        .route("/:book/chapters", get(list_chapters))
+        .route("/:book/chapters/:number", get(get_chapter))
+        .route("/:book/pages", get(list_pages))
+        .route("/:book/pages/:number/image", get(page_image))
+        .route("/:book/pages/:number/text", get(page_text))
+        .route("/:book/pages/:number/links", get(list_links))
+        .route("/:book/destinations", get(list_destinations))
+        .route("/:book/outline", get(list_outline))
+        .route("/:book/form", get(list_fields))
+        .route(
+            "/:book/form/values",
+            get(get_form_values).put(put_form_values),
+        )
+        .route("/:book/form/filled", get(get_filled_pdf))
+        .route("/:book/cover", get(get_cover))
+        .route("/:book/attachments", get(list_attachments))
+        .route("/:book/attachments/:number", get(get_attachment))
+        .route("/:book/search", get(search_book))
+        .route("/:book/history", get(get_history))
+        .route("/:book/history/back", post(go_back))
+}
+
+/// Lists every book, sorted by name.
+#[utoipa::path(
+    get,
+    path = "/api/v1/books",
+    responses((status = 200, description = "Every book in the library", body = [Book])),
+    tag = "books"
+)]
+pub async fn list_books(
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(devices): Extension<WrappedDeviceRegistry>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+) -> Json<Vec<Book>> {
+    let g = book_state.lock().await;
+    let devices = devices.lock().await;
+
+    let mut books: Vec<Book> = g
+        .pdfs
+        .values()
+        .map(|pdf| Book::new(pdf, &devices, &content_dirs))
+        .collect();
+    books.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Json(books)
+}
+
+/// Gets a single book.
+#[utoipa::path(
+    get,
+    path = "/api/v1/books/{book}",
+    params(("book" = String, Path, description = "Name of the book")),
+    responses(
+        (status = 200, description = "The book", body = Book),
+        (status = 404, description = "No such book", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn get_book(
+    Path(book): Path<String>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(devices): Extension<WrappedDeviceRegistry>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+) -> Result<Json<Book>, ApiError> {
+    let g = book_state.lock().await;
+    let pdf = g
+        .get_book_by_name(&book)
+        .ok_or_else(|| ApiError::not_found(book))?;
+
+    Ok(Json(Book::new(&pdf, &*devices.lock().await, &content_dirs)))
+}
+
+/// Gets the progress of a book and where every device is at in it.
+#[utoipa::path(
+    get,
+    path = "/api/v1/books/{book}/progress",
+    params(("book" = String, Path, description = "Name of the book")),
+    responses(
+        (status = 200, description = "The progress of the book", body = BookStatus),
+        (status = 404, description = "No such book", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+pub async fn get_progress(
+    Path(book): Path<String>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(devices): Extension<WrappedDeviceRegistry>,
+) -> Result<Json<BookStatus>, ApiError> {
+    let g = book_state.lock().await;
+    let pdf = g
+        .get_book_by_name(&book)
+        .ok_or_else(|| ApiError::not_found(book))?;
+
+    Ok(Json(book_status(&pdf, &*devices.lock().await)))
+}
+
+/// Moves a book to another page.
+///
+/// Identify the device making the change with the `x-device-id` header.
+#[utoipa::path(
+    put,
+    path = "/api/v1/books/{book}/progress",
+    params(("book" = String, Path, description = "Name of the book")),
+    request_body = ProgressUpdate,
+    responses(
+        (status = 200, description = "The book was moved", body = Progress),
+        (status = 400, description = "The page is out of range", body = ErrorBody),
+        (status = 404, description = "No such book", body = ErrorBody),
+        (status = 409, description = "The revision is outdated, `current` holds the state of the book", body = ErrorBody),
+    ),
+    tag = "books"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn put_progress(
+    Path(book): Path<String>,
+    headers: HeaderMap,
+    update: Result<Json<ProgressUpdate>, JsonRejection>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(stats): Extension<WrappedReadingStatistics>,
+    Extension(devices): Extension<WrappedDeviceRegistry>,
+    Extension(journal): Extension<WrappedJournal>,
+    Extension(events): Extension<EventSender>,
+) -> Result<Json<Progress>, ApiError> {
+    let Json(update) = update?;
+    move_book(
+        &book,
+        update.page,
+        update.offset,
+        Some(update.revision),
+        &headers,
+        &book_state,
+        &stats,
+        &devices,
+        &journal,
+        &events,
+    )
+    .await
+    .map(Json)
+}
@@ -0,0 +1,92 @@
+use std::path::{Path as FsPath, PathBuf};
+
+use axum::{extract::Path, Extension, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{routes::error::ApiError, state::WrappedPdfCollection};
+
+/// A content directory and the books in it.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct Collection {
+    pub name: String,
+    pub path: String,
+    /// Names of the books in the collection, sorted.
+    pub books: Vec<String>,
+}
+
+/// The name a content directory goes by in the API.
+pub fn collection_name(dir: &FsPath) -> String {
+    dir.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| dir.display().to_string())
+}
+
+/// The content directory `book` is stored in.
+pub fn collection_of<'a>(book: &FsPath, content_dirs: &'a [PathBuf]) -> Option<&'a PathBuf> {
+    content_dirs.iter().find(|dir| book.starts_with(dir))
+}
+
+async fn collections(
+    book_state: &WrappedPdfCollection,
+    content_dirs: &[PathBuf],
+) -> Vec<Collection> {
+    let g = book_state.lock().await;
+
+    content_dirs
+        .iter()
+        .map(|dir| {
+            let mut books: Vec<String> = g
+                .pdfs
+                .values()
+                .filter(|pdf| collection_of(pdf.path(), content_dirs) == Some(dir))
+                .map(|pdf| pdf.name().to_string())
+                .collect();
+            books.sort();
+
+            Collection {
+                name: collection_name(dir),
+                path: dir.display().to_string(),
+                books,
+            }
+        })
+        .collect()
+}
+
+/// Lists every collection.
+#[utoipa::path(
+    get,
+    path = "/api/v1/collections",
+    responses((status = 200, description = "Every collection", body = [Collection])),
+    tag = "collections"
+)]
+pub async fn list_collections(
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+) -> Json<Vec<Collection>> {
+    Json(collections(&book_state, &content_dirs).await)
+}
+
+/// Gets a single collection by name.
+#[utoipa::path(
+    get,
+    path = "/api/v1/collections/{collection}",
+    params(("collection" = String, Path, description = "Name of the collection")),
+    responses(
+        (status = 200, description = "The collection", body = Collection),
+        (status = 404, description = "No such collection", body = ErrorBody),
+    ),
+    tag = "collections"
+)]
+pub async fn get_collection(
+    Path(name): Path<String>,
+    Extension(book_state): Extension<WrappedPdfCollection>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+) -> Result<Json<Collection>, ApiError> {
+    collections(&book_state, &content_dirs)
+        .await
+        .into_iter()
+        .find(|c| c.name == name)
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(name))
+}
@@ -0,0 +1,130 @@
+// Version 1 of the JSON API, everything in here is served below `/api/v1`.
+//
+// Every resource is plain JSON and every error is a `ErrorBody`, the whole API
+// is described by the OpenAPI document at `/api/v1/openapi.json`.
+
+use axum::{
+    http::Uri,
+    response::Redirect,
+    routing::{delete, get, post},
+    Json, Router,
+};
+use utoipa::OpenApi;
+
+use crate::{
+    routes::{
+        error::ErrorBody,
+        events::events,
+        manage,
+        status::{BookStatus, DevicePosition},
+    },
+    state::{DocumentKind, PagePosition, Progress, ReaderPreferences, ZoomMode},
+};
+
+pub mod books;
+pub mod collections;
+pub mod stats;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "pdf-viewer", description = "Centralized PDF reading from your browser."),
+    paths(
+        books::list_books,
+        crate::routes::upload::upload_book,
+        books::get_book,
+        manage::update_book,
+        manage::delete_book,
+        crate::routes::epub::list_chapters,
+        crate::routes::epub::get_chapter,
+        crate::routes::comic::page_image,
+        crate::routes::cover::get_cover,
+        crate::routes::attachments::list_attachments,
+        crate::routes::attachments::get_attachment,
+        crate::routes::pages::list_pages,
+        crate::routes::text::page_text,
+        crate::routes::links::list_links,
+        crate::routes::links::list_destinations,
+        crate::routes::outline::list_outline,
+        crate::routes::forms::list_fields,
+        crate::routes::forms::get_form_values,
+        crate::routes::forms::put_form_values,
+        crate::routes::forms::get_filled_pdf,
+        crate::routes::search::search_book,
+        books::get_progress,
+        books::put_progress,
+        crate::routes::preferences::get_preferences,
+        crate::routes::preferences::put_preferences,
+        crate::routes::history::get_history,
+        crate::routes::history::go_back,
+        collections::list_collections,
+        collections::get_collection,
+        manage::list_trash,
+        manage::restore_book,
+        manage::purge_book,
+        stats::get_stats,
+    ),
+    components(schemas(
+        books::Book,
+        books::ProgressUpdate,
+        crate::routes::epub::ChapterInfo,
+        crate::geometry::PageGeometry,
+        crate::attachments::Attachment,
+        crate::links::Link,
+        crate::links::NamedDestination,
+        crate::outline::OutlineEntry,
+        crate::forms::FormField,
+        crate::forms::FieldKind,
+        crate::forms::FieldValue,
+        crate::routes::text::PageText,
+        crate::routes::search::SearchResults,
+        crate::routes::search::PageHits,
+        crate::text::Hit,
+        crate::text::Rect,
+        manage::BookChange,
+        manage::TrashedBook,
+        collections::Collection,
+        stats::Stats,
+        Progress,
+        PagePosition,
+        ReaderPreferences,
+        ZoomMode,
+        DocumentKind,
+        BookStatus,
+        DevicePosition,
+        ErrorBody,
+    )),
+    tags(
+        (name = "books", description = "The books and the progress in them"),
+        (name = "collections", description = "The content directories books are stored in"),
+        (name = "trash", description = "Deleted books, which can be restored until they are purged"),
+        (name = "stats", description = "Reading statistics"),
+    )
+)]
+pub struct ApiDoc;
+
+pub fn router() -> Router {
+    Router::new()
+        .nest("/books", books::router())
+        .route("/collections", get(collections::list_collections))
+        .route("/collections/:collection", get(collections::get_collection))
+        .route("/trash", get(manage::list_trash))
+        .route("/trash/:book", delete(manage::purge_book))
+        .route("/trash/:book/restore", post(manage::restore_book))
+        .route("/stats", get(stats::get_stats))
+        .route("/events", get(events))
+        .route("/openapi.json", get(openapi))
+}
+
+/// The OpenAPI description of this API.
+pub async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// Sends reads of the unversioned `/api/books/...` to the same place below `/api/v1/books`.
+///
+/// Only `GET` is redirected, anything changing books has to use the versioned API.
+pub async fn unversioned_books(uri: Uri) -> Redirect {
+    let rest = uri.path_and_query().map(|p| p.as_str()).unwrap_or_default();
+    let rest = rest.strip_prefix("/api/books").unwrap_or(rest);
+    Redirect::permanent(&format!("/api/v1/books{rest}"))
+}
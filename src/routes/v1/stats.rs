@@ -0,0 +1,32 @@
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::routes::stats::WrappedReadingStatistics;
+
+/// Pages read within the last day, week and month.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, ToSchema)]
+pub struct Stats {
+    pub today: usize,
+    pub week: usize,
+    pub month: usize,
+}
+
+/// Gets the reading statistics.
+#[utoipa::path(
+    get,
+    path = "/api/v1/stats",
+    responses((status = 200, description = "The reading statistics", body = Stats)),
+    tag = "stats"
+)]
+pub async fn get_stats(
+    Extension(reading_stats): Extension<WrappedReadingStatistics>,
+) -> Json<Stats> {
+    let g = reading_stats.lock().await;
+
+    Json(Stats {
+        today: g.last_day(),
+        week: g.last_week(),
+        month: g.last_month(),
+    })
+}
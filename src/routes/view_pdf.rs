@@ -1,40 +1,152 @@
+use std::path::PathBuf;
+
 use askama::Template;
-use axum::{extract::Path, response::IntoResponse, Extension};
-use tracing::{debug, error, info};
+use axum::{
+    extract::Path,
+    response::{Html, IntoResponse},
+    Extension,
+};
+use http::{header, HeaderMap};
+use tracing::{debug, info};
+
+use crate::{
+    devices::{device_cookie_header, new_device_id, DeviceIdentity},
+    journal::{JournalEvent, WrappedJournal},
+    state::{book_name, DocumentKind, Progress, WrappedPdfCollection},
+};
 
-use crate::state::WrappedPdfCollection;
+use super::{
+    epub::{file_url, open_epub, ChapterInfo},
+    error::ApiError,
+};
 
 #[derive(Template, Debug)]
 #[template(path = "view_pdf.html")]
 struct ViewPDFTemplate {
     pdf_name: String,
     cur_page_number: u16,
+    cur_revision: u64,
+    /// `ReaderPreferences` of the book as JSON.
+    preferences: String,
+}
+
+#[derive(Template, Debug)]
+#[template(path = "view_epub.html")]
+struct ViewEpubTemplate {
+    book_name: String,
+    title: String,
+    cur_chapter: u16,
+    cur_offset: f32,
+    cur_revision: u64,
+    chapters: Vec<ChapterInfo>,
+}
+
+#[derive(Template, Debug)]
+#[template(path = "view_comic.html")]
+struct ViewComicTemplate {
+    book_name: String,
+    cur_page_number: u16,
+    total_pages: u16,
+    cur_revision: u64,
 }
 
 /// The method for getting the page where the user views *one* PDF
+///
+/// Browsers which have not been given a device id yet get one here.
 pub async fn view_pdf(
     Path(pdf): Path<String>,
+    headers: HeaderMap,
     Extension(book_state): Extension<WrappedPdfCollection>,
-) -> impl IntoResponse {
+    Extension(journal): Extension<WrappedJournal>,
+    Extension(content_dirs): Extension<Vec<PathBuf>>,
+) -> Result<impl IntoResponse, ApiError> {
     let mut guard = book_state.lock().await;
-    let cur_page_number = match guard.get_book_by_name_mut(&pdf) {
-        Some(pdf) => {
-            pdf.access();
-            pdf.current_page()
+    let book = guard
+        .get_book_by_name_mut(&pdf)
+        .ok_or_else(|| ApiError::not_found(&pdf))?;
+    book.access();
+    let progress = book.progress();
+    let kind = book.kind();
+    let total_pages = book.total_pages();
+    let preferences = book.preferences(None).clone();
+    journal
+        .lock()
+        .await
+        .record(JournalEvent::Access {
+            book: book.name().to_string(),
+        })
+        .await;
+    drop(guard);
+
+    info!("Someone is trying to view {pdf}");
+    let page = match kind {
+        DocumentKind::Pdf => {
+            let template = ViewPDFTemplate {
+                pdf_name: pdf,
+                cur_page_number: progress.page,
+                cur_revision: progress.revision,
+                preferences: serde_json::to_string(&preferences)
+                    .map_err(|e| ApiError::Internal(e.to_string()))?,
+            };
+            debug!("Returning template {template:?}");
+            render(template)?
         }
-        None => {
-            error!("Request for non-existent content: {pdf}");
-            return Err(format!("Request for non-existent content: {pdf}"));
+        DocumentKind::Epub => view_epub(&pdf, &progress, &book_state, &content_dirs).await?,
+        DocumentKind::Cbz => {
+            let template = ViewComicTemplate {
+                book_name: book_name(&pdf).to_string(),
+                cur_page_number: progress.page,
+                total_pages,
+                cur_revision: progress.revision,
+            };
+            debug!("Returning template {template:?}");
+            render(template)?
         }
     };
-    drop(guard);
 
-    info!("Someone is trying to view {pdf}");
-    let template = ViewPDFTemplate {
-        pdf_name: pdf,
-        cur_page_number,
+    let mut response_headers = HeaderMap::new();
+    if DeviceIdentity::from_headers(&headers).is_none() {
+        let id = new_device_id();
+        debug!("Handing out device id {id}");
+        response_headers.insert(header::SET_COOKIE, device_cookie_header(&id));
+    }
+
+    Ok((response_headers, Html(page)))
+}
+
+/// The reader for EPUBs, which shows one chapter at a time.
+async fn view_epub(
+    pdf: &str,
+    progress: &Progress,
+    book_state: &WrappedPdfCollection,
+    content_dirs: &[PathBuf],
+) -> Result<String, ApiError> {
+    let epub = open_epub(book_state, content_dirs, pdf).await?;
+    let name = book_name(pdf);
+
+    let template = ViewEpubTemplate {
+        book_name: name.to_string(),
+        title: epub.title.clone().unwrap_or_else(|| name.to_string()),
+        cur_chapter: progress.page,
+        cur_offset: progress.offset,
+        cur_revision: progress.revision,
+        chapters: epub
+            .chapters
+            .iter()
+            .enumerate()
+            .map(|(i, chapter)| ChapterInfo {
+                number: i as u16 + 1,
+                title: chapter.title.clone(),
+                url: file_url(name, &chapter.file),
+            })
+            .collect(),
     };
     debug!("Returning template {template:?}");
+    render(template)
+}
 
-    Ok(askama_axum::IntoResponse::into_response(template))
+fn render(template: impl Template) -> Result<String, ApiError> {
+    template
+        .render()
+        .map_err(|e| ApiError::Internal(format!("Failed to render the viewer: {e}")))
 }
@@ -5,9 +5,12 @@ use std::{
     sync::Arc,
 };
 
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Local, NaiveDateTime};
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
+use utoipa::ToSchema;
+
+use crate::{comic::Comic, epub::Epub, forms::FieldValue};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum AccessTime {
@@ -17,7 +20,22 @@ pub enum AccessTime {
 
 impl AccessTime {
     pub fn now() -> Self {
-        AccessTime::Once(Local::now().format("%Y-%m-%d %H:%M:%S").to_string())
+        AccessTime::at(Local::now())
+    }
+
+    pub fn at(time: DateTime<Local>) -> Self {
+        AccessTime::Once(time.format("%Y-%m-%d %H:%M:%S").to_string())
+    }
+
+    /// The time of the access, `None` if there was none or it cannot be parsed.
+    pub fn time(&self) -> Option<DateTime<Local>> {
+        match self {
+            AccessTime::Never => None,
+            AccessTime::Once(t) => NaiveDateTime::parse_from_str(t, "%Y-%m-%d %H:%M:%S")
+                .ok()?
+                .and_local_timezone(Local)
+                .earliest(),
+        }
     }
 }
 
@@ -42,10 +60,78 @@ impl From<DateTime<Local>> for AccessTime {
 
 pub type WrappedPdfCollection = Arc<Mutex<PdfCollection>>;
 
+/// The kinds of documents the library can hold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentKind {
+    /// Page based, rendered by pdf.js in the browser.
+    Pdf,
+    /// Reflowable, every chapter of the spine counts as a page.
+    Epub,
+    /// A comic book archive, every image is a page.
+    Cbz,
+}
+
+impl DocumentKind {
+    pub const ALL: [DocumentKind; 3] = [DocumentKind::Pdf, DocumentKind::Epub, DocumentKind::Cbz];
+
+    /// The kind of the file at `path`, going by its extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        DocumentKind::ALL
+            .into_iter()
+            .find(|kind| kind.extension().eq_ignore_ascii_case(extension))
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DocumentKind::Pdf => "pdf",
+            DocumentKind::Epub => "epub",
+            DocumentKind::Cbz => "cbz",
+        }
+    }
+
+    pub fn mime(&self) -> &'static str {
+        match self {
+            DocumentKind::Pdf => "application/pdf",
+            DocumentKind::Epub => "application/epub+zip",
+            DocumentKind::Cbz => "application/vnd.comicbook+zip",
+        }
+    }
+
+    /// The bytes every file of this kind starts with.
+    pub fn magic(&self) -> &'static [u8] {
+        match self {
+            DocumentKind::Pdf => b"%PDF-",
+            DocumentKind::Epub | DocumentKind::Cbz => b"PK\x03\x04",
+        }
+    }
+}
+
+/// Turns a name like `book.pdf` or `book.cbz` as found in URLs into the name of the book.
+pub fn book_name(name: &str) -> &str {
+    DocumentKind::ALL
+        .iter()
+        .find_map(|kind| name.strip_suffix(&format!(".{}", kind.extension())))
+        .unwrap_or(name)
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PdfCollection {
     // Use hashmap instead for that sweet, sweet, k,v goodness. Also because JSON prefers it.
     pub pdfs: HashMap<String, Pdf>,
+    /// Deleted books by name, kept with their progress until they are purged.
+    pub trash: HashMap<String, TrashedPdf>,
+}
+
+/// A deleted book and where its file is kept until it is restored or purged.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TrashedPdf {
+    /// The book as it was before it was deleted, `path` is where it gets restored to.
+    pub book: Pdf,
+    /// Where the file is stored in the trash.
+    pub file: PathBuf,
+    pub deleted: DateTime<Local>,
 }
 
 impl PdfCollection {
@@ -53,36 +139,36 @@ impl PdfCollection {
         Arc::new(Mutex::new(self))
     }
 
-    pub fn get_book_by_name_mut<S: Into<String> + Display + ?Sized>(
+    pub fn get_book_by_name_mut<S: Into<String> + Display>(
         &mut self,
         name: &S,
     ) -> Option<&mut Pdf> {
         let stringed = name.to_string();
-        let name = stringed.strip_suffix(".pdf").unwrap_or(&stringed);
+        let name = book_name(&stringed);
         self.pdfs.get_mut(name)
     }
 
-    pub fn get_book_by_name<S: Into<String> + Display + ?Sized>(&self, name: &S) -> Option<Pdf> {
+    pub fn get_book_by_name<S: Into<String> + Display>(&self, name: &S) -> Option<Pdf> {
         let stringed = name.to_string();
-        let name = stringed.strip_suffix(".pdf").unwrap_or(&stringed);
+        let name = book_name(&stringed);
         self.pdfs.get(name).cloned()
     }
 
-    pub fn set_page_by_name<S: Into<String> + Display + ?Sized>(
+    pub fn set_page_by_name<S: Into<String> + Display>(
         &mut self,
         name: &S,
-        new_page: u16,
+        position: PagePosition,
     ) -> Option<()> {
         let stringed = name.to_string();
-        let name = stringed.strip_suffix(".pdf").unwrap_or(&stringed);
+        let name = book_name(&stringed);
         let pdf = self.pdfs.get_mut(name)?;
 
-        pdf.current_page = new_page;
+        pdf.set_page(position);
 
         Some(())
     }
 
-    pub fn has_book<S: Into<String> + Display + ?Sized>(&self, name: &S) -> bool {
+    pub fn has_book<S: Into<String> + Display>(&self, name: &S) -> bool {
         self.get_book_by_name(name).is_some()
     }
 
@@ -93,27 +179,196 @@ impl PdfCollection {
     pub fn pdfs(&self) -> HashMap<String, Pdf> {
         self.pdfs.clone()
     }
+
+    /// Moves the book `name` to `path`, its name follows the new file name.
+    pub fn relocate<S: Into<String> + Display>(&mut self, name: &S, path: PathBuf) -> Option<&Pdf> {
+        let stringed = name.to_string();
+        let name = book_name(&stringed);
+        let mut pdf = self.pdfs.remove(name)?;
+
+        pdf.relocate(path);
+        let name = pdf.name.clone();
+        self.pdfs.insert(name.clone(), pdf);
+        self.pdfs.get(&name)
+    }
+
+    /// Moves the book `name` into the trash, with its file stored at `file`.
+    ///
+    /// A book of the same name which was already in the trash is replaced.
+    pub fn trash<S: Into<String> + Display>(
+        &mut self,
+        name: &S,
+        file: PathBuf,
+        deleted: DateTime<Local>,
+    ) -> Option<&TrashedPdf> {
+        let stringed = name.to_string();
+        let name = book_name(&stringed);
+        let book = self.pdfs.remove(name)?;
+
+        self.trash.insert(
+            name.to_string(),
+            TrashedPdf {
+                book,
+                file,
+                deleted,
+            },
+        );
+        self.trash.get(name)
+    }
+
+    /// Moves the book `name` out of the trash again, with all its progress.
+    pub fn restore<S: Into<String> + Display>(&mut self, name: &S) -> Option<&Pdf> {
+        let stringed = name.to_string();
+        let name = book_name(&stringed);
+        let trashed = self.trash.remove(name)?;
+
+        self.add_book(trashed.book);
+        self.pdfs.get(name)
+    }
+
+    /// Forgets about the book `name` in the trash for good.
+    pub fn purge<S: Into<String> + Display>(&mut self, name: &S) -> Option<TrashedPdf> {
+        let stringed = name.to_string();
+        let name = book_name(&stringed);
+        self.trash.remove(name)
+    }
+}
+
+/// The amount of positions kept in the history of each book.
+pub const HISTORY_LENGTH: usize = 64;
+
+/// A page a book was turned to, and when and from where that happened.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct PagePosition {
+    pub time: DateTime<Local>,
+    pub page: u16,
+    /// How far into the page (a chapter for reflowable books) the reader is, from 0 to 1.
+    #[serde(default)]
+    pub offset: f32,
+    pub device: Option<String>,
+}
+
+impl PagePosition {
+    pub fn now(page: u16, device: Option<String>) -> Self {
+        PagePosition {
+            time: Local::now(),
+            page,
+            offset: 0.0,
+            device,
+        }
+    }
+
+    pub fn with_offset(self, offset: f32) -> Self {
+        PagePosition { offset, ..self }
+    }
+}
+
+/// Where a book is at, `revision` is bumped on every page change so clients
+/// can tell whether their view of the book is outdated.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct Progress {
+    pub page: u16,
+    /// How far into the page the reader is, see `PagePosition::offset`.
+    #[serde(default)]
+    pub offset: f32,
+    pub revision: u64,
+}
+
+/// How the viewer sizes pages.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ZoomMode {
+    /// As wide as the window, but no larger than a comfortable size on big screens.
+    #[default]
+    Auto,
+    FitWidth,
+    FitPage,
+    /// Always `ReaderPreferences::scale`.
+    Custom,
+}
+
+/// How a PDF is shown in the viewer, stored with the book so it opens the same way on every device.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct ReaderPreferences {
+    pub zoom: ZoomMode,
+    /// Zoom used with `ZoomMode::Custom`, 1 shows a page at 72 dpi.
+    pub scale: f32,
+    /// Clockwise rotation in degrees on top of the rotation of the pages, one of 0, 90, 180 and 270.
+    pub rotation: u16,
+    /// Show pages with inverted colors, for reading in the dark.
+    pub invert: bool,
+    /// Show two pages side by side.
+    pub spread: bool,
+    /// Show all pages below each other instead of one at a time.
+    pub continuous: bool,
+}
+
+impl Default for ReaderPreferences {
+    fn default() -> Self {
+        ReaderPreferences {
+            zoom: ZoomMode::Auto,
+            scale: 1.4,
+            rotation: 0,
+            invert: false,
+            spread: false,
+            continuous: false,
+        }
+    }
 }
 
+impl ReaderPreferences {
+    /// Checks the values are ones the viewer can show.
+    pub fn validate(&self) -> Result<(), String> {
+        if ![0, 90, 180, 270].contains(&self.rotation) {
+            return Err(format!(
+                "rotation must be 0, 90, 180 or 270, not {}",
+                self.rotation
+            ));
+        }
+        if !(0.25..=5.0).contains(&self.scale) {
+            return Err(format!(
+                "scale must be between 0.25 and 5, not {}",
+                self.scale
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A book in the library, despite the name it can be of any `DocumentKind`.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Pdf {
     last_access: AccessTime,
     name: String,
     path: PathBuf,
     current_page: u16,
+    /// See `PagePosition::offset`.
+    offset: f32,
+    /// Amount of page changes made to the book, see `Progress`.
+    revision: u64,
     total_pages: u16,
+    /// The latest positions of the book, oldest first.
+    history: Vec<PagePosition>,
+    /// The last position of the book on every device, by device id.
+    device_positions: HashMap<String, PagePosition>,
+    /// How the book is shown to anyone without settings of their own.
+    preferences: ReaderPreferences,
+    /// The viewer settings every sync user picked for the book, by user name.
+    user_preferences: HashMap<String, ReaderPreferences>,
+    /// What every sync user filled into the form of the book, by user name and field name.
+    form_values: HashMap<String, HashMap<String, FieldValue>>,
 }
 
 impl Pdf {
-    pub fn new(path: PathBuf) -> Pdf {
+    /// Creates a unread book at `path` without reading the file, the page count
+    /// comes from `get_total_pages` or is already known.
+    pub fn with_total_pages(path: PathBuf, total_pages: u16) -> Pdf {
         let name = path
             .file_stem()
             .expect("Failed to extract the filename from {path:?}")
             .to_str()
             .expect("Couldnt convert {path} to string!")
             .to_string();
-
-        let total_pages = Pdf::get_total_pages(path.as_path()).unwrap();
         tracing::info!("{name} has {total_pages} pages");
 
         Pdf {
@@ -121,20 +376,63 @@ impl Pdf {
             name,
             path,
             current_page: 1,
+            offset: 0.0,
+            revision: 0,
             total_pages,
+            history: vec![],
+            device_positions: HashMap::new(),
+            preferences: ReaderPreferences::default(),
+            user_preferences: HashMap::new(),
+            form_values: HashMap::new(),
         }
     }
 
-    /// Reads a pdf and gets the total pages in it.
+    /// Points the book at `path` and rereads its page count, keeping the progress.
+    pub fn refresh(&mut self, path: PathBuf) {
+        match Pdf::get_total_pages(path.as_path()) {
+            Ok(total_pages) => {
+                self.total_pages = total_pages;
+                self.path = path;
+            }
+            Err(e) => tracing::error!("Failed to refresh {path:?}: {e}"),
+        }
+    }
+
+    /// Points the book to a new file, its name follows the file name.
+    pub fn relocate(&mut self, path: PathBuf) {
+        if let Some(name) = path.file_stem().and_then(|n| n.to_str()) {
+            self.name = name.to_string();
+        }
+        self.path = path;
+    }
+
+    /// Reads a book and gets the total pages (chapters for EPUBs, images for CBZs) in it.
     /// Fails on invalid files.
-    fn get_total_pages(path: &Path) -> Result<u16, String> {
-        // Memory inefficient but gets the job done...
-        match lopdf::Document::load(path) {
-            Ok(p) => Ok(p.get_pages().into_iter().len() as u16),
-            Err(_) => Err(String::from("PDF not found at location: {path}")),
+    pub fn get_total_pages(path: &Path) -> Result<u16, String> {
+        Pdf::count_pages(
+            path,
+            DocumentKind::from_path(path).unwrap_or(DocumentKind::Pdf),
+        )
+    }
+
+    /// Like `get_total_pages`, for files whose name does not tell their kind.
+    pub fn count_pages(path: &Path, kind: DocumentKind) -> Result<u16, String> {
+        match kind {
+            DocumentKind::Epub => Ok(Epub::open(path)?.chapters.len() as u16),
+            DocumentKind::Cbz => Ok(Comic::open(path)?.pages.len() as u16),
+            // Memory inefficient but gets the job done...
+            DocumentKind::Pdf => match lopdf::Document::load(path) {
+                Ok(p) => Ok(p.get_pages().into_iter().len() as u16),
+                Err(e) => Err(format!("Failed to read PDF at {path:?}: {e}")),
+            },
         }
     }
 
+    /// The kind of the book, books of unknown kind are assumed to be PDFs.
+    pub fn kind(&self) -> DocumentKind {
+        DocumentKind::from_path(&self.path).unwrap_or(DocumentKind::Pdf)
+    }
+
     pub fn last_access(&self) -> &AccessTime {
         &self.last_access
     }
@@ -143,7 +441,6 @@ impl Pdf {
         self.name.as_ref()
     }
 
-    #[allow(dead_code)]
     pub fn path(&self) -> &PathBuf {
         &self.path
     }
@@ -156,11 +453,98 @@ impl Pdf {
         self.total_pages
     }
 
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn progress(&self) -> Progress {
+        Progress {
+            page: self.current_page,
+            offset: self.offset,
+            revision: self.revision,
+        }
+    }
+
+    pub fn history(&self) -> &[PagePosition] {
+        self.history.as_ref()
+    }
+
+    pub fn device_positions(&self) -> &HashMap<String, PagePosition> {
+        &self.device_positions
+    }
+
+    /// The device which last moved the book and the position it moved it to.
+    pub fn last_read(&self) -> Option<(&String, &PagePosition)> {
+        self.device_positions.iter().max_by_key(|(_, p)| p.time)
+    }
+
+    /// Moves the book to `position` and remembers it in the history.
+    pub fn set_page(&mut self, position: PagePosition) {
+        self.current_page = position.page;
+        self.offset = position.offset;
+        self.revision += 1;
+
+        if let Some(device) = &position.device {
+            self.device_positions
+                .insert(device.clone(), position.clone());
+        }
+
+        if self.history.len() >= HISTORY_LENGTH {
+            self.history.remove(0);
+        }
+        self.history.push(position);
+    }
+
+    /// How the book is shown to `user`, the settings of the book if the user
+    /// has none or no user is given.
+    pub fn preferences(&self, user: Option<&str>) -> &ReaderPreferences {
+        user.and_then(|user| self.user_preferences.get(user))
+            .unwrap_or(&self.preferences)
+    }
+
+    /// Changes the settings of `user`, or the ones of the book without a user.
+    pub fn set_preferences(&mut self, user: Option<&str>, preferences: ReaderPreferences) {
+        match user {
+            Some(user) => {
+                self.user_preferences.insert(user.to_string(), preferences);
+            }
+            None => self.preferences = preferences,
+        }
+    }
+
+    /// The values `user` filled into the form of the book.
+    pub fn form_values(&self, user: &str) -> HashMap<String, FieldValue> {
+        self.form_values.get(user).cloned().unwrap_or_default()
+    }
+
+    /// Replaces the values `user` filled in, an empty form is forgotten.
+    pub fn set_form_values(&mut self, user: &str, values: HashMap<String, FieldValue>) {
+        match values.is_empty() {
+            true => self.form_values.remove(user),
+            false => self.form_values.insert(user.to_string(), values),
+        };
+    }
+
+    /// The most recent position in the history on another page than the current one.
+    pub fn previous_position(&self) -> Option<&PagePosition> {
+        self.history
+            .iter()
+            .rev()
+            .find(|p| p.page != self.current_page)
+    }
+
     pub fn access(&mut self) {
-        self.last_access = AccessTime::now();
+        self.access_at(AccessTime::now());
+    }
+
+    pub fn access_at(&mut self, time: AccessTime) {
+        self.last_access = time;
     }
 
     pub fn percentage_read(&self) -> u32 {
+        if self.total_pages == 0 {
+            return 0;
+        }
         ((self.current_page as f32 / self.total_pages as f32) * 100.0).floor() as u32
     }
 }
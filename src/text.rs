@@ -0,0 +1,762 @@
+// Extracting the text of PDF pages.
+//
+// The content stream of a page is interpreted far enough to know where every
+// string is drawn and in which font, fonts are decoded through their
+// `ToUnicode` map when they have one and their encoding otherwise. The
+// strings are then put back into reading order: runs on the same baseline
+// form a line, lines are read top to bottom and column by column, and lines
+// which are further apart than usual or change size start a new paragraph.
+
+use std::collections::HashMap;
+
+use lopdf::{content::Content, Dictionary, Document, Object, ObjectId};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A rectangle on a page, in PDF points from the bottom left corner.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Rect {
+    pub left: f32,
+    pub bottom: f32,
+    pub right: f32,
+    pub top: f32,
+}
+
+/// A line of text on a page.
+///
+/// Coordinates are in PDF points from the bottom left corner of the page.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    pub text: String,
+    /// Left and right edge of every `char` of `text`.
+    pub glyphs: Vec<(f32, f32)>,
+    /// The lowest and highest point of the line.
+    pub bottom: f32,
+    pub top: f32,
+    /// Font size, in points.
+    pub size: f32,
+}
+
+impl Line {
+    fn baseline(&self) -> f32 {
+        self.bottom + self.size * DESCENT
+    }
+
+    /// The box around the `char`s `start..end` of the line.
+    pub fn bounds(&self, start: usize, end: usize) -> Option<Rect> {
+        let glyphs = self.glyphs.get(start..end)?;
+        Some(Rect {
+            left: glyphs.iter().map(|g| g.0.min(g.1)).reduce(f32::min)?,
+            bottom: self.bottom,
+            right: glyphs.iter().map(|g| g.0.max(g.1)).reduce(f32::max)?,
+            top: self.top,
+        })
+    }
+}
+
+/// How far below the baseline glyphs reach, relative to the font size.
+const DESCENT: f32 = 0.2;
+/// Width of glyphs which have none in the font, in thousandths of the font size.
+const DEFAULT_WIDTH: f32 = 500.0;
+
+/// The lines of the page with the id `page`, in reading order.
+pub fn page_lines(doc: &Document, page: ObjectId) -> Result<Vec<Line>, String> {
+    let content = doc
+        .get_and_decode_page_content(page)
+        .map_err(|e| format!("Failed to read the content of page {page:?}: {e}"))?;
+    let fonts: HashMap<Vec<u8>, Font> = doc
+        .get_page_fonts(page)
+        .into_iter()
+        .map(|(name, font)| (name, Font::new(doc, font)))
+        .collect();
+
+    let runs = Interpreter::new(&fonts).run(&content);
+    Ok(into_lines(runs))
+}
+
+/// Joins `lines` into paragraphs, undoing hyphenation at line ends.
+pub fn paragraphs(lines: &[Line]) -> Vec<String> {
+    // The usual distance between lines, paragraphs are spaced further apart.
+    // Headings and paragraph breaks make for the largest gaps, so the lower
+    // quartile is taken rather than the median.
+    let mut gaps: Vec<f32> = lines
+        .windows(2)
+        .map(|w| w[0].baseline() - w[1].baseline())
+        .filter(|gap| *gap > 0.0)
+        .collect();
+    gaps.sort_by(f32::total_cmp);
+    let usual_gap = gaps.get(gaps.len() / 4).copied().unwrap_or(0.0);
+
+    let mut paragraphs: Vec<String> = vec![];
+    let mut previous: Option<&Line> = None;
+    for line in lines {
+        let text = line.text.trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        let continues = previous.is_some_and(|p| {
+            let gap = p.baseline() - line.baseline();
+            gap > 0.0 && gap <= usual_gap * 1.4 && (p.size - line.size).abs() <= p.size * 0.1
+        });
+        match paragraphs.last_mut() {
+            Some(paragraph) if continues => {
+                let starts_lower = text.chars().next().is_some_and(char::is_lowercase);
+                if paragraph.ends_with('-') && starts_lower {
+                    paragraph.pop();
+                } else {
+                    paragraph.push(' ');
+                }
+                paragraph.push_str(text);
+            }
+            _ => paragraphs.push(text.to_string()),
+        }
+        previous = Some(line);
+    }
+
+    paragraphs
+}
+
+/// An occurrence of a search query on a page.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Hit {
+    /// The text leading up to the match.
+    pub before: String,
+    /// The match, as it is written on the page.
+    pub text: String,
+    /// The text following the match.
+    pub after: String,
+    /// Where the match is drawn, one box for every line it is on.
+    pub boxes: Vec<Rect>,
+}
+
+/// How many `char`s of context are given on either side of a hit.
+const CONTEXT: usize = 40;
+
+/// Finds every occurrence of `query` in `lines`, ignoring case, runs of
+/// whitespace and words hyphenated across lines.
+pub fn search(lines: &[Line], query: &str) -> Vec<Hit> {
+    let query: Vec<char> = query
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .map(fold_case)
+        .collect();
+    if query.is_empty() {
+        return vec![];
+    }
+
+    // The page as one string, with the line and glyph every `char` came from
+    let mut text: Vec<char> = vec![];
+    let mut origin: Vec<Option<(usize, usize)>> = vec![];
+    for (l, line) in lines.iter().enumerate() {
+        while text.last() == Some(&' ') {
+            text.pop();
+            origin.pop();
+        }
+        let starts_lower = line
+            .text
+            .trim_start()
+            .chars()
+            .next()
+            .is_some_and(char::is_lowercase);
+        if text.last() == Some(&'-') && starts_lower {
+            text.pop();
+            origin.pop();
+        } else if !text.is_empty() {
+            text.push(' ');
+            origin.push(None);
+        }
+
+        for (g, c) in line.text.chars().enumerate() {
+            if !c.is_whitespace() {
+                text.push(c);
+                origin.push(Some((l, g)));
+            } else if text.last().is_some_and(|c| *c != ' ') {
+                text.push(' ');
+                origin.push(None);
+            }
+        }
+    }
+
+    let folded: Vec<char> = text.iter().copied().map(fold_case).collect();
+    let mut hits = vec![];
+    let mut start = 0;
+    while start + query.len() <= folded.len() {
+        if folded[start..start + query.len()] != query[..] {
+            start += 1;
+            continue;
+        }
+        let end = start + query.len();
+
+        // Consecutive glyphs of the same line share a box
+        let mut boxes = vec![];
+        let mut span: Option<(usize, usize, usize)> = None;
+        for &(l, g) in origin[start..end].iter().flatten() {
+            match &mut span {
+                Some((line, _, last)) if *line == l => *last = g,
+                _ => {
+                    if let Some((line, first, last)) = span {
+                        boxes.extend(lines[line].bounds(first, last + 1));
+                    }
+                    span = Some((l, g, g));
+                }
+            }
+        }
+        if let Some((line, first, last)) = span {
+            boxes.extend(lines[line].bounds(first, last + 1));
+        }
+
+        hits.push(Hit {
+            before: text[start.saturating_sub(CONTEXT)..start].iter().collect(),
+            text: text[start..end].iter().collect(),
+            after: text[end..(end + CONTEXT).min(text.len())].iter().collect(),
+            boxes,
+        });
+        start = end;
+    }
+
+    hits
+}
+
+/// Lower cases `c`, keeping it a single `char` so positions stay the same.
+fn fold_case(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// Decodes a text string outside of a content stream, like the title of an
+/// outline entry or the name of an attachment.
+///
+/// They are UTF-16 with a byte order mark, UTF-8 with one since PDF 2.0, and
+/// PDFDocEncoding otherwise, which is Latin-1 for everything but a few symbols.
+pub fn text_string(object: &Object) -> Option<String> {
+    let Object::String(bytes, _) = object else {
+        return None;
+    };
+    Some(match bytes.as_slice() {
+        [0xfe, 0xff, rest @ ..] => {
+            let units: Vec<u16> = rest
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        [0xef, 0xbb, 0xbf, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+        _ => bytes.iter().map(|&b| b as char).collect(),
+    })
+}
+
+/// Encodes `text` as a text string, in Latin-1 when it fits and UTF-16 otherwise.
+pub fn text_object(text: &str) -> Object {
+    let latin1 = text
+        .chars()
+        .all(|c| matches!(c, '\t' | '\n' | '\r' | ' '..='~' | '\u{a1}'..='\u{ff}'));
+    let bytes = match latin1 {
+        true => text.chars().map(|c| c as u8).collect(),
+        false => [0xfe, 0xff]
+            .into_iter()
+            .chain(text.encode_utf16().flat_map(u16::to_be_bytes))
+            .collect(),
+    };
+    Object::String(bytes, lopdf::StringFormat::Literal)
+}
+
+/// A string as it was drawn on the page.
+#[derive(Clone, Debug)]
+struct Run {
+    text: String,
+    glyphs: Vec<(f32, f32)>,
+    baseline: f32,
+    size: f32,
+}
+
+impl Run {
+    fn left(&self) -> f32 {
+        self.glyphs[0].0
+    }
+
+    fn right(&self) -> f32 {
+        self.glyphs.last().map_or(self.left(), |g| g.1)
+    }
+}
+
+/// Puts `runs` in reading order and merges the ones on the same baseline.
+///
+/// Pages set in columns are read one column after the other, up to the next
+/// row which runs across the gutter, like a heading over both columns.
+fn into_lines(mut runs: Vec<Run>) -> Vec<Line> {
+    runs.retain(|r| !r.text.is_empty());
+    runs.sort_by(|a, b| b.baseline.total_cmp(&a.baseline));
+
+    let mut rows: Vec<Vec<Run>> = vec![];
+    for run in runs {
+        match rows.last_mut() {
+            Some(row)
+                if (row[0].baseline - run.baseline).abs() < row[0].size.min(run.size) * 0.5 =>
+            {
+                row.push(run)
+            }
+            _ => rows.push(vec![run]),
+        }
+    }
+    for row in &mut rows {
+        row.sort_by(|a, b| a.left().total_cmp(&b.left()));
+    }
+
+    let gutters = gutters(&rows);
+    let mut lines = vec![];
+    // The lines of every column since the last row across a gutter
+    let mut columns: Vec<Vec<Line>> = vec![vec![]; gutters.len() + 1];
+    for row in rows {
+        let crosses = row
+            .iter()
+            .any(|r| gutters.iter().any(|&g| r.left() < g && g < r.right()));
+        if crosses {
+            lines.extend(columns.iter_mut().flat_map(std::mem::take));
+            lines.push(merge(row));
+            continue;
+        }
+
+        let mut cells: Vec<Vec<Run>> = vec![vec![]; gutters.len() + 1];
+        for run in row {
+            let column = gutters.iter().filter(|&&g| g <= run.left()).count();
+            cells[column].push(run);
+        }
+        for (column, cell) in columns.iter_mut().zip(cells) {
+            if !cell.is_empty() {
+                column.push(merge(cell));
+            }
+        }
+    }
+    lines.extend(columns.into_iter().flatten());
+    lines
+}
+
+/// How many rows have to leave a gap at the same place for it to be taken as
+/// the gutter between two columns, rather than the space in a single row.
+const GUTTER_ROWS: usize = 3;
+
+/// Where the gaps between columns are, given the `rows` of a page sorted from
+/// left to right.
+///
+/// Every gap between runs that is wider than the font size is a candidate,
+/// placed just left of where the next column starts. Those that enough rows
+/// leave a gap at are the gutters.
+fn gutters(rows: &[Vec<Run>]) -> Vec<f32> {
+    let gaps: Vec<(f32, f32)> = rows
+        .iter()
+        .flat_map(|row| {
+            row.windows(2)
+                .map(|w| (w[0].right(), w[1].left(), w[0].size.max(w[1].size)))
+                .filter(|&(left, right, size)| right - left > size)
+                .map(|(left, right, _)| (left, right))
+        })
+        .collect();
+    let support = |x: f32| gaps.iter().filter(|&&(l, r)| l < x && x < r).count();
+
+    let mut candidates: Vec<(usize, f32)> = gaps
+        .iter()
+        .map(|&(_, right)| right - 0.5)
+        .map(|x| (support(x), x))
+        .filter(|&(n, _)| n >= GUTTER_ROWS)
+        .collect();
+    candidates.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.total_cmp(&b.1)));
+
+    let mut gutters: Vec<f32> = vec![];
+    for (_, x) in candidates {
+        // Candidates within a gap of an accepted one belong to the same gutter
+        let same = |g: f32| gaps.iter().any(|&(l, r)| l < x.min(g) && x.max(g) < r);
+        if !gutters.iter().any(|&g| same(g)) {
+            gutters.push(x);
+        }
+    }
+    gutters.sort_by(f32::total_cmp);
+    gutters
+}
+
+/// Merges the runs of a row, sorted from left to right, into a line.
+fn merge(row: Vec<Run>) -> Line {
+    let size = row.iter().map(|r| r.size).fold(0.0, f32::max);
+    let baseline = row[0].baseline;
+
+    let mut line = Line {
+        text: String::new(),
+        glyphs: vec![],
+        bottom: baseline - size * DESCENT,
+        top: baseline + size * (1.0 - DESCENT),
+        size,
+    };
+    for run in row {
+        if let Some(&(_, end)) = line.glyphs.last() {
+            let gap = run.left() - end;
+            let spaced = line.text.ends_with(' ') || run.text.starts_with(' ');
+            if gap > run.size * 0.15 && !spaced {
+                line.text.push(' ');
+                line.glyphs.push((end, run.left()));
+            }
+        }
+        line.text.push_str(&run.text);
+        line.glyphs.extend(run.glyphs);
+    }
+    line
+}
+
+/// What is needed of a font to decode and place its strings.
+struct Font {
+    /// Maps character codes to text, from the `ToUnicode` CMap.
+    to_unicode: Option<HashMap<u32, String>>,
+    /// The named encoding used without a `ToUnicode` map.
+    encoding: Option<String>,
+    /// Bytes per character code, 2 for composite fonts.
+    code_length: usize,
+    first_char: u32,
+    widths: Vec<f32>,
+}
+
+impl Font {
+    fn new(doc: &Document, font: &Dictionary) -> Self {
+        let composite = font.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Type0");
+        let to_unicode = font
+            .get(b"ToUnicode")
+            .and_then(|o| doc.dereference(o))
+            .and_then(|(_, o)| o.as_stream())
+            .ok()
+            .and_then(|s| {
+                s.decompressed_content()
+                    .ok()
+                    .or_else(|| Some(s.content.clone()))
+            })
+            .map(|cmap| parse_cmap(&String::from_utf8_lossy(&cmap)));
+
+        Font {
+            to_unicode,
+            encoding: font
+                .get(b"Encoding")
+                .and_then(Object::as_name_str)
+                .ok()
+                .map(String::from),
+            code_length: if composite { 2 } else { 1 },
+            first_char: font.get(b"FirstChar").and_then(Object::as_i64).unwrap_or(0) as u32,
+            widths: font
+                .get(b"Widths")
+                .and_then(|o| doc.dereference(o))
+                .and_then(|(_, o)| o.as_array())
+                .map(|w| w.iter().map(|n| n.as_float().unwrap_or(0.0)).collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Splits `bytes` into character codes and what they stand for.
+    fn decode(&self, bytes: &[u8]) -> Vec<(u32, String)> {
+        bytes
+            .chunks(self.code_length)
+            .map(|chunk| {
+                let code = chunk.iter().fold(0, |code, b| code << 8 | *b as u32);
+                let text = match &self.to_unicode {
+                    Some(map) => map.get(&code).cloned().unwrap_or_default(),
+                    None if self.code_length == 1 => Document::decode_text(
+                        self.encoding.as_deref().or(Some("StandardEncoding")),
+                        chunk,
+                    ),
+                    // Composite fonts cannot be read without a map
+                    None => String::new(),
+                };
+                (code, text)
+            })
+            .collect()
+    }
+
+    /// Advance of the glyph for `code`, in thousandths of the font size.
+    fn width(&self, code: u32) -> f32 {
+        code.checked_sub(self.first_char)
+            .and_then(|i| self.widths.get(i as usize))
+            .copied()
+            .filter(|w| *w > 0.0)
+            .unwrap_or(DEFAULT_WIDTH)
+    }
+}
+
+/// Reads the `bfchar` and `bfrange` mappings of a `ToUnicode` CMap.
+fn parse_cmap(cmap: &str) -> HashMap<u32, String> {
+    let mut map = HashMap::new();
+    let tokens: Vec<&str> = cmap
+        .split(|c: char| c.is_whitespace() || c == '<' || c == '>')
+        .flat_map(|t| split_brackets(t))
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    let hex = |t: &str| u32::from_str_radix(t, 16).ok();
+    let mut i = 0;
+    while i < tokens.len() {
+        match tokens[i] {
+            "beginbfchar" => {
+                i += 1;
+                while i + 1 < tokens.len() && tokens[i] != "endbfchar" {
+                    if let Some(code) = hex(tokens[i]) {
+                        map.insert(code, utf16_hex(tokens[i + 1]));
+                    }
+                    i += 2;
+                }
+            }
+            "beginbfrange" => {
+                i += 1;
+                while i + 2 < tokens.len() && tokens[i] != "endbfrange" {
+                    let (Some(low), Some(high)) = (hex(tokens[i]), hex(tokens[i + 1])) else {
+                        i += 1;
+                        continue;
+                    };
+                    if tokens[i + 2] == "[" {
+                        // One destination per code
+                        i += 3;
+                        let mut code = Some(low);
+                        while i < tokens.len() && tokens[i] != "]" {
+                            // Codes past `u32::MAX` are dropped, the rest of the array is skipped
+                            if let Some(c) = code {
+                                map.insert(c, utf16_hex(tokens[i]));
+                                code = c.checked_add(1);
+                            }
+                            i += 1;
+                        }
+                        i += 1;
+                    } else {
+                        // Consecutive codes map to consecutive characters
+                        let start = utf16_hex(tokens[i + 2]);
+                        let mut chars: Vec<char> = start.chars().collect();
+                        for code in low..=high.min(low.saturating_add(0xffff)) {
+                            map.insert(code, chars.iter().collect());
+                            if let Some(last) = chars.last_mut() {
+                                *last = char::from_u32(*last as u32 + 1).unwrap_or(*last);
+                            }
+                        }
+                        i += 3;
+                    }
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    map
+}
+
+/// Splits `[` and `]` off tokens, as in `[<0041><0042>]`.
+fn split_brackets(token: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut rest = token;
+    while let Some(i) = rest.find(['[', ']']) {
+        parts.push(&rest[..i]);
+        parts.push(&rest[i..=i]);
+        rest = &rest[i + 1..];
+    }
+    parts.push(rest);
+    parts
+}
+
+fn utf16_hex(hex: &str) -> String {
+    let units: Vec<u16> = hex
+        .as_bytes()
+        .chunks(4)
+        .filter_map(|c| u16::from_str_radix(std::str::from_utf8(c).ok()?, 16).ok())
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// An affine transformation `[a b c d e f]` as PDF writes them.
+#[derive(Clone, Copy, Debug)]
+struct Matrix([f32; 6]);
+
+impl Matrix {
+    const IDENTITY: Matrix = Matrix([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+    fn translation(x: f32, y: f32) -> Matrix {
+        Matrix([1.0, 0.0, 0.0, 1.0, x, y])
+    }
+
+    fn from_operands(operands: &[Object]) -> Option<Matrix> {
+        let numbers: Vec<f32> = operands.iter().filter_map(|o| o.as_float().ok()).collect();
+        Some(Matrix(numbers.try_into().ok()?))
+    }
+
+    /// `self` applied first, then `other`.
+    fn then(&self, other: &Matrix) -> Matrix {
+        let [a, b, c, d, e, f] = self.0;
+        let [a2, b2, c2, d2, e2, f2] = other.0;
+        Matrix([
+            a * a2 + b * c2,
+            a * b2 + b * d2,
+            c * a2 + d * c2,
+            c * b2 + d * d2,
+            e * a2 + f * c2 + e2,
+            e * b2 + f * d2 + f2,
+        ])
+    }
+
+    fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        let [a, b, c, d, e, f] = self.0;
+        (a * x + c * y + e, b * x + d * y + f)
+    }
+
+    /// How much the matrix scales vertical distances.
+    fn vertical_scale(&self) -> f32 {
+        self.0[2].hypot(self.0[3])
+    }
+}
+
+/// Just enough of the graphics and text state to place strings.
+struct Interpreter<'a> {
+    fonts: &'a HashMap<Vec<u8>, Font>,
+    ctm: Matrix,
+    saved: Vec<Matrix>,
+    text_matrix: Matrix,
+    line_matrix: Matrix,
+    font: Option<&'a Font>,
+    size: f32,
+    leading: f32,
+    char_spacing: f32,
+    word_spacing: f32,
+    runs: Vec<Run>,
+}
+
+impl<'a> Interpreter<'a> {
+    fn new(fonts: &'a HashMap<Vec<u8>, Font>) -> Self {
+        Interpreter {
+            fonts,
+            ctm: Matrix::IDENTITY,
+            saved: vec![],
+            text_matrix: Matrix::IDENTITY,
+            line_matrix: Matrix::IDENTITY,
+            font: None,
+            size: 0.0,
+            leading: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            runs: vec![],
+        }
+    }
+
+    fn run(mut self, content: &Content) -> Vec<Run> {
+        for operation in &content.operations {
+            let operands = &operation.operands;
+            let number = |i: usize| {
+                operands
+                    .get(i)
+                    .and_then(|o| o.as_float().ok())
+                    .unwrap_or(0.0)
+            };
+
+            match operation.operator.as_str() {
+                "q" => self.saved.push(self.ctm),
+                "Q" => self.ctm = self.saved.pop().unwrap_or(Matrix::IDENTITY),
+                "cm" => {
+                    if let Some(m) = Matrix::from_operands(operands) {
+                        self.ctm = m.then(&self.ctm);
+                    }
+                }
+                "BT" => {
+                    self.text_matrix = Matrix::IDENTITY;
+                    self.line_matrix = Matrix::IDENTITY;
+                }
+                "Tf" => {
+                    self.font = operands
+                        .first()
+                        .and_then(|o| o.as_name().ok())
+                        .and_then(|name| self.fonts.get(name));
+                    self.size = number(1);
+                }
+                "TL" => self.leading = number(0),
+                "Tc" => self.char_spacing = number(0),
+                "Tw" => self.word_spacing = number(0),
+                "Td" => self.next_line(number(0), number(1)),
+                "TD" => {
+                    self.leading = -number(1);
+                    self.next_line(number(0), number(1));
+                }
+                "Tm" => {
+                    if let Some(m) = Matrix::from_operands(operands) {
+                        self.text_matrix = m;
+                        self.line_matrix = m;
+                    }
+                }
+                "T*" => self.next_line(0.0, -self.leading),
+                "Tj" => self.show(operands),
+                "'" => {
+                    self.next_line(0.0, -self.leading);
+                    self.show(operands);
+                }
+                "\"" => {
+                    self.word_spacing = number(0);
+                    self.char_spacing = number(1);
+                    self.next_line(0.0, -self.leading);
+                    self.show(&operands[operands.len().min(2)..]);
+                }
+                "TJ" => {
+                    if let Some(Ok(parts)) = operands.first().map(Object::as_array) {
+                        self.show(parts);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.runs
+    }
+
+    fn next_line(&mut self, x: f32, y: f32) {
+        self.line_matrix = Matrix::translation(x, y).then(&self.line_matrix);
+        self.text_matrix = self.line_matrix;
+    }
+
+    /// Draws the strings in `parts`, numbers in between move the position back.
+    fn show(&mut self, parts: &[Object]) {
+        let Some(font) = self.font else {
+            return;
+        };
+        let to_page = self.text_matrix.then(&self.ctm);
+        let mut run = Run {
+            text: String::new(),
+            glyphs: vec![],
+            baseline: to_page.apply(0.0, 0.0).1,
+            size: self.size * to_page.vertical_scale(),
+        };
+
+        for part in parts {
+            match part {
+                Object::String(bytes, _) => {
+                    for (code, text) in font.decode(bytes) {
+                        let mut advance = font.width(code) / 1000.0 * self.size + self.char_spacing;
+                        if text == " " {
+                            advance += self.word_spacing;
+                        }
+                        let start = self.text_matrix.then(&self.ctm).apply(0.0, 0.0).0;
+                        self.text_matrix =
+                            Matrix::translation(advance, 0.0).then(&self.text_matrix);
+                        let end = self.text_matrix.then(&self.ctm).apply(0.0, 0.0).0;
+
+                        for c in text.chars() {
+                            run.text.push(c);
+                            run.glyphs.push((start, end));
+                        }
+                    }
+                }
+                number => {
+                    let shift = number.as_float().unwrap_or(0.0);
+                    let start = self.text_matrix.then(&self.ctm).apply(0.0, 0.0).0;
+                    self.text_matrix = Matrix::translation(-shift / 1000.0 * self.size, 0.0)
+                        .then(&self.text_matrix);
+                    // Large gaps are how many PDFs space their words
+                    if shift < -150.0 && !run.text.is_empty() && !run.text.ends_with(' ') {
+                        let end = self.text_matrix.then(&self.ctm).apply(0.0, 0.0).0;
+                        run.text.push(' ');
+                        run.glyphs.push((start, end));
+                    }
+                }
+            }
+        }
+
+        if !run.text.trim().is_empty() {
+            self.runs.push(run);
+        }
+    }
+}
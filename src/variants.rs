@@ -0,0 +1,317 @@
+// Reduced variants of PDFs for devices on slow or metered connections.
+//
+// The mobile variant compresses every stream which is stored uncompressed,
+// drops objects nothing refers to and downsamples images which have a higher
+// resolution than a phone screen can show. Generating one means rewriting the
+// whole book, so variants are cached on disk next to the state like covers,
+// and like covers the variants of older versions of a book are removed once
+// a new one is made.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Cursor, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use image::{imageops::FilterType, DynamicImage, GrayImage, ImageOutputFormat, RgbImage};
+use lopdf::{Document, Object, ObjectId, Stream};
+use serde::Deserialize;
+
+use crate::{
+    covers::{cache_key, prune_cache, remove_cached},
+    geometry::inherited,
+};
+
+/// Resolution images are downsampled to in the mobile variant.
+pub const MOBILE_DPI: f32 = 150.0;
+const JPEG_QUALITY: u8 = 75;
+
+/// The reduced versions of a book which can be downloaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Variant {
+    Mobile,
+}
+
+impl Variant {
+    fn name(&self) -> &'static str {
+        match self {
+            Variant::Mobile => "mobile",
+        }
+    }
+}
+
+/// Variants are stored in a directory named after the state file.
+#[derive(Clone, Debug)]
+pub struct VariantCache {
+    dir: PathBuf,
+    /// A lock for every variant being generated, so one is never made twice at once.
+    generating: Arc<Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>>,
+}
+
+impl VariantCache {
+    pub fn new(dir: PathBuf) -> Self {
+        VariantCache {
+            dir,
+            generating: Arc::default(),
+        }
+    }
+
+    pub fn location_for(state_location: &Path) -> PathBuf {
+        let mut path = state_location.as_os_str().to_owned();
+        path.push(".variants");
+        PathBuf::from(path)
+    }
+
+    /// Gets the path of `variant` of the PDF at `path`, generating it if it is not cached yet.
+    ///
+    /// When the variant would not be any smaller the path of the original is returned.
+    pub fn get(&self, path: &Path, variant: Variant) -> Result<PathBuf, String> {
+        let key = cache_key(path)?;
+        let cached = self.dir.join(format!("{key}.{}.pdf", variant.name()));
+        let unchanged = self.dir.join(format!("{key}.{}.same", variant.name()));
+        let lookup = || {
+            if cached.exists() {
+                Some(cached.clone())
+            } else if unchanged.exists() {
+                Some(path.to_path_buf())
+            } else {
+                None
+            }
+        };
+
+        if let Some(found) = lookup() {
+            return Ok(found);
+        }
+
+        let lock = self
+            .generating
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(cached.clone())
+            .or_default()
+            .clone();
+        let guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        // Someone else might have made it while we waited
+        let result = match lookup() {
+            Some(found) => Ok(found),
+            None => self.generate(path, variant, &key, &cached, &unchanged),
+        };
+        drop(guard);
+
+        let mut generating = self.generating.lock().unwrap_or_else(|e| e.into_inner());
+        if Arc::strong_count(&lock) == 2 {
+            generating.remove(&cached);
+        }
+        result
+    }
+
+    /// Writes `variant` of the PDF at `path` to `cached`, or marks it as `unchanged`.
+    fn generate(
+        &self,
+        path: &Path,
+        variant: Variant,
+        key: &str,
+        cached: &Path,
+        unchanged: &Path,
+    ) -> Result<PathBuf, String> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create {:?}: {e}", self.dir))?;
+        let mut doc =
+            Document::load(path).map_err(|e| format!("Failed to read PDF at {path:?}: {e}"))?;
+        match variant {
+            Variant::Mobile => reduce(&mut doc, MOBILE_DPI),
+        }
+        let mut reduced = vec![];
+        doc.save_to(&mut reduced).map_err(|e| {
+            format!(
+                "Failed to write the {} variant of {path:?}: {e}",
+                variant.name()
+            )
+        })?;
+
+        let original = fs::metadata(path).map(|m| m.len()).unwrap_or(u64::MAX);
+        if reduced.len() as u64 >= original {
+            tracing::info!("The {} variant of {path:?} is no smaller", variant.name());
+            fs::write(unchanged, b"").map_err(|e| format!("Failed to write {unchanged:?}: {e}"))?;
+            prune_cache(&self.dir, key);
+            return Ok(path.to_path_buf());
+        }
+
+        tracing::info!(
+            "Made the {} variant of {path:?}, {original} -> {} bytes",
+            variant.name(),
+            reduced.len()
+        );
+        let temp = self.dir.join(format!("{key}.{}.part", variant.name()));
+        fs::write(&temp, &reduced)
+            .and_then(|_| fs::rename(&temp, cached))
+            .map_err(|e| format!("Failed to write {cached:?}: {e}"))?;
+        prune_cache(&self.dir, key);
+        Ok(cached.to_path_buf())
+    }
+
+    /// Removes every variant of the book which was stored at `path`, for books which are gone.
+    pub fn forget(&self, path: &Path) {
+        remove_cached(&self.dir, path);
+    }
+}
+
+/// Shrinks `doc`, downsampling images to `dpi`.
+pub fn reduce(doc: &mut Document, dpi: f32) {
+    doc.prune_objects();
+    doc.delete_zero_length_streams();
+
+    for (id, (width, height)) in image_placements(doc) {
+        let Ok(Object::Stream(stream)) = doc.get_object_mut(id) else {
+            continue;
+        };
+        // Images are shown at most as large as the page they are on, so this
+        // is the smallest size which still has `dpi` wherever the image is drawn.
+        let target = ((width / 72.0 * dpi) as u32, (height / 72.0 * dpi) as u32);
+        if let Err(e) = downsample(stream, target) {
+            tracing::debug!("Leaving image {id:?} as it is: {e}");
+        }
+    }
+
+    doc.compress();
+}
+
+/// Every image drawn directly on a page, with the size of the largest page it is on in points.
+fn image_placements(doc: &Document) -> HashMap<ObjectId, (f32, f32)> {
+    let mut placements: HashMap<ObjectId, (f32, f32)> = HashMap::new();
+
+    for page in doc.get_pages().into_values() {
+        let Some((width, height)) = media_box(doc, page) else {
+            continue;
+        };
+
+        let (inline, referenced) = doc.get_page_resources(page);
+        let resources = inline.into_iter().chain(
+            referenced
+                .into_iter()
+                .filter_map(|id| doc.get_dictionary(id).ok()),
+        );
+        let xobjects = resources.filter_map(|r| {
+            r.get(b"XObject")
+                .and_then(|o| doc.dereference(o))
+                .and_then(|(_, o)| o.as_dict())
+                .ok()
+        });
+
+        for xobject in xobjects.flat_map(|x| x.iter()) {
+            let Ok(id) = xobject.1.as_reference() else {
+                continue;
+            };
+            let placement = placements.entry(id).or_insert((0.0, 0.0));
+            placement.0 = placement.0.max(width);
+            placement.1 = placement.1.max(height);
+        }
+    }
+
+    placements
+}
+
+/// The width and height of a page in points.
+fn media_box(doc: &Document, page: ObjectId) -> Option<(f32, f32)> {
+    let rect = inherited(doc, page, b"MediaBox")?.as_array().ok()?;
+    let rect: Vec<f32> = rect.iter().filter_map(|n| n.as_float().ok()).collect();
+    match rect[..] {
+        [x0, y0, x1, y1] => Some(((x1 - x0).abs(), (y1 - y0).abs())),
+        _ => None,
+    }
+}
+
+/// Scales the image in `stream` down so it is no larger than needed to cover `target` pixels.
+///
+/// Only 8 bit gray and RGB images stored as JPEG or plain Flate are touched,
+/// everything else is left as it is.
+fn downsample(stream: &mut Stream, target: (u32, u32)) -> Result<(), String> {
+    let dict = &stream.dict;
+    if dict.get(b"Subtype").and_then(Object::as_name).ok() != Some(b"Image") {
+        return Err(String::from("not an image"));
+    }
+    let dimension = |key| {
+        dict.get(key)
+            .and_then(Object::as_i64)
+            .map(|n| n as u32)
+            .map_err(|_| String::from("no dimensions"))
+    };
+    let (width, height) = (dimension(b"Width")?, dimension(b"Height")?);
+
+    // Keep enough pixels in both directions, however the image is stretched
+    let scale = f32::max(
+        target.0 as f32 / width as f32,
+        target.1 as f32 / height as f32,
+    );
+    if scale >= 1.0 {
+        return Err(String::from("no larger than needed"));
+    }
+    let (new_width, new_height) = (
+        ((width as f32 * scale).round() as u32).max(1),
+        ((height as f32 * scale).round() as u32).max(1),
+    );
+
+    let gray = match dict.get(b"ColorSpace").and_then(Object::as_name) {
+        Ok(b"DeviceGray") => true,
+        Ok(b"DeviceRGB") => false,
+        _ => return Err(String::from("unsupported color space")),
+    };
+    if dict.get(b"BitsPerComponent").and_then(Object::as_i64).ok() != Some(8)
+        || dict.has(b"Decode")
+        || dict.has(b"DecodeParms")
+    {
+        return Err(String::from("unsupported encoding"));
+    }
+
+    let filter = match dict.get(b"Filter") {
+        Ok(Object::Name(name)) => name.clone(),
+        Ok(Object::Array(filters)) if filters.len() == 1 => {
+            filters[0].as_name().map_err(|e| e.to_string())?.to_vec()
+        }
+        _ => return Err(String::from("unsupported filter")),
+    };
+
+    let content = match filter.as_slice() {
+        b"DCTDecode" => {
+            let image = image::load_from_memory(&stream.content).map_err(|e| e.to_string())?;
+            let image = image.resize_exact(new_width, new_height, FilterType::Triangle);
+            let image = match gray {
+                true => DynamicImage::ImageLuma8(image.to_luma8()),
+                false => DynamicImage::ImageRgb8(image.to_rgb8()),
+            };
+            let mut jpeg = Cursor::new(vec![]);
+            image
+                .write_to(&mut jpeg, ImageOutputFormat::Jpeg(JPEG_QUALITY))
+                .map_err(|e| e.to_string())?;
+            jpeg.into_inner()
+        }
+        b"FlateDecode" => {
+            let mut pixels = vec![];
+            ZlibDecoder::new(stream.content.as_slice())
+                .read_to_end(&mut pixels)
+                .map_err(|e| e.to_string())?;
+            let image = match gray {
+                true => GrayImage::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8),
+                false => RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8),
+            }
+            .ok_or_else(|| String::from("wrong amount of pixel data"))?;
+            let image = image.resize_exact(new_width, new_height, FilterType::Triangle);
+
+            let mut encoder = ZlibEncoder::new(vec![], Compression::best());
+            encoder
+                .write_all(image.as_bytes())
+                .map_err(|e| e.to_string())?;
+            encoder.finish().map_err(|e| e.to_string())?
+        }
+        _ => return Err(String::from("unsupported filter")),
+    };
+
+    stream.dict.set("Width", new_width as i64);
+    stream.dict.set("Height", new_height as i64);
+    stream.set_content(content);
+    Ok(())
+}
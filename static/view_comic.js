@@ -0,0 +1,144 @@
+var bookName = window.book_name;
+var pageNum = parseInt(window.book_page);
+var pageCount = parseInt(window.book_pages);
+var revision = parseInt(window.book_revision);
+var image = document.getElementById('the-image');
+
+/**
+* Shows `message` above the book, until the next change is saved.
+*/
+function showError(message) {
+    var error = document.getElementById('error');
+    error.textContent = message;
+    error.hidden = false;
+}
+
+function clearError() {
+    document.getElementById('error').hidden = true;
+}
+
+function pageUrl(num) {
+    return "http://" + window.location.host + "/api/v1/books/" + encodeURIComponent(bookName) + "/pages/" + num + "/image";
+}
+
+/**
+* Shows page `num` and loads the next one in the background so turning the page is instant.
+*/
+function showPage(num) {
+    pageNum = num;
+    image.src = pageUrl(num);
+    document.getElementById('page_num').textContent = num;
+    if (num < pageCount) {
+        new Image().src = pageUrl(num + 1);
+    }
+}
+
+function onPrevPage() {
+    if (pageNum <= 1) {
+        return;
+    }
+    window.scrollTo(0,0);
+    put_progress(pageNum - 1);
+}
+document.getElementById('prev').addEventListener('click', onPrevPage);
+
+function onNextPage() {
+    if (pageNum >= pageCount) {
+        return;
+    }
+    window.scrollTo(0,0);
+    put_progress(pageNum + 1);
+}
+document.getElementById('next').addEventListener('click', onNextPage);
+
+/**
+* Jumps back to the previous position stored on the server.
+*/
+function onBack() {
+    var dest = "http://" + window.location.host + "/api/v1/books/" + encodeURIComponent(bookName) + "/history/back";
+    fetch(dest, {method: "POST"}).then(function(response) {
+        if (!response.ok) {
+            return null;
+        }
+        return response.json();
+    }).then(function(position) {
+        if (position === null) {
+            return;
+        }
+        revision = position.revision;
+        showPage(position.page);
+    }).catch(() => {
+        showError("Failed to go back.");
+    });
+}
+document.getElementById('back').addEventListener('click', onBack);
+
+/**
+* Sends `newPage` to the server along with the revision we last saw.
+* On a 409 someone else moved the book, the user gets to pick which page
+* to continue on.
+*/
+function put_progress(newPage) {
+    var dest = "http://" + window.location.host + "/api/v1/books/" + encodeURIComponent(bookName) + "/progress";
+    fetch(dest, {
+        method: "PUT",
+        headers: {'Content-Type': 'application/json'},
+        body: JSON.stringify({
+            "page": newPage,
+            "revision": revision,
+        })
+    }).then(function(response) {
+        return response.json().then(data => ({status: response.status, body: data}));
+    }).then(function(res) {
+        if (res.status == 409) {
+            var current = res.body.current;
+            revision = current.revision;
+            var last_read = current.last_read ? "\n(" + current.last_read + ")" : "";
+            if (confirm("Desynced!\nJump to the page stored remotely?\n(local is at page: " + pageNum + ", server is at page: " + current.page + ")" + last_read)) {
+                showPage(current.page);
+            } else {
+                // Keep reading locally, overriding the server.
+                put_progress(newPage);
+            }
+            return;
+        }
+
+        if (res.status != 200) {
+            showError(res.body.message);
+            return;
+        }
+
+        clearError();
+        revision = res.body.revision;
+        showPage(res.body.page);
+    }).catch(() => {
+        showError("Failed to save the page, is the server running?");
+    });
+}
+
+/**
+* Follows page changes made on other devices as they happen.
+*/
+var events = new EventSource("http://" + window.location.host + "/api/events");
+events.addEventListener("progress", function(e) {
+    var data = JSON.parse(e.data);
+    if (data.book != bookName || data.status.revision <= revision) {
+        return;
+    }
+
+    revision = data.status.revision;
+    if (data.status.page != pageNum) {
+        showPage(data.status.page);
+    }
+});
+
+document.onkeydown = function(e) {
+    e = e || window.event;
+    if (e.keyCode == '37') {
+        onPrevPage();
+    } else if (e.keyCode == '39') {
+        onNextPage();
+    }
+};
+
+showPage(pageNum);
@@ -0,0 +1,186 @@
+var bookName = window.book_name;
+var chapterNum = parseInt(window.book_chapter);
+var offset = parseFloat(window.book_offset);
+var revision = parseInt(window.book_revision);
+var frame = document.getElementById('the-chapter');
+var select = document.getElementById('chapter');
+var chapterCount = select.options.length;
+var saveTimeout = null;
+
+/**
+* Shows `message` above the book, until the next change is saved.
+*/
+function showError(message) {
+    var error = document.getElementById('error');
+    error.textContent = message;
+    error.hidden = false;
+}
+
+function clearError() {
+    document.getElementById('error').hidden = true;
+}
+
+/**
+* Shows chapter `num`, scrolled `scrollTo` (0 to 1) of the way down.
+*/
+function showChapter(num, scrollTo) {
+    chapterNum = num;
+    offset = scrollTo;
+    select.value = num;
+    var url = select.options[num - 1].dataset.url;
+    if (frame.dataset.url == url) {
+        scrollFrame();
+    } else {
+        frame.dataset.url = url;
+        frame.src = url;
+    }
+}
+
+function scrollFrame() {
+    var doc = frame.contentDocument.documentElement;
+    frame.contentWindow.scrollTo(0, offset * (doc.scrollHeight - frame.clientHeight));
+}
+
+/**
+* How far the chapter is scrolled, from 0 to 1.
+*/
+function currentOffset() {
+    var doc = frame.contentDocument.documentElement;
+    var scrollable = doc.scrollHeight - frame.clientHeight;
+    if (scrollable <= 0) {
+        return 0;
+    }
+    return Math.min(1, Math.max(0, frame.contentWindow.scrollY / scrollable));
+}
+
+frame.addEventListener('load', function() {
+    var body = frame.contentDocument.body;
+    if (body) {
+        body.style.maxWidth = "40em";
+        body.style.margin = "1em auto";
+        body.style.padding = "0 1em";
+        body.style.lineHeight = "1.5";
+    }
+    scrollFrame();
+
+    // Remember how far into the chapter the reader is once they stop scrolling
+    frame.contentWindow.addEventListener('scroll', function() {
+        clearTimeout(saveTimeout);
+        saveTimeout = setTimeout(function() {
+            put_progress(chapterNum, currentOffset());
+        }, 1000);
+    });
+});
+
+/**
+* Sends the position to the server along with the revision we last saw.
+* On a 409 someone else moved the book, the user gets to pick which
+* position to continue at.
+*/
+function put_progress(newChapter, newOffset) {
+    var dest = "http://" + window.location.host + "/api/v1/books/" + encodeURIComponent(bookName) + "/progress";
+    fetch(dest, {
+        method: "PUT",
+        headers: {'Content-Type': 'application/json'},
+        body: JSON.stringify({
+            "page": newChapter,
+            "offset": newOffset,
+            "revision": revision,
+        })
+    }).then(function(response) {
+        return response.json().then(data => ({status: response.status, body: data}));
+    }).then(function(res) {
+        if (res.status == 409) {
+            var current = res.body.current;
+            revision = current.revision;
+            var last_read = current.last_read ? "\n(" + current.last_read + ")" : "";
+            if (confirm("Desynced!\nJump to the position stored remotely?\n(local is at chapter: " + chapterNum + ", server is at chapter: " + current.page + ")" + last_read)) {
+                showChapter(current.page, current.offset);
+            } else {
+                // Keep reading locally, overriding the server.
+                put_progress(newChapter, newOffset);
+            }
+            return;
+        }
+
+        if (res.status != 200) {
+            showError(res.body.message);
+            return;
+        }
+
+        clearError();
+        revision = res.body.revision;
+        if (res.body.page != chapterNum) {
+            showChapter(res.body.page, res.body.offset);
+        }
+    }).catch(() => {
+        showError("Failed to save the position, is the server running?");
+    });
+}
+
+function onPrevPage() {
+    if (chapterNum <= 1) {
+        return;
+    }
+    showChapter(chapterNum - 1, 0);
+    put_progress(chapterNum, 0);
+}
+document.getElementById('prev').addEventListener('click', onPrevPage);
+
+function onNextPage() {
+    if (chapterNum >= chapterCount) {
+        return;
+    }
+    showChapter(chapterNum + 1, 0);
+    put_progress(chapterNum, 0);
+}
+document.getElementById('next').addEventListener('click', onNextPage);
+
+select.addEventListener('change', function() {
+    showChapter(parseInt(select.value), 0);
+    put_progress(chapterNum, 0);
+});
+
+function onBack() {
+    var dest = "http://" + window.location.host + "/api/v1/books/" + encodeURIComponent(bookName) + "/history/back";
+    fetch(dest, {method: "POST"}).then(function(response) {
+        if (response.status != 200) {
+            return;
+        }
+        return response.json();
+    }).then(function(position) {
+        if (position === undefined) {
+            return;
+        }
+        revision = position.revision;
+        showChapter(position.page, position.offset);
+    }).catch(() => {
+        showError("Failed to go back.");
+    });
+}
+document.getElementById('back').addEventListener('click', onBack);
+
+/**
+* Follows position changes made on other devices as they happen.
+*/
+var events = new EventSource("http://" + window.location.host + "/api/events");
+events.addEventListener("progress", function(e) {
+    var data = JSON.parse(e.data);
+    if (data.book != bookName || data.status.revision <= revision) {
+        return;
+    }
+
+    revision = data.status.revision;
+    showChapter(data.status.page, data.status.offset);
+});
+
+document.onkeydown = function(e) {
+    e = e || window.event;
+    if (e.keyCode == '37') {
+        onPrevPage();
+    } else if (e.keyCode == '39') {
+        onNextPage();
+    }
+};
+
+showChapter(chapterNum, offset);
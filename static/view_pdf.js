@@ -9,87 +9,487 @@ pdfjsLib.GlobalWorkerOptions.workerSrc = '//mozilla.github.io/pdf.js/build/pdf.w
 
 var pdfDoc = null;
 var pageNum = parseInt(window.pdf_page);
+var revision = parseInt(window.pdf_revision);
 var pdf_name = window.pdf_name;
-var pageRendering = false;
-var pageNumPending = null;
-var scale = 1.01;
-var canvas = document.getElementById('the-canvas');
-var ctx = canvas.getContext('2d');
+var book = pdf_name.replace(/\.pdf$/, "");
+var pagesView = document.getElementById('pages');
+var textView = document.getElementById('the-text');
+// Whether pages are shown as reflowed text instead of rendered, remembered per browser
+var reflow = localStorage.getItem('reflow') == 'true';
+// Boxes of the hits of the last search, by page number
+var searchHits = {};
+// Size and rotation of every page, as given by the server
+var pageGeometry = [];
+// Used for pages the server did not give the size of, US Letter like the server
+var defaultSize = {width: 612, height: 792};
+// How the book is shown, stored on the server so every device shows it the same way
+var preferences = window.reader_preferences;
 
 /**
-* Get page info from document, resize canvas accordingly, and render page.
+* Shows `message` above the book, until the next change is saved.
+*/
+function showError(message) {
+    var error = document.getElementById('error');
+    error.textContent = message;
+    error.hidden = false;
+}
+
+function clearError() {
+    document.getElementById('error').hidden = true;
+}
+
+/**
+* Shows page `num`, along with the page next to it in a spread. In
+* continuous mode every page is laid out and page `num` is scrolled to.
 * @param num Page number.
 */
 function renderPage(num) {
-    pageRendering = true;
-    // Using promise to fetch the page
+    document.getElementById('page_num').textContent = num;
+    if (reflow) {
+        showText(num);
+        return;
+    }
+
+    if (preferences.continuous) {
+        if (pagesView.dataset.layout != "continuous") {
+            layoutContinuous();
+        }
+        var canvas = pagesView.querySelector('canvas[data-page="' + num + '"]');
+        if (canvas !== null && !isShown(canvas)) {
+            canvas.scrollIntoView();
+        }
+        return;
+    }
+
+    pagesView.dataset.layout = "single";
+    pagesView.replaceChildren();
+    var row = rowOf(num);
+    var scale = rowScale(row);
+    var div = document.createElement('div');
+    div.className = 'row';
+    row.forEach(function(n) {
+        var canvas = pageCanvas(n, scale);
+        div.appendChild(pageFrame(canvas));
+        drawPage(canvas);
+    });
+    pagesView.appendChild(div);
+}
+
+/**
+* Lays out every page below each other, pages are drawn once they come close to the screen.
+*/
+function layoutContinuous() {
+    pagesView.dataset.layout = "continuous";
+    pagesView.replaceChildren();
+    if (pageObserver !== null) {
+        pageObserver.disconnect();
+    }
+    pageObserver = new IntersectionObserver(function(entries) {
+        entries.forEach(function(entry) {
+            if (entry.isIntersecting) {
+                drawPage(entry.target);
+            } else {
+                releasePage(entry.target);
+            }
+        });
+    }, {rootMargin: "100% 0px"});
+
+    for (var first = 1; first <= pdfDoc.numPages; first = rowOf(first).slice(-1)[0] + 1) {
+        var row = rowOf(first);
+        var scale = rowScale(row);
+        var div = document.createElement('div');
+        div.className = 'row';
+        row.forEach(function(n) {
+            var canvas = pageCanvas(n, scale);
+            div.appendChild(pageFrame(canvas));
+            pageObserver.observe(canvas);
+        });
+        pagesView.appendChild(div);
+    }
+}
+var pageObserver = null;
+
+/**
+* The pages shown together with page `num`, pairs of an odd and an even page in a spread.
+*/
+function rowOf(num) {
+    if (!preferences.spread) {
+        return [num];
+    }
+    var first = num % 2 == 1 ? num : num - 1;
+    return first + 1 <= pdfDoc.numPages ? [first, first + 1] : [first];
+}
+
+/**
+* The size of page `num` at scale 1, as it is shown after rotating.
+*/
+function pageSize(num) {
+    var size = pageGeometry[num - 1] || defaultSize;
+    if (preferences.rotation % 180 != 0) {
+        return {width: size.height, height: size.width};
+    }
+    return {width: size.width, height: size.height};
+}
+
+/**
+* The scale the pages of `row` are shown at, following the zoom preference.
+*/
+function rowScale(row) {
+    var sizes = row.map(pageSize);
+    var width = sizes.reduce((sum, size) => sum + size.width, 0);
+    var height = Math.max(...sizes.map((size) => size.height));
+    var availableWidth = document.documentElement.clientWidth - 20 - 10 * (row.length - 1);
+    var availableHeight = window.innerHeight - document.querySelector('.button_container').offsetHeight - 20;
+
+    switch (preferences.zoom) {
+        case "fit_width":
+            return availableWidth / width;
+        case "fit_page":
+            return Math.min(availableWidth / width, availableHeight / height);
+        case "custom":
+            return preferences.scale;
+        default:
+            // As wide as the window, but no larger than comfortable on big screens
+            return Math.min(1.4, availableWidth / width);
+    }
+}
+
+/**
+* Creates the canvas for page `num`, sized so the layout does not jump around when it is drawn.
+*/
+function pageCanvas(num, scale) {
+    var canvas = document.createElement('canvas');
+    var size = pageSize(num);
+    canvas.dataset.page = num;
+    canvas.dataset.scale = scale;
+    canvas.style.width = Math.floor(size.width * scale) + "px";
+    canvas.style.height = Math.floor(size.height * scale) + "px";
+    return canvas;
+}
+
+/**
+* Wraps `canvas` in an element the links of the page can be put on top of.
+*/
+function pageFrame(canvas) {
+    var frame = document.createElement('div');
+    frame.className = 'page';
+    frame.appendChild(canvas);
+    return frame;
+}
+
+/**
+* Renders the page of `canvas` into it, unless that was already done.
+*/
+function drawPage(canvas) {
+    if (canvas.drawing) {
+        return;
+    }
+    var num = parseInt(canvas.dataset.page);
+    var token = {};
+    canvas.drawing = token;
+
     pdfDoc.getPage(num).then(function(page) {
-        var viewport = page.getViewport({scale: scale});
-
-        console.log("mobile: " + /Android|webOS|iPhone|iPad|iPod|BlackBerry|IEMobile|Opera Mini/i.test(navigator.userAgent));
-        if (/Android|webOS|iPhone|iPad|iPod|BlackBerry|IEMobile|Opera Mini/i.test(navigator.userAgent)) {
-	    // mobile
-	    scale = 1.2;
-            //scale = canvas.width / page.getViewport(1.0);
-            viewport = page.getViewport({scale: scale});
-            canvas.height = viewport.height + 50;
-            canvas.width = viewport.width;
-        } else {
-	    // desktop
-            scale = 1.4;
-            viewport = page.getViewport({scale: scale});
-            canvas.height = viewport.height;
-            canvas.width = viewport.width;
+        if (canvas.drawing !== token) {
+            return;
         }
+        var viewport = page.getViewport({
+            scale: parseFloat(canvas.dataset.scale),
+            rotation: (page.rotate + preferences.rotation) % 360,
+        });
+        canvas.width = viewport.width;
+        canvas.height = viewport.height;
+        canvas.style.width = Math.floor(viewport.width) + "px";
+        canvas.style.height = Math.floor(viewport.height) + "px";
+
+        canvas.renderTask = page.render({canvasContext: canvas.getContext('2d'), viewport: viewport});
+        canvas.renderTask.promise.then(function() {
+            highlight(canvas, num, viewport);
+            addLinks(canvas, num, viewport);
+        }).catch(function() {
+            // Cancelled because the page went off screen
+        });
+    });
+}
+
+/**
+* Frees the memory of a page which went far off screen, it is drawn again when it comes back.
+*/
+function releasePage(canvas) {
+    if (canvas.renderTask) {
+        canvas.renderTask.cancel();
+        canvas.renderTask = null;
+    }
+    canvas.drawing = null;
+    canvas.width = 0;
+    canvas.height = 0;
+    canvas.parentElement.querySelectorAll('a').forEach((a) => a.remove());
+}
+
+// Links of every page, fetched the first time the page is drawn
+var pageLinks = {};
+
+/**
+* Puts the links of page `num` over `canvas`, links within the book turn the page.
+*/
+function addLinks(canvas, num, viewport) {
+    if (pageLinks[num] === undefined) {
+        var dest = "http://" + window.location.host + "/api/v1/books/" + book + "/pages/" + num + "/links";
+        pageLinks[num] = fetch(dest).then(function(response) {
+            return response.ok ? response.json() : [];
+        }).catch(() => []);
+    }
 
-        console.log("width: " +canvas.width+"\theight: " + canvas.height);
-
-        // Render PDF page into canvas context
-        var renderContext = {
-            canvasContext: ctx,
-            // transparent background, doesnt look that good tho...
-            // background: 'rgba(0,0,0,0)',
-            viewport: viewport
-        };
-        var renderTask = page.render(renderContext);
-
-        // Wait for rendering to finish
-        renderTask.promise.then(function() {
-            pageRendering = false;
-            if (pageNumPending !== null) {
-                // New page rendering is pending
-                renderPage(pageNumPending);
-                pageNumPending = null;
+    pageLinks[num].then(function(links) {
+        var frame = canvas.parentElement;
+        frame.querySelectorAll('a').forEach((a) => a.remove());
+        links.forEach(function(link) {
+            var a = document.createElement('a');
+            if (link.page !== null) {
+                a.href = "#";
+                a.title = "Page " + link.page;
+                a.addEventListener('click', function(e) {
+                    e.preventDefault();
+                    toTop();
+                    post_page(link.page);
+                });
+            } else if (/^(https?|mailto):/i.test(link.uri)) {
+                // Anything else, like javascript: links, is never followed
+                a.href = link.uri;
+                a.target = "_blank";
+                a.rel = "noopener noreferrer";
+                a.title = link.uri;
+            } else {
+                return;
             }
+
+            var rect = viewport.convertToViewportRectangle([link.rect.left, link.rect.bottom, link.rect.right, link.rect.top]);
+            a.className = 'link';
+            a.style.left = Math.min(rect[0], rect[2]) + "px";
+            a.style.top = Math.min(rect[1], rect[3]) + "px";
+            a.style.width = Math.abs(rect[2] - rect[0]) + "px";
+            a.style.height = Math.abs(rect[3] - rect[1]) + "px";
+            frame.appendChild(a);
         });
     });
+}
 
-    // Update page counters
+/**
+* Whether the middle of the window is on `canvas`.
+*/
+function isShown(canvas) {
+    var rect = canvas.getBoundingClientRect();
+    return rect.top <= window.innerHeight / 2 && rect.bottom >= window.innerHeight / 2;
+}
+
+/**
+* In continuous mode the page in the middle of the window is the current one.
+*/
+var scrollTimeout = null;
+window.addEventListener('scroll', function() {
+    if (!preferences.continuous || reflow) {
+        return;
+    }
+    clearTimeout(scrollTimeout);
+    scrollTimeout = setTimeout(function() {
+        var shown = Array.from(pagesView.querySelectorAll('canvas')).find(isShown);
+        if (shown !== undefined && parseInt(shown.dataset.page) != pageNum) {
+            post_page(parseInt(shown.dataset.page));
+        }
+    }, 300);
+});
+
+/**
+* Lays the pages out again, e.g. after the window was resized.
+*/
+function relayout() {
+    pagesView.dataset.layout = "";
+    if (pdfDoc !== null) {
+        renderPage(pageNum);
+    }
+}
+var resizeTimeout = null;
+window.addEventListener('resize', function() {
+    clearTimeout(resizeTimeout);
+    resizeTimeout = setTimeout(relayout, 200);
+});
+
+/**
+* Goes to the top of the page after turning it, so user can read good.
+* Continuous mode scrolls to the page instead.
+*/
+function toTop() {
+    if (!preferences.continuous) {
+        window.scrollTo(0,0);
+    }
+}
+
+/**
+* Shows the text of page `num` as paragraphs which wrap to fit the screen.
+*/
+function showText(num) {
     document.getElementById('page_num').textContent = num;
+    var dest = "http://" + window.location.host + "/api/v1/books/" + book + "/pages/" + num + "/text";
+    fetch(dest).then(function(response) {
+        return response.json();
+    }).then(function(page) {
+        // Answers for pages which are no longer shown are dropped
+        if (page.page != pageNum || !reflow) {
+            return;
+        }
+        textView.replaceChildren();
+        if (page.paragraphs === undefined || page.paragraphs.length == 0) {
+            var empty = document.createElement('p');
+            empty.textContent = "This page has no text, it may be a scan.";
+            textView.appendChild(empty);
+        }
+        (page.paragraphs || []).forEach(function(text) {
+            var paragraph = document.createElement('p');
+            paragraph.textContent = text;
+            textView.appendChild(paragraph);
+        });
+    }).catch(() => {
+        showError("Failed to load the text of page " + num + ".");
+    });
 }
 
 /**
-* If another page rendering in progress, waits until the rendering is
-* finised. Otherwise, executes rendering immediately.
+* Marks the search hits on page `num`, which was just drawn on `canvas` with `viewport`.
 */
-function queueRenderPage(num) {
-    if (pageRendering) {
-        pageNumPending = num;
-    } else {
-        renderPage(num);
+function highlight(canvas, num, viewport) {
+    var ctx = canvas.getContext('2d');
+    ctx.fillStyle = 'rgba(255, 220, 0, 0.4)';
+    (searchHits[num] || []).forEach(function(box) {
+        var rect = viewport.convertToViewportRectangle([box.left, box.bottom, box.right, box.top]);
+        ctx.fillRect(Math.min(rect[0], rect[2]), Math.min(rect[1], rect[3]),
+            Math.abs(rect[2] - rect[0]), Math.abs(rect[3] - rect[1]));
+    });
+}
+
+/**
+* Searches the book and lists the hits, clicking one jumps to its page.
+*/
+function onSearch(e) {
+    e.preventDefault();
+    var query = document.getElementById('search_query').value;
+    var list = document.getElementById('search_results');
+    list.replaceChildren();
+    searchHits = {};
+    if (query.trim() == "") {
+        list.hidden = true;
+        relayout();
+        return;
     }
+
+    var dest = "http://" + window.location.host + "/api/v1/books/" + book + "/search?q=" + encodeURIComponent(query);
+    fetch(dest).then(function(response) {
+        return response.json();
+    }).then(function(results) {
+        list.hidden = false;
+        if (results.pages === undefined || results.pages.length == 0) {
+            var none = document.createElement('li');
+            none.textContent = "Nothing found.";
+            list.appendChild(none);
+            return;
+        }
+        results.pages.forEach(function(page) {
+            searchHits[page.page] = page.hits.flatMap((hit) => hit.boxes);
+            page.hits.forEach(function(hit) {
+                var item = document.createElement('li');
+                var mark = document.createElement('mark');
+                mark.textContent = hit.text;
+                item.append("p. " + page.page + ": …" + hit.before, mark, hit.after + "…");
+                item.addEventListener('click', function() {
+                    toTop();
+                    post_page(page.page);
+                });
+                list.appendChild(item);
+            });
+        });
+        relayout();
+    }).catch(() => {
+        showError("Failed to search the book.");
+    });
+}
+document.getElementById('search').addEventListener('submit', onSearch);
+
+/**
+* Switches between the rendered page and its reflowed text.
+*/
+function onReflow() {
+    reflow = !reflow;
+    localStorage.setItem('reflow', reflow);
+    pagesView.hidden = reflow;
+    textView.hidden = !reflow;
+    relayout();
 }
+document.getElementById('reflow').addEventListener('click', onReflow);
+pagesView.hidden = reflow;
+textView.hidden = !reflow;
+
+/**
+* Fills the settings form in with `preferences`.
+*/
+function showPreferences() {
+    document.getElementById('zoom').value = preferences.zoom;
+    document.getElementById('scale').value = preferences.scale;
+    document.getElementById('scale').hidden = preferences.zoom != "custom";
+    document.getElementById('rotation').value = preferences.rotation;
+    document.getElementById('invert').checked = preferences.invert;
+    document.getElementById('spread').checked = preferences.spread;
+    document.getElementById('continuous').checked = preferences.continuous;
+    pagesView.classList.toggle('inverted', preferences.invert);
+}
+showPreferences();
+
+/**
+* Stores the settings on the server and shows the book with them.
+*/
+function onPreferencesChange() {
+    var changed = {
+        zoom: document.getElementById('zoom').value,
+        scale: parseFloat(document.getElementById('scale').value) || preferences.scale,
+        rotation: parseInt(document.getElementById('rotation').value),
+        invert: document.getElementById('invert').checked,
+        spread: document.getElementById('spread').checked,
+        continuous: document.getElementById('continuous').checked,
+    };
+    var dest = "http://" + window.location.host + "/api/v1/books/" + book + "/preferences";
+    fetch(dest, {
+        method: "PUT",
+        headers: {'Content-Type': 'application/json'},
+        body: JSON.stringify(changed),
+    }).then(function(response) {
+        return response.json().then(data => ({status: response.status, body: data}));
+    }).then(function(res) {
+        if (res.status != 200) {
+            showError(res.body.message);
+        } else {
+            clearError();
+            preferences = res.body;
+        }
+        showPreferences();
+        toTop();
+        relayout();
+    }).catch(() => {
+        showError("Failed to save the settings.");
+    });
+}
+document.getElementById('preferences').addEventListener('change', onPreferencesChange);
+document.getElementById('preferences').addEventListener('submit', (e) => e.preventDefault());
+document.getElementById('settings').addEventListener('click', function() {
+    var form = document.getElementById('preferences');
+    form.hidden = !form.hidden;
+});
 
 /**
 * Displays previous page.
 */
 function onPrevPage() {
-    if (pageNum <= 1) {
+    if (rowOf(pageNum)[0] <= 1) {
         return;
     }
 
-    // Scroll to the top of the page so user can read good
-    window.scrollTo(0,0);
+    toTop();
     set_page("-");
 }
 document.getElementById('prev').addEventListener('click', onPrevPage);
@@ -98,61 +498,170 @@ document.getElementById('prev').addEventListener('click', onPrevPage);
 * Displays next page.
 */
 function onNextPage() {
-    if (pageNum >= pdfDoc.numPages) {
+    if (rowOf(pageNum).slice(-1)[0] >= pdfDoc.numPages) {
         return;
     }
 
-    // Scroll to the top of the page so user can read good
-    window.scrollTo(0,0);
+    toTop();
     set_page("+");
 }
 document.getElementById('next').addEventListener('click', onNextPage);
 
-function set_page(direction) {
-    var dest = "http://" + window.location.host + "/status/"+pdf_name;
-    fetch(dest).then(function(response) {
+/**
+* Jumps back to the previous position stored on the server,
+* e.g. after accidentally jumping to the wrong page.
+*/
+function onBack() {
+    var dest = "http://" + window.location.host + "/api/v1/books/" + pdf_name + "/history/back";
+    fetch(dest, {method: "POST"}).then(function(response) {
+        if (!response.ok) {
+            return null;
+        }
         return response.json();
-    }).then(function(data) {
-        console.log(data);
-        server_page = parseInt(data);
-        console.log(server_page);
-
-        if (server_page === NaN || server_page == -1) {
-            // return early on error
+    }).then(function(position) {
+        if (position === null) {
             return;
         }
 
-        // Only actually increment the page if we are unsynced.
-        if (pageNum != server_page) {
-            if (confirm("Desynced!\nJump to the page stored remotely?\n(local is at page: " + pageNum + ", server is at page: " + server_page + ")")) {
-                pageNum = data;
-            }
-        } else {
-            if (direction == "+") {
-                pageNum++;
+        toTop();
+        pageNum = position.page;
+        revision = position.revision;
+        renderPage(pageNum);
+    }).catch(() => {
+        showError("Failed to go back.");
+    });
+}
+document.getElementById('back').addEventListener('click', onBack);
+
+function set_page(direction) {
+    // Spreads turn both pages at once
+    var row = rowOf(pageNum);
+    var newPage = direction == "+" ? row[row.length - 1] + 1 : rowOf(row[0] - 1)[0];
+    post_page(newPage);
+}
+
+/**
+* Sends `newPage` to the server along with the revision we last saw.
+* If someone else moved the book in the meantime the server answers with
+* 409 and its current progress, in which case the user gets to pick which
+* page to continue on.
+*/
+function post_page(newPage) {
+    var dest = "http://"+window.location.host+"/view/"+pdf_name+"/set_page";
+    fetch(dest, {
+        method: "POST",
+        headers: {'Content-Type': 'application/json'},
+        body: JSON.stringify({
+            "token" : "",
+            "pdf_name" : pdf_name,
+            "new_page" : newPage,
+            "revision" : revision,
+        })
+    }).then(function(response) {
+        return response.json().then(data => ({status: response.status, body: data}));
+    }).then(function(res) {
+        if (res.status == 409) {
+            var current = res.body.current;
+            revision = current.revision;
+            var last_read = current.last_read ? "\n(" + current.last_read + ")" : "";
+            if (confirm("Desynced!\nJump to the page stored remotely?\n(local is at page: " + pageNum + ", server is at page: " + current.page + ")" + last_read)) {
+                pageNum = current.page;
+                renderPage(pageNum);
             } else {
-                pageNum--;
+                // Keep reading locally, overriding the server.
+                post_page(newPage);
             }
+            return;
         }
 
-        var dest = "http://"+window.location.host+"/view/"+pdf_name+"/set_page";
-        fetch(dest, {
-            method: "POST",
-            headers: {'Content-Type': 'application/json'},
-            body: JSON.stringify({
-                "token" : "",
-                "pdf_name" : pdf_name,
-                "new_page" : pageNum,
-            })
-        }).then(res => {});
-        queueRenderPage(pageNum);
-    }).catch((e) => {
-        console.log("Booo");
-        console.log(e);
-    });
+        if (res.status != 200) {
+            showError(res.body.message);
+            return;
+        }
 
+        clearError();
+        pageNum = res.body.page;
+        revision = res.body.revision;
+        renderPage(pageNum);
+    }).catch(() => {
+        showError("Failed to save the page, is the server running?");
+    });
 }
 
+/**
+* Follows page changes made on other devices as they happen.
+*/
+var events = new EventSource("http://" + window.location.host + "/api/events");
+events.addEventListener("progress", function(e) {
+    var data = JSON.parse(e.data);
+    if (data.book != book || data.status.revision <= revision) {
+        return;
+    }
+
+    revision = data.status.revision;
+    if (data.status.page != pageNum) {
+        pageNum = data.status.page;
+        // The initial render picks the page up if the PDF is still loading
+        if (pdfDoc !== null) {
+            renderPage(pageNum);
+        }
+    }
+});
+
+/**
+* Lists the files embedded in the book, if it has any.
+*/
+fetch("http://" + window.location.host + "/api/v1/books/" + book + "/attachments").then(function(response) {
+    return response.ok ? response.json() : [];
+}).then(function(attachments) {
+    var list = document.querySelector('#attachments ul');
+    attachments.forEach(function(attachment) {
+        var link = document.createElement('a');
+        link.href = "/api/v1/books/" + book + "/attachments/" + attachment.number;
+        link.textContent = attachment.name;
+        var item = document.createElement('li');
+        item.appendChild(link);
+        if (attachment.description) {
+            item.append(" " + attachment.description);
+        }
+        if (attachment.page !== null) {
+            item.append(" (page " + attachment.page + ")");
+        }
+        list.appendChild(item);
+    });
+    document.getElementById('attachments').hidden = attachments.length == 0;
+}).catch(() => {
+    // The list stays hidden, the book reads fine without it
+});
+
+// Pages and chapters are downloaded as PDFs of their own
+document.getElementById('extract_pages').action = url;
+document.getElementById('extract_chapter').action = url;
+fetch("http://" + window.location.host + "/api/v1/books/" + book + "/outline").then(function(response) {
+    return response.ok ? response.json() : [];
+}).then(function(entries) {
+    var select = document.querySelector('#extract_chapter select');
+    entries.forEach(function(entry) {
+        var option = document.createElement('option');
+        option.value = entry.number;
+        option.textContent = "\u00a0\u00a0".repeat(entry.level) + entry.title
+            + " (" + (entry.page == entry.last_page ? entry.page : entry.page + "-" + entry.last_page) + ")";
+        select.appendChild(option);
+    });
+    document.getElementById('extract_chapter').hidden = entries.length == 0;
+}).catch(() => {
+    // Chapters cannot be picked, pages still can
+});
+
+// The sizes of the pages are needed to pick the zoom
+var geometryLoaded = fetch("http://" + window.location.host + "/api/v1/books/" + book + "/pages").then(function(response) {
+    return response.ok ? response.json() : [];
+}).then(function(pages) {
+    pageGeometry = pages;
+}).catch(() => {
+    // Every page is taken to be of the default size
+});
+
 /**
 * Asynchronously downloads PDF.
 */
@@ -160,13 +669,19 @@ pdfjsLib.getDocument(url).promise.then(function(pdfDoc_) {
     pdfDoc = pdfDoc_;
     document.getElementById('page_count').textContent = pdfDoc.numPages;
 
-    // Initial/first page rendering
-    renderPage(pageNum);
+    // Initial/first page rendering, once the zoom can be picked
+    geometryLoaded.then(function() {
+        renderPage(pageNum);
+    });
 });
 
 document.onkeydown = checkKey;
 function checkKey(e) {
     e = e || window.event;
+    // Arrow keys move the cursor while typing a search or changing a setting
+    if (e.target.tagName == 'INPUT' || e.target.tagName == 'SELECT') {
+        return;
+    }
 
     if (e.keyCode == '37') {
         onPrevPage();
@@ -0,0 +1,42 @@
+use axum::{
+    body::{Body, HttpBody},
+    extract::{FromRequest, Path, RequestParts},
+    http::Uri,
+    response::IntoResponse,
+};
+use http::{header, Request, StatusCode};
+use pdf_viewer::routes::{
+    error::{ApiError, ErrorBody},
+    v1::unversioned_books,
+};
+
+#[tokio::test]
+async fn unversioned_reads_are_redirected() {
+    let uri: Uri = "/api/books/linear%20algebra/search?q=matrix"
+        .parse()
+        .unwrap();
+    let response = unversioned_books(uri).await.into_response();
+
+    assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(
+        response.headers()[header::LOCATION],
+        "/api/v1/books/linear%20algebra/search?q=matrix"
+    );
+}
+
+#[tokio::test]
+async fn path_rejections_are_error_bodies() {
+    let request = Request::builder()
+        .uri("/api/v1/books/sicp/pages/first/text")
+        .body(Body::empty())
+        .unwrap();
+    let rejection = Path::<(String, u16)>::from_request(&mut RequestParts::new(request))
+        .await
+        .unwrap_err();
+
+    let response = ApiError::from(rejection).into_response();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = response.into_body().data().await.unwrap().unwrap();
+    let body: ErrorBody = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body.error, "bad_request");
+}
@@ -0,0 +1,93 @@
+use std::io::Write;
+
+use flate2::{write::ZlibEncoder, Compression};
+use lopdf::{dictionary, Object, Stream, StringFormat};
+use pdf_viewer::attachments::{list, read};
+
+mod common;
+use common::text_pdf;
+
+#[test]
+fn finds_document_and_page_attachments() {
+    let mut doc = text_pdf(&["", ""]);
+
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    encoder.write_all(b"fn main() {}\n").unwrap();
+    let source = doc.add_object(Stream::new(
+        dictionary! {
+            "Type" => "EmbeddedFile",
+            "Subtype" => "text/x-rust",
+            "Filter" => "FlateDecode",
+            "Params" => dictionary! { "Size" => 13 },
+        },
+        encoder.finish().unwrap(),
+    ));
+    // "Lösung.rs" as UTF-16, inside a directory the name must not keep
+    let mut name = vec![0xfe, 0xff];
+    for unit in "src/Lösung.rs".encode_utf16() {
+        name.extend(unit.to_be_bytes());
+    }
+    let source_spec = doc.add_object(dictionary! {
+        "Type" => "Filespec",
+        "UF" => Object::String(name, StringFormat::Hexadecimal),
+        "Desc" => Object::string_literal("The solution"),
+        "EF" => dictionary! { "F" => source },
+    });
+
+    let data = doc.add_object(Stream::new(
+        dictionary! { "Type" => "EmbeddedFile" },
+        b"x,y\n1,2\n".to_vec(),
+    ));
+    let data_spec = doc.add_object(dictionary! {
+        "Type" => "Filespec",
+        "F" => Object::string_literal("data.csv"),
+        "EF" => dictionary! { "F" => data },
+    });
+
+    let names = doc.add_object(dictionary! {
+        "Names" => vec![Object::string_literal("solution"), source_spec.into()],
+    });
+    let catalog = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+    doc.get_dictionary_mut(catalog)
+        .unwrap()
+        .set("Names", dictionary! { "EmbeddedFiles" => names });
+
+    // Attached to the second page, along with the solution again
+    let annotations: Vec<Object> = [data_spec, source_spec]
+        .into_iter()
+        .map(|spec| {
+            doc.add_object(dictionary! {
+                "Type" => "Annot",
+                "Subtype" => "FileAttachment",
+                "Rect" => vec![0.into(), 0.into(), 10.into(), 10.into()],
+                "FS" => spec,
+            })
+            .into()
+        })
+        .collect();
+    let second = doc.get_pages()[&2];
+    doc.get_dictionary_mut(second)
+        .unwrap()
+        .set("Annots", annotations);
+
+    let attachments = list(&doc);
+    assert_eq!(attachments.len(), 2);
+
+    assert_eq!(attachments[0].number, 1);
+    assert_eq!(attachments[0].name, "Lösung.rs");
+    assert_eq!(attachments[0].description.as_deref(), Some("The solution"));
+    assert_eq!(attachments[0].mime, "text/x-rust");
+    assert_eq!(attachments[0].size, Some(13));
+    assert_eq!(attachments[0].page, None);
+
+    assert_eq!(attachments[1].name, "data.csv");
+    assert_eq!(attachments[1].mime, "text/csv");
+    assert_eq!(attachments[1].size, Some(8));
+    assert_eq!(attachments[1].page, Some(2));
+
+    let (_, contents) = read(&doc, 1).unwrap().unwrap();
+    assert_eq!(contents, b"fn main() {}\n");
+    let (_, contents) = read(&doc, 2).unwrap().unwrap();
+    assert_eq!(contents, b"x,y\n1,2\n");
+    assert!(read(&doc, 3).unwrap().is_none());
+}
@@ -0,0 +1,49 @@
+use std::{cmp::Ordering, fs, io::Write};
+
+use pdf_viewer::{
+    comic::{natural_cmp, Comic},
+    state::Pdf,
+};
+use zip::{write::FileOptions, ZipWriter};
+
+mod common;
+use common::temp_dir;
+
+#[test]
+fn numbers_sort_by_value() {
+    assert_eq!(natural_cmp("page2.jpg", "page10.jpg"), Ordering::Less);
+    assert_eq!(natural_cmp("Page10.jpg", "page9.jpg"), Ordering::Greater);
+    assert_eq!(natural_cmp("002.png", "2.png"), Ordering::Equal);
+    assert_eq!(natural_cmp("ch1/10.png", "ch2/1.png"), Ordering::Less);
+}
+
+#[test]
+fn pages_are_the_sorted_images() {
+    let dir = temp_dir("comic");
+    let path = dir.join("comic.cbz");
+    let mut zip = ZipWriter::new(fs::File::create(&path).unwrap());
+    for name in [
+        "comic/page10.jpg",
+        "comic/page2.PNG",
+        "comic/page1.jpg",
+        "comic/info.txt",
+        "__MACOSX/comic/._page1.jpg",
+    ] {
+        zip.start_file(name, FileOptions::default()).unwrap();
+        zip.write_all(name.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+
+    let comic = Comic::open(&path).unwrap();
+    assert_eq!(
+        comic.pages,
+        ["comic/page1.jpg", "comic/page2.PNG", "comic/page10.jpg"]
+    );
+    let (name, contents) = comic.read_page(3).unwrap();
+    assert_eq!(name, "comic/page10.jpg");
+    assert_eq!(contents, b"comic/page10.jpg");
+    assert!(comic.read_page(4).is_err());
+    assert_eq!(Pdf::get_total_pages(&path), Ok(3));
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,91 @@
+use std::{fs, io::Write, time::Duration};
+
+use image::ImageOutputFormat;
+use pdf_viewer::covers::{CoverCache, THUMBNAIL_SIZE};
+use zip::{write::FileOptions, ZipWriter};
+
+mod common;
+use common::{encoded, temp_dir, write_pdf};
+
+#[test]
+fn pdf_covers_are_scaled_down_and_cached() {
+    let dir = temp_dir("covers-pdf");
+    let book = dir.join("book.pdf");
+    write_pdf(
+        &book,
+        Some((900, 1200, encoded(900, 1200, ImageOutputFormat::Jpeg(90)))),
+    );
+
+    let covers = CoverCache::new(dir.join("covers"));
+    let thumbnail = covers.thumbnail(&book).unwrap().unwrap();
+    let image = image::load_from_memory(&thumbnail).unwrap();
+    assert!(image.width() <= THUMBNAIL_SIZE.0 && image.height() <= THUMBNAIL_SIZE.1);
+    assert_eq!((image.width(), image.height()), (300, 400));
+
+    // The second lookup is served from the cache
+    assert_eq!(fs::read_dir(dir.join("covers")).unwrap().count(), 1);
+    assert_eq!(covers.thumbnail(&book).unwrap(), Some(thumbnail));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn covers_of_changed_books_replace_the_old_ones() {
+    let dir = temp_dir("covers-changed");
+    let book = dir.join("book.pdf");
+    write_pdf(&book, None);
+
+    let covers = CoverCache::new(dir.join("covers"));
+    assert_eq!(covers.thumbnail(&book).unwrap(), None);
+
+    let modified = fs::metadata(&book).unwrap().modified().unwrap();
+    write_pdf(
+        &book,
+        Some((90, 120, encoded(90, 120, ImageOutputFormat::Jpeg(90)))),
+    );
+    fs::File::options()
+        .write(true)
+        .open(&book)
+        .unwrap()
+        .set_modified(modified + Duration::from_secs(1))
+        .unwrap();
+
+    assert!(covers.thumbnail(&book).unwrap().is_some());
+    assert_eq!(fs::read_dir(dir.join("covers")).unwrap().count(), 1);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn books_without_covers_are_remembered() {
+    let dir = temp_dir("covers-none");
+    let book = dir.join("text.pdf");
+    write_pdf(&book, None);
+
+    let covers = CoverCache::new(dir.join("covers"));
+    assert_eq!(covers.thumbnail(&book).unwrap(), None);
+    assert_eq!(covers.thumbnail(&book).unwrap(), None);
+    assert_eq!(fs::read_dir(dir.join("covers")).unwrap().count(), 1);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn comics_use_their_first_page() {
+    let dir = temp_dir("covers-cbz");
+    let book = dir.join("comic.cbz");
+    let mut zip = ZipWriter::new(fs::File::create(&book).unwrap());
+    for (name, width) in [("2.png", 20), ("1.png", 10)] {
+        zip.start_file(name, FileOptions::default()).unwrap();
+        zip.write_all(&encoded(width, 10, ImageOutputFormat::Png))
+            .unwrap();
+    }
+    zip.finish().unwrap();
+
+    let covers = CoverCache::new(dir.join("covers"));
+    let thumbnail = covers.thumbnail(&book).unwrap().unwrap();
+    // Small images are not scaled up
+    assert_eq!(image::load_from_memory(&thumbnail).unwrap().width(), 10);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,28 @@
+use std::{fs, sync::Arc, time::Duration};
+
+use pdf_viewer::documents::DocumentCache;
+
+mod common;
+use common::{temp_dir, write_pdf};
+
+#[test]
+fn documents_are_parsed_once_per_version() {
+    let dir = temp_dir("documents");
+    let book = dir.join("book.pdf");
+    write_pdf(&book, None);
+
+    let documents = DocumentCache::default();
+    let first = documents.get(&book).unwrap();
+    assert!(Arc::ptr_eq(&first, &documents.get(&book).unwrap()));
+
+    let modified = fs::metadata(&book).unwrap().modified().unwrap();
+    fs::File::options()
+        .write(true)
+        .open(&book)
+        .unwrap()
+        .set_modified(modified + Duration::from_secs(1))
+        .unwrap();
+    assert!(!Arc::ptr_eq(&first, &documents.get(&book).unwrap()));
+
+    fs::remove_dir_all(dir).unwrap();
+}
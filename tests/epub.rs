@@ -0,0 +1,104 @@
+use std::{fs, io::Write, path::Path};
+
+use pdf_viewer::{
+    epub::{resolve, Epub},
+    state::Pdf,
+};
+use zip::{write::FileOptions, ZipWriter};
+
+mod common;
+use common::temp_dir;
+
+const CONTAINER: &str = r#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>"#;
+
+const PACKAGE: &str = r#"<?xml version="1.0"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>A Short Book</dc:title>
+  </metadata>
+  <manifest>
+    <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+    <item id="one" href="text/one.xhtml" media-type="application/xhtml+xml"/>
+    <item id="two" href="text/two%20words.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine toc="ncx">
+    <itemref idref="two"/>
+    <itemref idref="one"/>
+  </spine>
+</package>"#;
+
+const NCX: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE ncx PUBLIC "-//NISO//DTD ncx 2005-1//EN" "http://www.daisy.org/z3986/2005/ncx-2005-1.dtd">
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+  <navMap>
+    <navPoint id="p1"><navLabel><text>The  Beginning</text></navLabel><content src="text/two%20words.xhtml#start"/></navPoint>
+  </navMap>
+</ncx>"#;
+
+fn write_epub(path: &Path) {
+    let mut zip = ZipWriter::new(fs::File::create(path).unwrap());
+    let files = [
+        ("mimetype", "application/epub+zip"),
+        ("META-INF/container.xml", CONTAINER),
+        ("OEBPS/content.opf", PACKAGE),
+        ("OEBPS/toc.ncx", NCX),
+        ("OEBPS/text/one.xhtml", "<html><body>one</body></html>"),
+        (
+            "OEBPS/text/two words.xhtml",
+            "<html><body>two</body></html>",
+        ),
+    ];
+    for (name, contents) in files {
+        zip.start_file(name, FileOptions::default()).unwrap();
+        zip.write_all(contents.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+#[test]
+fn chapters_follow_the_spine() {
+    let dir = temp_dir("epub");
+    let path = dir.join("book.epub");
+    write_epub(&path);
+
+    let epub = Epub::open(&path).unwrap();
+    assert_eq!(epub.title.as_deref(), Some("A Short Book"));
+    let chapters: Vec<(&str, &str)> = epub
+        .chapters
+        .iter()
+        .map(|c| (c.file.as_str(), c.title.as_str()))
+        .collect();
+    assert_eq!(
+        chapters,
+        [
+            ("OEBPS/text/two words.xhtml", "The Beginning"),
+            ("OEBPS/text/one.xhtml", "Chapter 2"),
+        ]
+    );
+    assert_eq!(
+        epub.read_file("OEBPS/text/one.xhtml").unwrap(),
+        b"<html><body>one</body></html>"
+    );
+    assert_eq!(Pdf::get_total_pages(&path), Ok(2));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn links_resolve_relative_to_their_file() {
+    assert_eq!(
+        resolve("OEBPS/text/", "../images/a.png"),
+        "OEBPS/images/a.png"
+    );
+    assert_eq!(
+        resolve("OEBPS/", "text/a%20b.xhtml#note"),
+        "OEBPS/text/a b.xhtml"
+    );
+    assert_eq!(resolve("OEBPS/", "/cover.xhtml"), "cover.xhtml");
+    assert_eq!(resolve("", "./a.xhtml"), "a.xhtml");
+}
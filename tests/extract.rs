@@ -0,0 +1,144 @@
+use lopdf::{dictionary, Document, Object, ObjectId};
+use pdf_viewer::{
+    extract::extract,
+    geometry::page_geometry,
+    links::page_links,
+    outline::{items, outline},
+};
+
+mod common;
+use common::text_pdf;
+
+/// The title of an outline item, its page and how high up the page it goes.
+type Entry<'a> = (&'a str, u32, Option<i64>);
+
+/// Adds the outline `entries`, with the children of each, below `parent`.
+fn add_items(doc: &mut Document, parent: ObjectId, entries: &[(Entry, &[Entry])]) -> Vec<ObjectId> {
+    let pages = doc.get_pages();
+    let ids: Vec<ObjectId> = entries.iter().map(|_| doc.new_object_id()).collect();
+    for (i, ((title, page, top), children)) in entries.iter().enumerate() {
+        let top = top.map_or(Object::Null, Object::Integer);
+        let mut item = dictionary! {
+            "Title" => Object::string_literal(*title),
+            "Parent" => parent,
+            "Dest" => vec![pages[page].into(), "XYZ".into(), Object::Null, top, Object::Null],
+        };
+        if i > 0 {
+            item.set("Prev", ids[i - 1]);
+        }
+        if let Some(next) = ids.get(i + 1) {
+            item.set("Next", *next);
+        }
+        doc.objects.insert(ids[i], Object::Dictionary(item));
+
+        let children: Vec<(Entry, &[Entry])> =
+            children.iter().map(|&entry| (entry, &[][..])).collect();
+        if !children.is_empty() {
+            let kids = add_items(doc, ids[i], &children);
+            let item = doc.get_dictionary_mut(ids[i]).unwrap();
+            item.set("First", kids[0]);
+            item.set("Last", kids[kids.len() - 1]);
+            item.set("Count", kids.len() as i64);
+        }
+    }
+    ids
+}
+
+fn book() -> Document {
+    let mut doc = text_pdf(&["", "", "", "", ""]);
+    let root = doc.new_object_id();
+    let top = add_items(
+        &mut doc,
+        root,
+        &[
+            (("One", 1, None), &[("One.A", 2, Some(800))]),
+            (("Two", 3, Some(830)), &[("Two.A", 4, Some(300))]),
+            (("Three", 4, Some(400)), &[]),
+        ],
+    );
+    doc.objects.insert(
+        root,
+        Object::Dictionary(dictionary! {
+            "Type" => "Outlines",
+            "First" => top[0],
+            "Last" => top[2],
+            "Count" => 5,
+        }),
+    );
+
+    let pages = doc.get_pages();
+    let links: Vec<Object> = [4, 1]
+        .into_iter()
+        .map(|page| {
+            doc.add_object(dictionary! {
+                "Type" => "Annot",
+                "Subtype" => "Link",
+                "Rect" => vec![72.into(), 700.into(), 200.into(), 712.into()],
+                "A" => dictionary! {
+                    "S" => "GoTo",
+                    "D" => vec![pages[&page].into(), "Fit".into()],
+                },
+            })
+            .into()
+        })
+        .collect();
+    doc.get_dictionary_mut(pages[&3])
+        .unwrap()
+        .set("Annots", links);
+
+    let catalog = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+    doc.get_dictionary_mut(catalog)
+        .unwrap()
+        .set("Outlines", root);
+    doc
+}
+
+#[test]
+fn chapters_span_until_the_next_one() {
+    let entries: Vec<(String, u16, u16, u16)> = outline(&book())
+        .into_iter()
+        .map(|e| (e.title, e.level, e.page, e.last_page))
+        .collect();
+    assert_eq!(
+        entries,
+        [
+            ("One".to_string(), 0, 1, 2),
+            ("One.A".to_string(), 1, 2, 2),
+            ("Two".to_string(), 0, 3, 4),
+            // Three starts halfway down page 4
+            ("Two.A".to_string(), 1, 4, 4),
+            ("Three".to_string(), 0, 4, 5),
+        ]
+    );
+}
+
+#[test]
+fn extracts_pages_with_their_outline_and_links() {
+    let pdf = extract(book(), 3, 4).unwrap();
+    let doc = Document::load_mem(&pdf).unwrap();
+
+    assert_eq!(doc.get_pages().len(), 2);
+    // The media box was inherited from the old page tree
+    assert!(page_geometry(&doc).iter().all(|g| g.width == 595.0));
+
+    let entries: Vec<(String, u16, u16)> = outline(&doc)
+        .into_iter()
+        .map(|e| (e.title, e.level, e.page))
+        .collect();
+    assert_eq!(
+        entries,
+        [
+            ("Two".to_string(), 0, 1),
+            ("Two.A".to_string(), 1, 2),
+            ("Three".to_string(), 0, 2),
+        ]
+    );
+    assert!(items(&doc)[0].open);
+
+    // The link to page 1 leads out of the extract and is gone
+    let links = page_links(&doc, 1);
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].page, Some(2));
+
+    assert!(extract(book(), 4, 6).is_err());
+}
@@ -0,0 +1,276 @@
+use std::{collections::HashMap, fs};
+
+use axum::{extract::Path, Extension, Json};
+use http::{HeaderMap, HeaderValue};
+use lopdf::{dictionary, Document, Object, ObjectId, Stream};
+use pdf_viewer::{
+    documents::DocumentCache,
+    forms::{check_values, fields, fill, FieldKind, FieldValue},
+    journal::{Journal, JournalEvent},
+    kosync::SyncUsers,
+    persistence::DiscState,
+    routes::forms::put_form_values,
+    state::Pdf,
+};
+
+mod common;
+use common::{temp_dir, text_pdf};
+
+fn rect() -> Vec<Object> {
+    vec![72.into(), 700.into(), 272.into(), 720.into()]
+}
+
+/// A widget for a button with the appearance states `states`.
+fn button_widget(doc: &mut Document, page: ObjectId, states: &[&str]) -> ObjectId {
+    let mut normal = lopdf::Dictionary::new();
+    for state in states.iter().chain(&["Off"]) {
+        let appearance = doc.add_object(Stream::new(dictionary! {}, vec![]));
+        normal.set(*state, appearance);
+    }
+    doc.add_object(dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Widget",
+        "Rect" => rect(),
+        "P" => page,
+        "AP" => dictionary! { "N" => normal },
+    })
+}
+
+/// A worksheet with a text field, a checkbox, radio buttons, a list box,
+/// a nested text field and a push button.
+fn worksheet() -> Document {
+    let mut doc = text_pdf(&["", ""]);
+    let page = doc.get_pages()[&1];
+    let font = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+    });
+
+    let name = doc.add_object(dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Widget",
+        "FT" => "Tx",
+        "T" => Object::string_literal("name"),
+        "MaxLen" => 20,
+        "Rect" => rect(),
+        "P" => page,
+    });
+    let agree = button_widget(&mut doc, page, &["Yes"]);
+    {
+        let agree = doc.get_dictionary_mut(agree).unwrap();
+        agree.set("FT", "Btn");
+        agree.set("T", Object::string_literal("agree"));
+        agree.set("V", "Off");
+    }
+    let small = button_widget(&mut doc, page, &["S"]);
+    let medium = button_widget(&mut doc, page, &["M"]);
+    let size = doc.add_object(dictionary! {
+        "FT" => "Btn",
+        "Ff" => 1 << 15,
+        "T" => Object::string_literal("size"),
+        "Kids" => vec![small.into(), medium.into()],
+    });
+    let topics = doc.add_object(dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Widget",
+        "FT" => "Ch",
+        "Ff" => 1 << 21,
+        "T" => Object::string_literal("topics"),
+        "Opt" => vec![
+            Object::string_literal("sets"),
+            vec![Object::string_literal("logic"), Object::string_literal("Logic")].into(),
+        ],
+        "V" => Object::string_literal("sets"),
+        "Rect" => rect(),
+        "P" => page,
+    });
+    let city = doc.add_object(dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Widget",
+        "T" => Object::string_literal("city"),
+        "Ff" => 1 << 12,
+        "Rect" => rect(),
+        "P" => page,
+    });
+    let address = doc.add_object(dictionary! {
+        "FT" => "Tx",
+        "T" => Object::string_literal("address"),
+        "Kids" => vec![city.into()],
+    });
+    let submit = button_widget(&mut doc, page, &[]);
+    {
+        let submit = doc.get_dictionary_mut(submit).unwrap();
+        submit.set("FT", "Btn");
+        submit.set("Ff", 1 << 16);
+        submit.set("T", Object::string_literal("submit"));
+    }
+
+    let annots: Vec<Object> = [name, agree, small, medium, topics, city, submit]
+        .into_iter()
+        .map(Object::from)
+        .collect();
+    for annot in [small, medium] {
+        doc.get_dictionary_mut(annot).unwrap().set("Parent", size);
+    }
+    doc.get_dictionary_mut(city).unwrap().set("Parent", address);
+    doc.get_dictionary_mut(page).unwrap().set("Annots", annots);
+
+    let form = doc.add_object(dictionary! {
+        "Fields" => vec![name.into(), agree.into(), size.into(), topics.into(), address.into(), submit.into()],
+        "DA" => Object::string_literal("/Helv 0 Tf 0 g"),
+        "DR" => dictionary! { "Font" => dictionary! { "Helv" => font } },
+        "XFA" => Object::string_literal("<xdp/>"),
+    });
+    let catalog = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+    doc.get_dictionary_mut(catalog)
+        .unwrap()
+        .set("AcroForm", form);
+    doc
+}
+
+#[test]
+fn lists_form_fields() {
+    let fields = fields(&worksheet());
+    let summary: Vec<(&str, FieldKind, Option<u16>)> = fields
+        .iter()
+        .map(|f| (f.name.as_str(), f.kind, f.page))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("name", FieldKind::Text, Some(1)),
+            ("agree", FieldKind::Checkbox, Some(1)),
+            ("size", FieldKind::Radio, Some(1)),
+            ("topics", FieldKind::ListBox, Some(1)),
+            ("address.city", FieldKind::Text, Some(1)),
+            ("submit", FieldKind::Button, Some(1)),
+        ]
+    );
+    assert_eq!(fields[0].max_length, Some(20));
+    assert_eq!(fields[1].value, Some(FieldValue::Checked(false)));
+    assert_eq!(fields[2].options, ["S", "M"]);
+    assert_eq!(fields[3].options, ["sets", "logic"]);
+    assert_eq!(
+        fields[3].value,
+        Some(FieldValue::Choices(vec!["sets".into()]))
+    );
+    assert!(fields[4].multiline);
+
+    let check = |name: &str, value: FieldValue| {
+        check_values(&fields, &HashMap::from([(name.to_string(), value)]))
+    };
+    assert!(check("size", FieldValue::Text("M".into())).is_ok());
+    assert!(check("size", FieldValue::Text("XL".into())).is_err());
+    assert!(check("agree", FieldValue::Text("Yes".into())).is_err());
+    assert!(check("name", FieldValue::Text("x".repeat(21))).is_err());
+    assert!(check("submit", FieldValue::Checked(true)).is_err());
+    assert!(check("missing", FieldValue::Checked(true)).is_err());
+}
+
+#[test]
+fn fills_in_forms() {
+    let values = HashMap::from([
+        ("name".to_string(), FieldValue::Text("Ada Lovelace".into())),
+        ("agree".to_string(), FieldValue::Checked(true)),
+        ("size".to_string(), FieldValue::Text("M".into())),
+        (
+            "topics".to_string(),
+            FieldValue::Choices(vec!["logic".into()]),
+        ),
+        (
+            "address.city".to_string(),
+            FieldValue::Text("Zürich\nSwitzerland".into()),
+        ),
+        // Does not fit, so it is left out
+        ("submit".to_string(), FieldValue::Checked(true)),
+    ]);
+    let pdf = fill(worksheet(), &values).unwrap();
+    let doc = Document::load_mem(&pdf).unwrap();
+
+    let filled: HashMap<String, Option<FieldValue>> = fields(&doc)
+        .into_iter()
+        .map(|f| (f.name, f.value))
+        .collect();
+    for (name, value) in values.iter().filter(|(name, _)| *name != "submit") {
+        assert_eq!(filled[name].as_ref(), Some(value), "{name}");
+    }
+    assert_eq!(filled["submit"], None);
+
+    // The widgets show the new values
+    let widgets: Vec<&lopdf::Dictionary> = doc
+        .get_dictionary(doc.get_pages()[&1])
+        .unwrap()
+        .get(b"Annots")
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|a| doc.get_dictionary(a.as_reference().unwrap()).unwrap())
+        .collect();
+    let state = |widget: &lopdf::Dictionary| widget.get(b"AS").unwrap().as_name().unwrap().to_vec();
+    assert_eq!(state(widgets[1]), b"Yes");
+    assert_eq!(
+        (state(widgets[2]), state(widgets[3])),
+        (b"Off".to_vec(), b"M".to_vec())
+    );
+    for text in [widgets[0], widgets[5]] {
+        let appearance = text.get(b"AP").unwrap().as_dict().unwrap();
+        let stream = doc
+            .get_object(appearance.get(b"N").unwrap().as_reference().unwrap())
+            .unwrap()
+            .as_stream()
+            .unwrap();
+        let content = String::from_utf8_lossy(&stream.content);
+        assert!(content.contains("/Helv"), "{content}");
+    }
+
+    let catalog = doc.catalog().unwrap();
+    let form = doc
+        .get_dictionary(catalog.get(b"AcroForm").unwrap().as_reference().unwrap())
+        .unwrap();
+    assert!(form.get(b"NeedAppearances").unwrap().as_bool().unwrap());
+    assert!(!form.has(b"XFA"));
+}
+
+#[tokio::test]
+async fn values_are_journaled_under_the_name_of_the_book() {
+    let dir = temp_dir("forms-journal");
+    let content = dir.join("content");
+    fs::create_dir_all(&content).unwrap();
+    worksheet().save(content.join("worksheet.pdf")).unwrap();
+
+    let mut pdfs = DiscState::new().pdfs;
+    pdfs.add_book(Pdf::with_total_pages(content.join("worksheet.pdf"), 2));
+    let mut users = SyncUsers::default();
+    users.register("ada", "key");
+    let mut headers = HeaderMap::new();
+    headers.insert("x-auth-user", HeaderValue::from_static("ada"));
+    headers.insert("x-auth-key", HeaderValue::from_static("key"));
+    let path = dir.join("journal");
+    let (journal, _) = Journal::open(path.clone(), 0).unwrap();
+
+    put_form_values(
+        Path("worksheet.pdf".into()),
+        headers,
+        Ok(Json(HashMap::from([(
+            "name".to_string(),
+            FieldValue::Text("Ada".into()),
+        )]))),
+        Extension(pdfs.wrapped()),
+        Extension(vec![content]),
+        Extension(DocumentCache::default()),
+        Extension(users.wrapped()),
+        Extension(journal.wrapped()),
+    )
+    .await
+    .unwrap();
+
+    let (_, pending) = Journal::open(path, 0).unwrap();
+    assert!(matches!(
+        &pending[0].event,
+        JournalEvent::FormValues { book, .. } if book == "worksheet"
+    ));
+
+    fs::remove_dir_all(dir).unwrap();
+}
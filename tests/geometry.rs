@@ -0,0 +1,45 @@
+use lopdf::Object;
+use pdf_viewer::{geometry::page_geometry, text::Rect};
+
+mod common;
+use common::text_pdf;
+
+#[test]
+fn pages_inherit_boxes_and_rotation() {
+    let mut doc = text_pdf(&["", ""]);
+    let second = doc.get_pages()[&2];
+    let page = doc.get_object_mut(second).unwrap().as_dict_mut().unwrap();
+    page.set("Rotate", Object::Integer(-90));
+    // Reaches past the media box on the right
+    page.set(
+        "CropBox",
+        vec![50.into(), 842.into(), 700.into(), 42.into()],
+    );
+
+    let pages = page_geometry(&doc);
+    assert_eq!(pages.len(), 2);
+
+    let a4 = Rect {
+        left: 0.0,
+        bottom: 0.0,
+        right: 595.0,
+        top: 842.0,
+    };
+    assert_eq!(pages[0].media_box, a4);
+    assert_eq!(pages[0].crop_box, a4);
+    assert_eq!(pages[0].rotation, 0);
+    assert_eq!((pages[0].width, pages[0].height), (595.0, 842.0));
+
+    assert_eq!(pages[1].page, 2);
+    assert_eq!(
+        pages[1].crop_box,
+        Rect {
+            left: 50.0,
+            bottom: 42.0,
+            right: 595.0,
+            top: 842.0,
+        }
+    );
+    assert_eq!(pages[1].rotation, 270);
+    assert_eq!((pages[1].width, pages[1].height), (800.0, 545.0));
+}
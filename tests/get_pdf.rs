@@ -0,0 +1,146 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use pdf_viewer::{
+    persistence::DiscState,
+    routes::{error::ApiError, get_pdf::resolve_pdf},
+    state::PdfCollection,
+};
+use serde_json::{json, Map, Value};
+
+mod common;
+use common::temp_dir;
+
+const PDF: &[u8] = b"%PDF-1.4\n%%EOF\n";
+
+/// A content directory and a directory next to it which is not part of the library.
+struct Library {
+    root: PathBuf,
+    content: PathBuf,
+    outside: PathBuf,
+}
+
+impl Library {
+    fn new(test: &str) -> Self {
+        let root = temp_dir(test);
+        let content = root.join("content");
+        let outside = root.join("outside");
+        fs::create_dir_all(&content).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+
+        Library {
+            root,
+            content,
+            outside,
+        }
+    }
+
+    /// A collection with the books `name -> path`, as it would be read from a state file.
+    fn collection(&self, books: &[(&str, &Path)]) -> PdfCollection {
+        let pdfs: Map<String, Value> = books
+            .iter()
+            .map(|(name, path)| {
+                let pdf = json!({
+                    "last_access": "Never",
+                    "name": name,
+                    "path": path,
+                    "current_page": 1,
+                    "total_pages": 1,
+                });
+                (name.to_string(), pdf)
+            })
+            .collect();
+        let state = json!({
+            "pdfs": { "pdfs": pdfs },
+            "reading_history": { "events": [] },
+        });
+
+        DiscState::parse(&state.to_string()).unwrap().pdfs
+    }
+
+    fn dirs(&self) -> Vec<PathBuf> {
+        vec![self.content.clone()]
+    }
+}
+
+impl Drop for Library {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+#[test]
+fn resolves_registered_books() {
+    let lib = Library::new("resolve");
+    let book = lib.content.join("book.pdf");
+    fs::write(&book, PDF).unwrap();
+    let pdfs = lib.collection(&[("book", &book)]);
+
+    let resolved = resolve_pdf(&pdfs, &lib.dirs(), "book.pdf").unwrap();
+    assert_eq!(resolved, fs::canonicalize(&book).unwrap());
+    assert!(resolve_pdf(&pdfs, &lib.dirs(), "book").is_ok());
+}
+
+#[test]
+fn rejects_unregistered_names() {
+    let lib = Library::new("unregistered");
+    fs::write(lib.content.join("book.pdf"), PDF).unwrap();
+    fs::write(lib.outside.join("secret.pdf"), PDF).unwrap();
+    let pdfs = lib.collection(&[]);
+
+    for name in [
+        "book.pdf",
+        "../outside/secret.pdf",
+        "..%2Foutside%2Fsecret.pdf",
+        "/etc/passwd",
+        lib.outside.join("secret.pdf").to_str().unwrap(),
+    ] {
+        assert!(
+            matches!(
+                resolve_pdf(&pdfs, &lib.dirs(), name),
+                Err(ApiError::NotFound(_))
+            ),
+            "{name} was resolved"
+        );
+    }
+}
+
+#[test]
+fn rejects_books_outside_the_content_dirs() {
+    let lib = Library::new("outside");
+    let secret = lib.outside.join("secret.pdf");
+    fs::write(&secret, PDF).unwrap();
+    let dotted = lib.content.join("../outside/secret.pdf");
+    let pdfs = lib.collection(&[("secret", &secret), ("dotted", &dotted)]);
+
+    assert!(resolve_pdf(&pdfs, &lib.dirs(), "secret.pdf").is_err());
+    assert!(resolve_pdf(&pdfs, &lib.dirs(), "dotted.pdf").is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn rejects_symlinks_out_of_the_content_dirs() {
+    let lib = Library::new("symlink");
+    let secret = lib.outside.join("secret.pdf");
+    fs::write(&secret, PDF).unwrap();
+    let link = lib.content.join("link.pdf");
+    std::os::unix::fs::symlink(&secret, &link).unwrap();
+    let pdfs = lib.collection(&[("link", &link)]);
+
+    assert!(resolve_pdf(&pdfs, &lib.dirs(), "link.pdf").is_err());
+}
+
+#[test]
+fn rejects_files_which_are_not_pdfs() {
+    let lib = Library::new("not-pdf");
+    let fake = lib.content.join("fake.pdf");
+    fs::write(&fake, "#!/bin/sh\n").unwrap();
+    let notes = lib.content.join("notes.txt");
+    fs::write(&notes, PDF).unwrap();
+    let pdfs = lib.collection(&[("fake", &fake), ("notes", &notes)]);
+
+    assert!(resolve_pdf(&pdfs, &lib.dirs(), "fake.pdf").is_err());
+    assert!(resolve_pdf(&pdfs, &lib.dirs(), "notes").is_err());
+}
@@ -0,0 +1,284 @@
+use std::{collections::HashMap, fs};
+
+use pdf_viewer::{
+    forms::FieldValue,
+    journal::{Journal, JournalEvent},
+    persistence::DiscState,
+    state::{ReaderPreferences, ZoomMode},
+};
+
+mod common;
+use common::temp_dir;
+
+const V2: &str = include_str!("fixtures/state_v2.json");
+
+#[tokio::test]
+async fn replays_entries_newer_than_the_snapshot() {
+    let dir = temp_dir("journal");
+    let path = Journal::location_for(&dir.join("state.json"));
+
+    let (mut journal, pending) = Journal::open(path.clone(), 0).unwrap();
+    assert!(pending.is_empty());
+    journal
+        .record(JournalEvent::SetPage {
+            book: "sicp".into(),
+            page: 50,
+            offset: 0.0,
+            device: None,
+        })
+        .await;
+    journal.record(JournalEvent::Read).await;
+    journal
+        .record(JournalEvent::SetPage {
+            book: "sicp".into(),
+            page: 51,
+            offset: 0.5,
+            device: Some("phone".into()),
+        })
+        .await;
+    drop(journal);
+
+    // Pretend the first entry already made it into a snapshot and that the
+    // last write got cut short by a crash.
+    let mut contents = fs::read_to_string(&path).unwrap();
+    contents.push_str("{\"seq\":4,\"ti");
+    fs::write(&path, contents).unwrap();
+
+    let (journal, pending) = Journal::open(path, 1).unwrap();
+    assert_eq!(pending.len(), 2);
+    assert_eq!(journal.seq(), 3);
+
+    let mut state = DiscState::parse(V2).unwrap();
+    state.reading_history.update();
+    let reads = state.reading_history.last_day();
+    for entry in pending {
+        entry.apply(&mut state);
+    }
+
+    let sicp = state.pdfs.get_book_by_name(&"sicp").unwrap();
+    assert_eq!(sicp.current_page(), 51);
+    assert_eq!(sicp.history().len(), 1);
+    assert_eq!(sicp.history()[0].device.as_deref(), Some("phone"));
+    assert_eq!(state.journal_seq, 3);
+    state.reading_history.update();
+    assert_eq!(state.reading_history.last_day(), reads + 1);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn entries_after_a_torn_line_survive_the_next_restart() {
+    let dir = temp_dir("journal-torn");
+    let path = Journal::location_for(&dir.join("state.json"));
+
+    let (mut journal, _) = Journal::open(path.clone(), 0).unwrap();
+    journal.record(JournalEvent::Read).await;
+    drop(journal);
+    // A crash in the middle of writing the second entry
+    let mut torn = fs::read(&path).unwrap();
+    torn.extend_from_slice(br#"{"seq":2,"time":"#);
+    fs::write(&path, torn).unwrap();
+
+    let (mut journal, pending) = Journal::open(path.clone(), 0).unwrap();
+    assert_eq!(pending.len(), 1);
+    journal.record(JournalEvent::Read).await;
+    drop(journal);
+
+    let (_, pending) = Journal::open(path, 0).unwrap();
+    let seqs: Vec<u64> = pending.iter().map(|e| e.seq).collect();
+    assert_eq!(seqs, [1, 2]);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn replays_uploads_with_their_progress() {
+    let dir = temp_dir("uploads");
+    let path = Journal::location_for(&dir.join("state.json"));
+
+    let (mut journal, _) = Journal::open(path.clone(), 0).unwrap();
+    journal
+        .record(JournalEvent::Add {
+            path: "content/notes.epub".into(),
+            total_pages: 12,
+        })
+        .await;
+    journal
+        .record(JournalEvent::SetPage {
+            book: "notes".into(),
+            page: 5,
+            offset: 0.0,
+            device: None,
+        })
+        .await;
+    drop(journal);
+
+    let (_, pending) = Journal::open(path, 0).unwrap();
+    let mut state = DiscState::parse(V2).unwrap();
+    for entry in pending {
+        entry.apply(&mut state);
+    }
+
+    let notes = state.pdfs.get_book_by_name(&"notes").unwrap();
+    assert_eq!(notes.total_pages(), 12);
+    assert_eq!(notes.current_page(), 5);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn replays_renames_and_deletions() {
+    let dir = temp_dir("manage");
+    let path = Journal::location_for(&dir.join("state.json"));
+
+    let (mut journal, _) = Journal::open(path.clone(), 0).unwrap();
+    journal
+        .record(JournalEvent::Relocate {
+            book: "sicp".into(),
+            path: "other/wizard.pdf".into(),
+        })
+        .await;
+    journal
+        .record(JournalEvent::Trash {
+            book: "wizard".into(),
+            file: "other/.trash/wizard.pdf".into(),
+        })
+        .await;
+    journal
+        .record(JournalEvent::Restore {
+            book: "wizard".into(),
+        })
+        .await;
+    journal
+        .record(JournalEvent::Trash {
+            book: "bok".into(),
+            file: "content/.trash/bok.pdf".into(),
+        })
+        .await;
+    drop(journal);
+
+    let (_, pending) = Journal::open(path, 0).unwrap();
+    let mut state = DiscState::parse(V2).unwrap();
+    let page = state.pdfs.get_book_by_name(&"sicp").unwrap().current_page();
+    for entry in pending {
+        entry.apply(&mut state);
+    }
+
+    assert!(state.pdfs.get_book_by_name(&"sicp").is_none());
+    let wizard = state.pdfs.get_book_by_name(&"wizard").unwrap();
+    assert_eq!(wizard.path(), std::path::Path::new("other/wizard.pdf"));
+    assert_eq!(wizard.current_page(), page);
+
+    assert!(state.pdfs.get_book_by_name(&"bok").is_none());
+    assert_eq!(
+        state.pdfs.trash["bok"].file,
+        std::path::Path::new("content/.trash/bok.pdf")
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn replays_viewer_settings() {
+    let dir = temp_dir("preferences");
+    let path = Journal::location_for(&dir.join("state.json"));
+
+    let preferences = ReaderPreferences {
+        zoom: ZoomMode::FitPage,
+        rotation: 90,
+        invert: true,
+        ..ReaderPreferences::default()
+    };
+    assert!(preferences.validate().is_ok());
+    assert!(ReaderPreferences {
+        rotation: 45,
+        ..ReaderPreferences::default()
+    }
+    .validate()
+    .is_err());
+
+    let own = ReaderPreferences {
+        spread: true,
+        ..ReaderPreferences::default()
+    };
+
+    let (mut journal, _) = Journal::open(path.clone(), 0).unwrap();
+    journal
+        .record(JournalEvent::Preferences {
+            book: "sicp".into(),
+            user: None,
+            preferences: preferences.clone(),
+        })
+        .await;
+    journal
+        .record(JournalEvent::Preferences {
+            book: "sicp".into(),
+            user: Some("ada".into()),
+            preferences: own.clone(),
+        })
+        .await;
+    drop(journal);
+
+    let (_, pending) = Journal::open(path, 0).unwrap();
+    let mut state = DiscState::parse(V2).unwrap();
+    for entry in pending {
+        entry.apply(&mut state);
+    }
+
+    let sicp = state.pdfs.get_book_by_name(&"sicp").unwrap();
+    assert_eq!(sicp.preferences(None), &preferences);
+    assert_eq!(sicp.preferences(Some("ada")), &own);
+    // Everyone else falls back to the settings of the book
+    assert_eq!(sicp.preferences(Some("bob")), &preferences);
+    let bok = state.pdfs.get_book_by_name(&"bok").unwrap();
+    assert_eq!(bok.preferences(Some("ada")), &ReaderPreferences::default());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn replays_form_values_per_user() {
+    let dir = temp_dir("forms");
+    let path = Journal::location_for(&dir.join("state.json"));
+
+    let values = HashMap::from([
+        ("name".to_string(), FieldValue::Text("Ada".into())),
+        ("agree".to_string(), FieldValue::Checked(true)),
+    ]);
+    let (mut journal, _) = Journal::open(path.clone(), 0).unwrap();
+    journal
+        .record(JournalEvent::FormValues {
+            book: "sicp".into(),
+            user: "ada".into(),
+            values: values.clone(),
+        })
+        .await;
+    journal
+        .record(JournalEvent::FormValues {
+            book: "sicp".into(),
+            user: "bob".into(),
+            values: HashMap::from([("name".to_string(), FieldValue::Text("Bob".into()))]),
+        })
+        .await;
+    // An empty form is forgotten
+    journal
+        .record(JournalEvent::FormValues {
+            book: "sicp".into(),
+            user: "bob".into(),
+            values: HashMap::new(),
+        })
+        .await;
+    drop(journal);
+
+    let (_, pending) = Journal::open(path, 0).unwrap();
+    let mut state = DiscState::parse(V2).unwrap();
+    for entry in pending {
+        entry.apply(&mut state);
+    }
+
+    let sicp = state.pdfs.get_book_by_name(&"sicp").unwrap();
+    assert_eq!(sicp.form_values("ada"), values);
+    assert!(sicp.form_values("bob").is_empty());
+
+    fs::remove_dir_all(dir).unwrap();
+}
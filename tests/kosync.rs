@@ -0,0 +1,160 @@
+use std::fs;
+
+use axum::{Extension, Json};
+use http::HeaderMap;
+use pdf_viewer::{
+    devices::DeviceRegistry,
+    journal::Journal,
+    kosync::{
+        document_hashes, file_name_md5, library_paths, partial_md5, DocumentIndex, SyncSettings,
+        SyncUsers,
+    },
+    persistence::DiscState,
+    routes::{
+        error::ApiError, events::event_channel, kosync::create_user, set_page::move_book,
+        stats::ReadingStatistics,
+    },
+    state::Pdf,
+};
+use serde_json::json;
+
+mod common;
+use common::temp_dir;
+
+#[test]
+fn small_files_hash_like_plain_md5() {
+    let dir = temp_dir("kosync");
+    let path = dir.join("book.pdf");
+    fs::write(&path, b"%PDF-1.4\n%%EOF\n").unwrap();
+
+    assert_eq!(
+        partial_md5(&path).unwrap(),
+        format!("{:x}", md5::compute(b"%PDF-1.4\n%%EOF\n"))
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn samples_at_growing_offsets() {
+    let dir = temp_dir("kosync-big");
+    let path = dir.join("book.pdf");
+    let contents: Vec<u8> = (0..16_884u32).map(|i| (i % 251) as u8).collect();
+    fs::write(&path, &contents).unwrap();
+
+    // The samples at 0, 1 KiB and 4 KiB are whole, the one at 16 KiB is cut short
+    // by the end of the file and there is nothing left at 64 KiB
+    let mut expected = md5::Context::new();
+    for offset in [0, 1024, 4096, 16384] {
+        expected.consume(&contents[offset..(offset + 1024).min(contents.len())]);
+    }
+    assert_eq!(
+        partial_md5(&path).unwrap(),
+        format!("{:x}", expected.compute())
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn finds_books_by_file_name_hash() {
+    let state = DiscState::parse(include_str!("fixtures/state_v7.json")).unwrap();
+    let books = library_paths(&state.pdfs);
+    let mut index = DocumentIndex::default();
+    assert_eq!(index.outdated(&books), books);
+    for (name, path) in index.outdated(&books) {
+        let hashes = document_hashes(&path);
+        index.insert(name, path, hashes);
+    }
+    assert!(index.outdated(&books).is_empty());
+
+    let hash = file_name_md5("content/sicp.pdf".as_ref()).unwrap();
+    assert_eq!(index.lookup(&books, &hash).as_deref(), Some("sicp"));
+    assert_eq!(index.lookup(&books, "0123456789abcdef"), None);
+}
+
+#[test]
+fn users_need_the_right_key() {
+    let mut users = SyncUsers::default();
+    assert!(users.register("reader", "5f4dcc3b5aa765d61d8327deb882cf99"));
+    assert!(!users.register("reader", "other"));
+
+    assert!(users.authorize("reader", "5f4dcc3b5aa765d61d8327deb882cf99"));
+    assert!(!users.authorize("reader", "other"));
+    assert!(!users.authorize("someone", "5f4dcc3b5aa765d61d8327deb882cf99"));
+}
+
+#[tokio::test]
+async fn registration_is_closed_by_default() {
+    let dir = temp_dir("kosync-register");
+    let (journal, _) = Journal::open(dir.join("journal"), 0).unwrap();
+    let journal = journal.wrapped();
+    let users = SyncUsers::default().wrapped();
+    let credentials = || {
+        Ok(Json(
+            serde_json::from_value(json!({
+                "username": "reader",
+                "password": "5f4dcc3b5aa765d61d8327deb882cf99",
+            }))
+            .unwrap(),
+        ))
+    };
+
+    let closed = create_user(
+        credentials(),
+        Extension(users.clone()),
+        Extension(SyncSettings::default()),
+        Extension(journal.clone()),
+    )
+    .await;
+    assert!(matches!(closed, Err(ApiError::Forbidden(_))));
+    assert!(users.lock().await.users.is_empty());
+
+    let open = create_user(
+        credentials(),
+        Extension(users.clone()),
+        Extension(SyncSettings {
+            open_registration: true,
+        }),
+        Extension(journal),
+    )
+    .await;
+    assert!(open.is_ok());
+    assert!(users
+        .lock()
+        .await
+        .authorize("reader", "5f4dcc3b5aa765d61d8327deb882cf99"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn updates_without_a_revision_always_apply() {
+    let dir = temp_dir("kosync-race");
+    let (journal, _) = Journal::open(dir.join("journal"), 0).unwrap();
+    let journal = journal.wrapped();
+    let mut pdfs = DiscState::new().pdfs;
+    pdfs.add_book(Pdf::with_total_pages(dir.join("book.pdf"), 10));
+    let pdfs = pdfs.wrapped();
+    let stats = ReadingStatistics::wrapped();
+    let devices = DeviceRegistry::default().wrapped();
+    let events = event_channel();
+    let headers = HeaderMap::new();
+    let move_to = |page, revision| {
+        move_book(
+            "book", page, 0.0, revision, &headers, &pdfs, &stats, &devices, &journal, &events,
+        )
+    };
+
+    assert_eq!(move_to(2, Some(0)).await.unwrap().revision, 1);
+    // Another update got in first, clients which know about revisions are told
+    assert!(matches!(
+        move_to(3, Some(0)).await,
+        Err(ApiError::Conflict(_))
+    ));
+    // KOReader does not, its update wins
+    let progress = move_to(4, None).await.unwrap();
+    assert_eq!((progress.page, progress.revision), (4, 2));
+
+    fs::remove_dir_all(dir).unwrap();
+}
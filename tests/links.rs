@@ -0,0 +1,94 @@
+use lopdf::{dictionary, Dictionary, Object};
+use pdf_viewer::links::{destination, destinations, page_links};
+
+mod common;
+use common::text_pdf;
+
+fn link(rect: [i64; 4], target: (&str, Object)) -> Dictionary {
+    let mut annotation = dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Link",
+        "Rect" => rect.iter().map(|&n| n.into()).collect::<Vec<Object>>(),
+    };
+    annotation.set(target.0, target.1);
+    annotation
+}
+
+#[test]
+fn resolves_links_and_named_destinations() {
+    let mut doc = text_pdf(&["", "", ""]);
+    let pages = doc.get_pages();
+    let third: Object = pages[&3].into();
+
+    let xyz = vec![
+        third.clone(),
+        "XYZ".into(),
+        0.into(),
+        700.into(),
+        Object::Null,
+    ];
+    let old_style =
+        doc.add_object(dictionary! { "chapter2" => vec![pages[&2].into(), "Fit".into()] });
+    let tree = doc.add_object(dictionary! {
+        "Names" => vec![
+            Object::string_literal("loop.a"), Object::string_literal("loop.b"),
+            Object::string_literal("loop.b"), Object::string_literal("loop.a"),
+            Object::string_literal("section3"), dictionary! { "D" => xyz.clone() }.into(),
+        ],
+    });
+    let catalog = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+    let catalog = doc.get_dictionary_mut(catalog).unwrap();
+    catalog.set("Dests", old_style);
+    catalog.set("Names", dictionary! { "Dests" => tree });
+
+    let annotations: Vec<Object> = [
+        link([72, 700, 200, 712], ("Dest", Object::Name(b"chapter2".to_vec()))),
+        link(
+            [72, 680, 200, 692],
+            ("A", dictionary! { "S" => "GoTo", "D" => Object::string_literal("section3") }.into()),
+        ),
+        link(
+            [72, 660, 200, 672],
+            ("A", dictionary! { "S" => "URI", "URI" => Object::string_literal("https://example.com/") }.into()),
+        ),
+        link(
+            [72, 640, 200, 652],
+            ("A", dictionary! { "S" => "Named", "N" => "NextPage" }.into()),
+        ),
+        // Go nowhere, so they are left out
+        link(
+            [72, 620, 200, 632],
+            ("A", dictionary! { "S" => "Named", "N" => "PrevPage" }.into()),
+        ),
+        link([72, 600, 200, 612], ("Dest", Object::string_literal("loop.a"))),
+    ]
+    .into_iter()
+    .map(|annotation| doc.add_object(annotation).into())
+    .collect();
+    doc.get_dictionary_mut(pages[&1])
+        .unwrap()
+        .set("Annots", annotations);
+
+    let links = page_links(&doc, 1);
+    assert_eq!(links.len(), 4);
+    assert_eq!((links[0].page, links[0].top), (Some(2), None));
+    assert_eq!(links[0].rect.left, 72.0);
+    assert_eq!(links[0].rect.top, 712.0);
+    assert_eq!((links[1].page, links[1].top), (Some(3), Some(700.0)));
+    assert_eq!(links[2].page, None);
+    assert_eq!(links[2].uri.as_deref(), Some("https://example.com/"));
+    assert_eq!(links[3].page, Some(2));
+    assert!(page_links(&doc, 2).is_empty());
+
+    let names: Vec<(String, u16)> = destinations(&doc)
+        .into_iter()
+        .map(|d| (d.name, d.page))
+        .collect();
+    assert_eq!(
+        names,
+        [("chapter2".to_string(), 2), ("section3".to_string(), 3)]
+    );
+    assert_eq!(destination(&doc, "section3").unwrap().top, Some(700.0));
+    assert!(destination(&doc, "loop.a").is_none());
+    assert!(destination(&doc, "missing").is_none());
+}
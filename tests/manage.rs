@@ -0,0 +1,88 @@
+use std::fs;
+
+use axum::{extract::Path, Extension, Json};
+use pdf_viewer::{
+    devices::DeviceRegistry,
+    journal::Journal,
+    persistence::DiscState,
+    routes::{
+        events::event_channel,
+        manage::{delete_book, restore_book, update_book, BookChange, TRASH_DIR},
+    },
+    state::{DocumentKind, Pdf},
+    variants::VariantCache,
+};
+
+mod common;
+use common::temp_dir;
+
+#[tokio::test]
+async fn epubs_keep_their_extension_when_renamed_and_trashed() {
+    let dir = temp_dir("manage-epub");
+    let content = dir.join("content");
+    fs::create_dir_all(&content).unwrap();
+    fs::write(content.join("novel.epub"), b"PK\x03\x04").unwrap();
+
+    let mut pdfs = DiscState::new().pdfs;
+    pdfs.add_book(Pdf::with_total_pages(content.join("novel.epub"), 12));
+    let pdfs = pdfs.wrapped();
+    let devices = DeviceRegistry::default().wrapped();
+    let variants = VariantCache::new(dir.join("variants"));
+    let dirs = vec![content.clone()];
+    let (journal, _) = Journal::open(dir.join("journal"), 0).unwrap();
+    let journal = journal.wrapped();
+    let events = event_channel();
+
+    let change = BookChange {
+        name: Some("story".into()),
+        collection: None,
+    };
+    update_book(
+        Path("novel.epub".into()),
+        Ok(Json(change)),
+        Extension(pdfs.clone()),
+        Extension(devices.clone()),
+        Extension(variants.clone()),
+        Extension(dirs.clone()),
+        Extension(journal.clone()),
+        Extension(events.clone()),
+    )
+    .await
+    .unwrap();
+    let story = content.join("story.epub");
+    assert!(story.exists());
+    let book = pdfs.lock().await.get_book_by_name(&"story").unwrap();
+    assert_eq!(book.path(), &story);
+    assert_eq!(book.kind(), DocumentKind::Epub);
+
+    delete_book(
+        Path("story".into()),
+        Extension(pdfs.clone()),
+        Extension(variants),
+        Extension(dirs.clone()),
+        Extension(journal.clone()),
+        Extension(events.clone()),
+    )
+    .await
+    .unwrap();
+    let trashed = content.join(TRASH_DIR).join("story.epub");
+    assert!(trashed.exists());
+    assert!(!story.exists());
+
+    restore_book(
+        Path("story.epub".into()),
+        Extension(pdfs.clone()),
+        Extension(devices),
+        Extension(dirs),
+        Extension(journal),
+        Extension(events),
+    )
+    .await
+    .unwrap();
+    assert!(story.exists());
+    let book = pdfs.lock().await.get_book_by_name(&"story").unwrap();
+    assert_eq!(book.path(), &story);
+    assert_eq!(book.kind(), DocumentKind::Epub);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,143 @@
+use std::path::PathBuf;
+
+use axum::{extract::Path, response::IntoResponse, Extension};
+use pdf_viewer::{
+    persistence::DiscState,
+    routes::opds::{all_books, collection_books},
+    state::WrappedPdfCollection,
+};
+use serde_json::json;
+
+/// Two books in the "course" collection and one outside of it.
+fn library() -> (WrappedPdfCollection, Vec<PathBuf>) {
+    let book = |name: &str, path: &str, page: u16| {
+        json!({
+            "last_access": "Never",
+            "name": name,
+            "path": path,
+            "current_page": page,
+            "total_pages": 100,
+        })
+    };
+    let state = json!({
+        "pdfs": { "pdfs": {
+            "linear algebra": book("linear algebra", "/library/course/linear algebra.pdf", 20),
+            "notes": book("notes", "/library/course/notes.epub", 1),
+            "novel": book("novel", "/library/other/novel.pdf", 1),
+        }},
+        "reading_history": { "events": [] },
+    });
+
+    let pdfs = DiscState::parse(&state.to_string()).unwrap().pdfs.wrapped();
+    let dirs = vec![
+        PathBuf::from("/library/course"),
+        PathBuf::from("/library/other"),
+    ];
+    (pdfs, dirs)
+}
+
+async fn body(response: impl IntoResponse) -> String {
+    let bytes = hyper::body::to_bytes(response.into_response().into_body())
+        .await
+        .unwrap();
+    String::from_utf8(bytes.to_vec()).unwrap()
+}
+
+/// `(rel, href, type)` of every link of an entry.
+fn links(entry: roxmltree::Node) -> Vec<(String, String, String)> {
+    entry
+        .children()
+        .filter(|n| n.has_tag_name("link"))
+        .map(|n| {
+            let attr = |name| n.attribute(name).unwrap_or_default().to_string();
+            (attr("rel"), attr("href"), attr("type"))
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn feed_lists_books_with_acquisition_and_cover_links() {
+    let (pdfs, dirs) = library();
+    let xml = body(all_books(Extension(pdfs), Extension(dirs)).await.unwrap()).await;
+    let doc = roxmltree::Document::parse(&xml).unwrap();
+
+    let entries: Vec<_> = doc
+        .root_element()
+        .children()
+        .filter(|n| n.has_tag_name("entry"))
+        .collect();
+    let titles: Vec<_> = entries
+        .iter()
+        .map(|e| {
+            e.children()
+                .find(|n| n.has_tag_name("title"))
+                .unwrap()
+                .text()
+        })
+        .collect();
+    assert_eq!(
+        titles,
+        [Some("linear algebra"), Some("notes"), Some("novel")]
+    );
+
+    let algebra = links(entries[0]);
+    for expected in [
+        (
+            "http://opds-spec.org/acquisition",
+            "/get_pdf/linear%20algebra.pdf",
+            "application/pdf",
+        ),
+        (
+            "http://opds-spec.org/image",
+            "/api/v1/books/linear%20algebra/cover",
+            "image/jpeg",
+        ),
+        (
+            "http://opds-spec.org/image/thumbnail",
+            "/api/v1/books/linear%20algebra/cover",
+            "image/jpeg",
+        ),
+    ] {
+        let expected = (
+            expected.0.to_string(),
+            expected.1.to_string(),
+            expected.2.to_string(),
+        );
+        assert!(algebra.contains(&expected), "{expected:?} missing");
+    }
+
+    let notes = links(entries[1]);
+    assert!(notes.contains(&(
+        "http://opds-spec.org/acquisition".into(),
+        "/get_pdf/notes.epub".into(),
+        "application/epub+zip".into(),
+    )));
+}
+
+#[tokio::test]
+async fn collection_feed_only_holds_its_books() {
+    let (pdfs, dirs) = library();
+    let response = collection_books(Path("course".into()), Extension(pdfs), Extension(dirs))
+        .await
+        .unwrap();
+    let xml = body(response).await;
+    let doc = roxmltree::Document::parse(&xml).unwrap();
+
+    let ids: Vec<_> = doc
+        .descendants()
+        .filter(|n| n.has_tag_name("entry"))
+        .filter_map(|e| e.children().find(|n| n.has_tag_name("id")))
+        .filter_map(|n| n.text())
+        .collect();
+    assert_eq!(
+        ids,
+        [
+            "urn:pdf-viewer:book:linear%20algebra",
+            "urn:pdf-viewer:book:notes"
+        ]
+    );
+    assert!(doc
+        .descendants()
+        .filter(|n| n.has_tag_name("category"))
+        .all(|n| n.attribute("term") == Some("course")));
+}
@@ -0,0 +1,95 @@
+use std::fs;
+
+use axum::{extract::Path, Extension, Json};
+use http::{HeaderMap, HeaderValue};
+use pdf_viewer::{
+    journal::{Journal, JournalEvent},
+    persistence::DiscState,
+    routes::{
+        error::ApiError,
+        preferences::{get_preferences, put_preferences},
+    },
+    state::{ReaderPreferences, ZoomMode},
+};
+
+mod common;
+use common::temp_dir;
+
+fn signed_in(user: &str, key: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert("x-auth-user", HeaderValue::from_str(user).unwrap());
+    headers.insert("x-auth-key", HeaderValue::from_str(key).unwrap());
+    headers
+}
+
+#[tokio::test]
+async fn sync_users_keep_their_own_settings() {
+    let dir = temp_dir("preferences-users");
+    let (journal, _) = Journal::open(dir.join("journal"), 0).unwrap();
+    let journal = journal.wrapped();
+    let state = DiscState::parse(include_str!("fixtures/state_v10.json")).unwrap();
+    let pdfs = state.pdfs.wrapped();
+    let users = state.sync_users.wrapped();
+    let reader = || signed_in("reader", "5f4dcc3b5aa765d61d8327deb882cf99");
+    let get = |headers| {
+        get_preferences(
+            Path("sicp".into()),
+            headers,
+            Extension(pdfs.clone()),
+            Extension(users.clone()),
+        )
+    };
+
+    let own = ReaderPreferences {
+        rotation: 180,
+        ..ReaderPreferences::default()
+    };
+    put_preferences(
+        Path("sicp".into()),
+        reader(),
+        Ok(Json(own.clone())),
+        Extension(pdfs.clone()),
+        Extension(users.clone()),
+        Extension(journal.clone()),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(get(reader()).await.unwrap().0, own);
+    // Without credentials the settings of the book are used, those did not change
+    assert_eq!(
+        get(HeaderMap::new()).await.unwrap().0.zoom,
+        ZoomMode::FitWidth
+    );
+    let wrong = get(signed_in("reader", "wrong")).await;
+    assert!(matches!(wrong, Err(ApiError::Unauthorized(_))));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn settings_are_journaled_under_the_name_of_the_book() {
+    let dir = temp_dir("preferences-journal");
+    let path = dir.join("journal");
+    let (journal, _) = Journal::open(path.clone(), 0).unwrap();
+    let state = DiscState::parse(include_str!("fixtures/state_v10.json")).unwrap();
+
+    put_preferences(
+        Path("sicp.pdf".into()),
+        HeaderMap::new(),
+        Ok(Json(ReaderPreferences::default())),
+        Extension(state.pdfs.wrapped()),
+        Extension(state.sync_users.wrapped()),
+        Extension(journal.wrapped()),
+    )
+    .await
+    .unwrap();
+
+    let (_, pending) = Journal::open(path, 0).unwrap();
+    assert!(matches!(
+        &pending[0].event,
+        JournalEvent::Preferences { book, .. } if book == "sicp"
+    ));
+
+    fs::remove_dir_all(dir).unwrap();
+}
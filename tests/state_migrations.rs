@@ -0,0 +1,202 @@
+use std::fs;
+
+use pdf_viewer::{
+    persistence::{DiscState, STATE_VERSION},
+    state::{ReaderPreferences, ZoomMode},
+};
+
+mod common;
+use common::temp_dir;
+
+const V0: &str = include_str!("fixtures/state_v0.json");
+const V1: &str = include_str!("fixtures/state_v1.json");
+const V2: &str = include_str!("fixtures/state_v2.json");
+const V3: &str = include_str!("fixtures/state_v3.json");
+const V4: &str = include_str!("fixtures/state_v4.json");
+const V5: &str = include_str!("fixtures/state_v5.json");
+const V6: &str = include_str!("fixtures/state_v6.json");
+const V7: &str = include_str!("fixtures/state_v7.json");
+const V8: &str = include_str!("fixtures/state_v8.json");
+const V9: &str = include_str!("fixtures/state_v9.json");
+const V10: &str = include_str!("fixtures/state_v10.json");
+const V11: &str = include_str!("fixtures/state_v11.json");
+
+#[test]
+fn migrates_v0_flat_page_map() {
+    let state = DiscState::parse(V0).unwrap();
+    assert_eq!(state.version, STATE_VERSION);
+    assert_eq!(state.pdfs.pdfs.len(), 4);
+
+    let sicp = state.pdfs.get_book_by_name(&"sicp.pdf").unwrap();
+    assert_eq!(sicp.name(), "sicp");
+    assert_eq!(sicp.current_page(), 48);
+    assert_eq!(sicp.total_pages(), 0);
+
+    let bok = state.pdfs.get_book_by_name(&"bok").unwrap();
+    assert_eq!(bok.current_page(), 457);
+}
+
+#[test]
+fn migrates_v1_unversioned_state() {
+    let state = DiscState::parse(V1).unwrap();
+    assert_eq!(state.version, STATE_VERSION);
+
+    let sicp = state.pdfs.get_book_by_name(&"sicp").unwrap();
+    assert_eq!(sicp.current_page(), 48);
+    assert_eq!(sicp.total_pages(), 883);
+    assert_eq!(sicp.last_access().to_string(), "2023-07-02 18:21:40");
+    assert_eq!(state.reading_history.last_day(), 1);
+}
+
+#[test]
+fn migrates_v2_state_without_journal() {
+    let state = DiscState::parse(V2).unwrap();
+    assert_eq!(state.version, STATE_VERSION);
+    assert_eq!(state.journal_seq, 0);
+    assert_eq!(
+        state.pdfs.get_book_by_name(&"bok").unwrap().total_pages(),
+        612
+    );
+}
+
+#[test]
+fn migrates_v3_state_without_history() {
+    let state = DiscState::parse(V3).unwrap();
+    assert_eq!(state.version, STATE_VERSION);
+
+    let sicp = state.pdfs.get_book_by_name(&"sicp").unwrap();
+    assert_eq!(sicp.current_page(), 48);
+    assert!(sicp.history().is_empty());
+}
+
+#[test]
+fn migrates_v4_state_without_revisions() {
+    let state = DiscState::parse(V4).unwrap();
+    assert_eq!(state.version, STATE_VERSION);
+
+    let sicp = state.pdfs.get_book_by_name(&"sicp").unwrap();
+    assert_eq!(sicp.revision(), 0);
+    assert_eq!(sicp.history().len(), 1);
+}
+
+#[test]
+fn migrates_v5_state_without_devices() {
+    let state = DiscState::parse(V5).unwrap();
+    assert_eq!(state.version, STATE_VERSION);
+    assert!(state.devices.devices.is_empty());
+
+    let sicp = state.pdfs.get_book_by_name(&"sicp").unwrap();
+    assert_eq!(sicp.revision(), 3);
+    assert!(sicp.device_positions().is_empty());
+    assert!(sicp.last_read().is_none());
+}
+
+#[test]
+fn migrates_v6_state_without_trash() {
+    let state = DiscState::parse(V6).unwrap();
+    assert_eq!(state.version, STATE_VERSION);
+    assert!(state.pdfs.trash.is_empty());
+
+    let sicp = state.pdfs.get_book_by_name(&"sicp").unwrap();
+    assert_eq!(
+        sicp.last_read().map(|(device, _)| device.as_str()),
+        Some("phone")
+    );
+    assert_eq!(state.devices.name("phone"), "Phone");
+}
+
+#[test]
+fn migrates_v7_state_without_sync_users() {
+    let state = DiscState::parse(V7).unwrap();
+    assert_eq!(state.version, STATE_VERSION);
+    assert!(state.sync_users.users.is_empty());
+    assert_eq!(state.pdfs.pdfs.len(), 2);
+}
+
+#[test]
+fn migrates_v8_state_without_offsets() {
+    let state = DiscState::parse(V8).unwrap();
+    assert_eq!(state.version, STATE_VERSION);
+    assert!(state
+        .sync_users
+        .authorize("reader", "5f4dcc3b5aa765d61d8327deb882cf99"));
+
+    let sicp = state.pdfs.get_book_by_name(&"sicp").unwrap();
+    assert_eq!(sicp.progress().offset, 0.0);
+    assert_eq!(sicp.history()[0].offset, 0.0);
+    assert_eq!(state.pdfs.trash["lotr"].book.progress().offset, 0.0);
+}
+
+#[test]
+fn migrates_v9_state_without_preferences() {
+    let state = DiscState::parse(V9).unwrap();
+    assert_eq!(state.version, STATE_VERSION);
+
+    let sicp = state.pdfs.get_book_by_name(&"sicp").unwrap();
+    assert_eq!(sicp.preferences(None), &ReaderPreferences::default());
+    assert_eq!(
+        state.pdfs.trash["bok"].book.preferences(None),
+        &ReaderPreferences::default()
+    );
+}
+
+#[test]
+fn migrates_v10_state_without_form_values() {
+    let state = DiscState::parse(V10).unwrap();
+    assert_eq!(state.version, STATE_VERSION);
+
+    let sicp = state.pdfs.get_book_by_name(&"sicp").unwrap();
+    assert_eq!(sicp.preferences(None).zoom, ZoomMode::FitWidth);
+    assert!(sicp.form_values("reader").is_empty());
+    assert!(state.pdfs.trash["bok"]
+        .book
+        .form_values("reader")
+        .is_empty());
+}
+
+#[test]
+fn migrates_v11_state_without_user_preferences() {
+    let state = DiscState::parse(V11).unwrap();
+    assert_eq!(state.version, STATE_VERSION);
+
+    // Users without settings of their own see the ones of the book
+    let sicp = state.pdfs.get_book_by_name(&"sicp").unwrap();
+    assert_eq!(sicp.preferences(Some("reader")).zoom, ZoomMode::FitWidth);
+    assert_eq!(sicp.form_values("reader").len(), 1);
+    assert_eq!(
+        state.pdfs.trash["bok"].book.preferences(Some("reader")),
+        &ReaderPreferences::default()
+    );
+}
+
+#[test]
+fn current_version_round_trips() {
+    let state = DiscState::parse(V1).unwrap();
+    let written = serde_json::to_string(&state).unwrap();
+    let reread = DiscState::parse(&written).unwrap();
+
+    assert_eq!(reread.version, STATE_VERSION);
+    assert_eq!(reread.pdfs.pdfs.len(), state.pdfs.pdfs.len());
+}
+
+#[test]
+fn rejects_newer_versions() {
+    let newer = format!(r#"{{"version": {}}}"#, STATE_VERSION + 1);
+    assert!(DiscState::parse(&newer).is_err());
+}
+
+#[test]
+fn load_keeps_a_backup_of_old_state() {
+    let dir = temp_dir("migrate");
+    let path = dir.join("state.json");
+    fs::write(&path, V0).unwrap();
+
+    let state = DiscState::load(&path).unwrap();
+    assert_eq!(state.pdfs.pdfs.len(), 4);
+    assert_eq!(
+        fs::read_to_string(dir.join("state.json.v0.bak")).unwrap(),
+        V0
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
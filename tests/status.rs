@@ -0,0 +1,42 @@
+use axum::{body::HttpBody, extract::Path, Extension};
+use http::{header, HeaderMap, HeaderValue, StatusCode};
+use pdf_viewer::{devices::DeviceRegistry, persistence::DiscState, routes::status::status};
+
+fn accepting(mime: &'static str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::ACCEPT, HeaderValue::from_static(mime));
+    headers
+}
+
+#[tokio::test]
+async fn status_is_json_unless_plain_text_is_asked_for() {
+    let state = DiscState::parse(include_str!("fixtures/state_v10.json")).unwrap();
+    let pdfs = state.pdfs.wrapped();
+    let devices = DeviceRegistry::default().wrapped();
+    let get = |book: &str, headers| {
+        status(
+            Path(book.to_string()),
+            headers,
+            Extension(pdfs.clone()),
+            Extension(devices.clone()),
+        )
+    };
+
+    let response = get("sicp", HeaderMap::new()).await.unwrap();
+    let body = response.into_body().data().await.unwrap().unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["page"], 48);
+    assert!(json["devices"].as_array().unwrap().is_empty());
+
+    let response = get("sicp", accepting("text/plain")).await.unwrap();
+    let body = response.into_body().data().await.unwrap().unwrap();
+    assert_eq!(&body[..], b"48");
+    let response = get("missing", accepting("text/plain")).await.unwrap();
+    let body = response.into_body().data().await.unwrap().unwrap();
+    assert_eq!(&body[..], b"-1");
+
+    let error = get("missing", accepting("application/json, text/plain"))
+        .await
+        .unwrap_err();
+    assert_eq!(error.status_code(), StatusCode::NOT_FOUND);
+}
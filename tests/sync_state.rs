@@ -0,0 +1,99 @@
+use std::{fs, path::Path};
+
+use pdf_viewer::{
+    journal::Journal,
+    persistence::{sync_state, DiscState},
+    routes::{events::event_channel, stats::ReadingStatistics},
+    state::{Pdf, PdfCollection},
+};
+use serde_json::json;
+
+mod common;
+use common::{temp_dir, write_pdf};
+
+/// Runs one round of `sync_state` over `dir/content` and returns the resulting books.
+async fn sync(dir: &Path, pdfs: PdfCollection) -> PdfCollection {
+    let state = DiscState::new();
+    let (journal, _) = Journal::open(dir.join("journal"), 0).unwrap();
+    let pdfs = pdfs.wrapped();
+
+    sync_state(
+        vec![dir.join("content")],
+        dir.join("state.json"),
+        pdfs.clone(),
+        ReadingStatistics::wrapped(),
+        state.devices.wrapped(),
+        state.sync_users.wrapped(),
+        journal.wrapped(),
+        event_channel(),
+    )
+    .await
+    .unwrap();
+
+    let pdfs = pdfs.lock().await.clone();
+    pdfs
+}
+
+#[tokio::test]
+async fn books_keyed_by_their_first_dot_keep_their_progress() {
+    let dir = temp_dir("sync-dotted");
+    let content = dir.join("content");
+    fs::create_dir_all(&content).unwrap();
+    let path = content.join("algebra.2nd.pdf");
+    write_pdf(&path, None);
+
+    let state = json!({
+        "pdfs": { "pdfs": { "algebra": {
+            "last_access": { "Once": "2023-07-02 18:21:40" },
+            "name": "algebra",
+            "path": path,
+            "current_page": 1,
+            "total_pages": 1,
+        }}},
+        "reading_history": { "events": [] },
+    });
+    let pdfs = DiscState::parse(&state.to_string()).unwrap().pdfs;
+
+    let pdfs = sync(&dir, pdfs).await;
+    assert_eq!(pdfs.pdfs.len(), 1);
+    let book = pdfs.get_book_by_name(&"algebra.2nd").unwrap();
+    assert_eq!(book.path(), &path);
+    assert!(book.last_access().time().is_some());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn files_with_the_same_stem_are_not_mixed_up() {
+    let dir = temp_dir("sync-stems");
+    let content = dir.join("content");
+    fs::create_dir_all(&content).unwrap();
+    let pdf = content.join("notes.pdf");
+    write_pdf(&pdf, None);
+    let mut pdfs = DiscState::new().pdfs;
+    pdfs.add_book(Pdf::with_total_pages(pdf.clone(), 1));
+
+    // Not a valid EPUB, it must not even be opened
+    fs::write(content.join("notes.epub"), "not a zip").unwrap();
+
+    let pdfs = sync(&dir, pdfs).await;
+    assert_eq!(pdfs.pdfs.len(), 1);
+    assert_eq!(pdfs.get_book_by_name(&"notes").unwrap().path(), &pdf);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn broken_archives_are_skipped() {
+    let dir = temp_dir("sync-broken");
+    let content = dir.join("content");
+    fs::create_dir_all(&content).unwrap();
+    write_pdf(&content.join("good.pdf"), None);
+    fs::write(content.join("broken.cbz"), "not a zip").unwrap();
+
+    let pdfs = sync(&dir, DiscState::new().pdfs).await;
+    assert_eq!(pdfs.pdfs.len(), 1);
+    assert!(pdfs.get_book_by_name(&"good").is_some());
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,122 @@
+use lopdf::{dictionary, Stream};
+use pdf_viewer::text::{page_lines, paragraphs, search};
+
+mod common;
+use common::text_pdf;
+
+const PAGE: &str = "BT /F1 12 Tf
+72 700 Td [(Reading)-600(order)] TJ
+0 -14 Td (is hard to get right, espe-) Tj
+0 -14 Td (cially with columns.) Tj
+0 -40 Td (A new paragraph.) Tj
+ET
+BT /F1 12 Tf 300 800 Td (world) Tj ET
+BT /F1 12 Tf 72 800 Td (Hello) Tj ET";
+
+#[test]
+fn text_comes_out_in_reading_order() {
+    let doc = text_pdf(&[PAGE]);
+    let lines = page_lines(&doc, doc.get_pages()[&1]).unwrap();
+
+    let texts: Vec<&str> = lines.iter().map(|l| l.text.as_str()).collect();
+    assert_eq!(
+        texts,
+        [
+            "Hello world",
+            "Reading order",
+            "is hard to get right, espe-",
+            "cially with columns.",
+            "A new paragraph.",
+        ]
+    );
+    assert!(lines
+        .iter()
+        .all(|l| l.glyphs.len() == l.text.chars().count()));
+    // Helvetica has no widths in the PDF, every glyph is taken to be half the size
+    assert_eq!(lines[0].glyphs[0], (72.0, 78.0));
+
+    assert_eq!(
+        paragraphs(&lines),
+        [
+            "Hello world",
+            "Reading order is hard to get right, especially with columns.",
+            "A new paragraph.",
+        ]
+    );
+}
+
+/// A heading over two columns of three lines each, and a footer across both.
+const COLUMNS: &str = "BT /F1 16 Tf 72 780 Td (Two columns) Tj ET
+BT /F1 10 Tf 72 740 Td 12 TL
+(Left one) Tj T* (left two) Tj T* (left three) Tj ET
+BT /F1 10 Tf 320 740 Td 12 TL
+(Right one) Tj T* (right two) Tj T* (right three) Tj ET
+BT /F1 10 Tf 72 600 Td (A footer which runs across the gutter into the right column) Tj ET";
+
+#[test]
+fn columns_are_read_one_after_the_other() {
+    let doc = text_pdf(&[COLUMNS]);
+    let lines = page_lines(&doc, doc.get_pages()[&1]).unwrap();
+
+    let texts: Vec<&str> = lines.iter().map(|l| l.text.as_str()).collect();
+    assert_eq!(
+        texts,
+        [
+            "Two columns",
+            "Left one",
+            "left two",
+            "left three",
+            "Right one",
+            "right two",
+            "right three",
+            "A footer which runs across the gutter into the right column",
+        ]
+    );
+}
+
+#[test]
+fn search_finds_hits_across_lines() {
+    let doc = text_pdf(&[PAGE]);
+    let lines = page_lines(&doc, doc.get_pages()[&1]).unwrap();
+
+    let hits = search(&lines, "HARD to");
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].before, "Hello world Reading order is ");
+    assert_eq!(hits[0].text, "hard to");
+    assert_eq!(hits[0].after, " get right, especially with columns. A n");
+    assert_eq!(hits[0].boxes.len(), 1);
+    assert_eq!(hits[0].boxes[0].left, 72.0 + 3.0 * 6.0);
+
+    // Hyphenated at the end of a line, so the hit is on two lines
+    let hits = search(&lines, "especially");
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].boxes.len(), 2);
+    assert!(hits[0].boxes[0].bottom > hits[0].boxes[1].bottom);
+
+    assert!(search(&lines, "paragraphs").is_empty());
+    assert!(search(&lines, "  ").is_empty());
+}
+
+#[test]
+fn cmap_ranges_at_the_end_of_the_code_space_do_not_overflow() {
+    let mut doc = text_pdf(&["BT /F1 12 Tf 72 700 Td (AB) Tj ET"]);
+    let cmap = "begincmap
+2 beginbfrange
+<FFFFFFFF> <FFFFFFFF> [<0061> <0062> <0063>]
+<FFFFFFF0> <FFFFFFFF> <0061>
+endbfrange
+1 beginbfrange
+<41> <42> [<0058> <0059>]
+endbfrange
+endcmap";
+    let cmap = doc.add_object(Stream::new(dictionary! {}, cmap.as_bytes().to_vec()));
+    let (_, font) = doc
+        .objects
+        .iter_mut()
+        .find(|(_, o)| o.as_dict().is_ok_and(|d| d.has(b"BaseFont")))
+        .unwrap();
+    font.as_dict_mut().unwrap().set("ToUnicode", cmap);
+
+    let lines = page_lines(&doc, doc.get_pages()[&1]).unwrap();
+    assert_eq!(lines[0].text, "XY");
+}
@@ -0,0 +1,38 @@
+use pdf_viewer::routes::upload::sanitize_file_name;
+
+#[test]
+fn keeps_plain_pdf_names() {
+    assert_eq!(sanitize_file_name("book.pdf").unwrap(), "book.pdf");
+    assert_eq!(sanitize_file_name(" my book.pdf ").unwrap(), "my book.pdf");
+}
+
+#[test]
+fn lowers_the_case_of_extensions() {
+    assert_eq!(sanitize_file_name("Book.PDF").unwrap(), "Book.pdf");
+    assert_eq!(sanitize_file_name("Comic.Cbz").unwrap(), "Comic.cbz");
+}
+
+#[test]
+fn strips_client_paths() {
+    assert_eq!(sanitize_file_name("../../book.pdf").unwrap(), "book.pdf");
+    assert_eq!(
+        sanitize_file_name(r"C:\Users\me\book.pdf").unwrap(),
+        "book.pdf"
+    );
+}
+
+#[test]
+fn rejects_other_names() {
+    for name in [
+        "",
+        "book",
+        "book.txt",
+        ".pdf",
+        "../",
+        "a.b.pdf",
+        "book.pdf/",
+        "bo\nok.pdf",
+    ] {
+        assert!(sanitize_file_name(name).is_err(), "{name:?} was accepted");
+    }
+}
@@ -0,0 +1,100 @@
+use std::{fs, time::Duration};
+
+use image::ImageOutputFormat;
+use lopdf::{dictionary, Document, Object, Stream};
+use pdf_viewer::variants::{Variant, VariantCache};
+
+mod common;
+use common::{encoded, temp_dir, write_pdf};
+
+#[test]
+fn mobile_variants_are_smaller() {
+    let dir = temp_dir("variants");
+    let book = dir.join("scan.pdf");
+    write_pdf(
+        &book,
+        Some((2000, 2000, encoded(2000, 2000, ImageOutputFormat::Jpeg(95)))),
+    );
+
+    // An uncompressed content stream and an object nothing uses
+    let mut doc = Document::load(&book).unwrap();
+    let page = doc.get_pages()[&1];
+    let text = "BT /F1 12 Tf 72 712 Td (Hello) Tj ET\n".repeat(200);
+    let contents = doc.add_object(Stream::new(dictionary! {}, text.into_bytes()));
+    doc.get_dictionary_mut(page)
+        .unwrap()
+        .set("Contents", contents);
+    doc.add_object(Object::string_literal("unused"));
+    doc.save(&book).unwrap();
+    let objects = doc.objects.len();
+
+    let variants = VariantCache::new(dir.join("variants"));
+    let mobile = variants.get(&book, Variant::Mobile).unwrap();
+    assert_ne!(mobile, book);
+    assert!(fs::metadata(&mobile).unwrap().len() < fs::metadata(&book).unwrap().len());
+
+    let reduced = Document::load(&mobile).unwrap();
+    assert_eq!(reduced.objects.len(), objects - 1);
+    let image = reduced
+        .objects
+        .values()
+        .filter_map(|o| o.as_stream().ok())
+        .find(|s| s.dict.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Image"))
+        .unwrap();
+    // An A4 page is 842 points high, which is 1754 pixels at 150 DPI
+    assert_eq!(image.dict.get(b"Height").unwrap().as_i64().unwrap(), 1754);
+    let contents = reduced.get_page_contents(reduced.get_pages()[&1])[0];
+    assert!(reduced
+        .get_object(contents)
+        .unwrap()
+        .as_stream()
+        .unwrap()
+        .dict
+        .has(b"Filter"));
+
+    // Served from the cache the second time
+    assert_eq!(variants.get(&book, Variant::Mobile).unwrap(), mobile);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn variants_which_are_no_smaller_are_skipped() {
+    let dir = temp_dir("variants-same");
+    let book = dir.join("tiny.pdf");
+    write_pdf(
+        &book,
+        Some((10, 10, encoded(10, 10, ImageOutputFormat::Jpeg(50)))),
+    );
+
+    let variants = VariantCache::new(dir.join("variants"));
+    assert_eq!(variants.get(&book, Variant::Mobile).unwrap(), book);
+    assert_eq!(variants.get(&book, Variant::Mobile).unwrap(), book);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn variants_of_old_versions_are_removed() {
+    let dir = temp_dir("variants-stale");
+    let book = dir.join("tiny.pdf");
+    write_pdf(&book, None);
+
+    let variants = VariantCache::new(dir.join("variants"));
+    variants.get(&book, Variant::Mobile).unwrap();
+
+    let modified = fs::metadata(&book).unwrap().modified().unwrap();
+    fs::File::options()
+        .write(true)
+        .open(&book)
+        .unwrap()
+        .set_modified(modified + Duration::from_secs(1))
+        .unwrap();
+    variants.get(&book, Variant::Mobile).unwrap();
+    assert_eq!(fs::read_dir(dir.join("variants")).unwrap().count(), 1);
+
+    variants.forget(&book);
+    assert_eq!(fs::read_dir(dir.join("variants")).unwrap().count(), 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}